@@ -13,6 +13,7 @@ self_cell!(
 
 pub type Ast<'a> = Vec<&'a str>;
 
+#[cfg(not(feature = "stable_deref"))]
 self_cell!(
     pub struct StringCell {
         owner: String,
@@ -21,3 +22,15 @@ self_cell!(
         dependent: Ast,
     }
 );
+
+// Stores the String inline, saving the heap allocation for the cell and one
+// pointer indirection per access.
+#[cfg(feature = "stable_deref")]
+self_cell!(
+    pub struct StringCell {
+        owner: String,
+
+        #[covariant, stable_deref]
+        dependent: Ast,
+    }
+);
@@ -22,7 +22,7 @@ self_cell!(
         dependent: LazyAstCell,
     }
 
-    impl {Debug, PartialEq, Eq, Hash}
+    impl {Clone(with = |_| OnceCell::new()), Debug, PartialEq, Eq, Hash}
 );
 
 impl LazyAst {
@@ -41,12 +41,6 @@ impl LazyAst {
     }
 }
 
-impl Clone for LazyAst {
-    fn clone(&self) -> Self {
-        Self::new(self.borrow_owner().clone(), |_| OnceCell::new())
-    }
-}
-
 fn main() {
     let lazy_ast = LazyAst::new("a[i * x[y]] * sin(z)".into(), |_| OnceCell::new());
 
@@ -46,10 +46,9 @@
 //! to compile due to its use of procedural macros.
 //!
 //! This alternative is `no_std`, uses no proc-macros, some self contained
-//! unsafe and works on stable Rust, and is miri tested. With a total of less
-//! than 300 lines of implementation code, which consists mostly of type and
-//! trait implementations, this crate aims to be a good minimal solution to the
-//! problem of self-referential structs.
+//! unsafe and works on stable Rust, and is miri tested. Its implementation
+//! code consists mostly of type and trait implementations, and this crate aims
+//! to be a good minimal solution to the problem of self-referential structs.
 //!
 //! It has undergone [community code
 //! review](https://users.rust-lang.org/t/experimental-safe-to-use-proc-macro-free-self-referential-structs-in-stable-rust/52775)
@@ -64,8 +63,8 @@
 //! Completed self_cell v0.7.0 in 0.2s
 //! ```
 //!
-//! Because it does **not** use proc-macros, and has 0 dependencies
-//! compile-times are fast.
+//! Because it does **not** use proc-macros, and has no dependencies unless
+//! optional features like `allocator_api2` are enabled, compile-times are fast.
 //!
 //! Measurements done on a slow laptop.
 //!
@@ -130,6 +129,11 @@
 //! makes it safe to move the generated SelfCell but you have to pay for the
 //! heap allocation.
 //!
+//! Alternatively `#[covariant, pinned]` stores owner and dependent inline. Such
+//! a cell is built in place behind `Pin<&mut Self>`, e.g. via
+//! `core::pin::pin!`, and works without an allocator. Disabling the default
+//! `alloc` feature removes the heap based API, leaving only pinned cells.
+//!
 //! See the documentation for [`self_cell`] to dive further into the details.
 //!
 //! Or take a look at the advanced examples:
@@ -157,12 +161,24 @@
 
 #![no_std]
 
+#[cfg(feature = "alloc")]
 #[doc(hidden)]
 pub extern crate alloc;
 
+#[cfg(feature = "std")]
+#[doc(hidden)]
+pub extern crate std;
+
+#[cfg(feature = "serde")]
+#[doc(hidden)]
+pub extern crate serde as _serde;
+
 #[doc(hidden)]
 pub mod unsafe_self_cell;
 
+#[cfg(all(feature = "serde", feature = "alloc"))]
+pub mod serde;
+
 /// This macro declares a new struct of `$StructName` and implements traits
 /// based on `$AutomaticDerive`.
 ///
@@ -214,6 +230,37 @@ pub mod unsafe_self_cell;
 /// ) -> Result<Self, ($Owner, Err)>
 /// ```
 ///
+/// ```ignore
+/// // Returns the owner instead of calling handle_alloc_error if allocating fails.
+/// fn try_new_alloc(
+///     owner: $Owner,
+///     dependent_builder: impl for<'a> ::core::ops::FnOnce(&'a $Owner) -> $Dependent<'a>
+/// ) -> Result<Self, ($Owner, self_cell::AllocError)>
+/// ```
+///
+/// ```ignore
+/// fn try_new_or_recover_alloc<Err>(
+///     owner: $Owner,
+///     dependent_builder: impl for<'a> ::core::ops::FnOnce(&'a $Owner) -> Result<$Dependent<'a>, Err>
+/// ) -> Result<Self, ($Owner, self_cell::TryNewError<Err>)>
+/// ```
+///
+/// ```ignore
+/// // The error may borrow from owner, E names its type, see BorrowedError.
+/// fn try_new_borrowed_err<E: for<'a> self_cell::BorrowedError<'a>>(
+///     owner: $Owner,
+///     dependent_builder: impl for<'a> ::core::ops::FnOnce(&'a $Owner) -> Result<$Dependent<'a>, <E as BorrowedError<'a>>::Error>
+/// ) -> Result<Self, self_cell::ErrorCell<$Owner, E>>
+/// ```
+///
+/// ```ignore
+/// // Requires the std feature, not available with async_builder.
+/// fn new_catch_unwind(
+///     owner: $Owner,
+///     dependent_builder: impl for<'a> ::core::ops::FnOnce(&'a $Owner) -> $Dependent<'a>
+/// ) -> Result<Self, ($Owner, Box<dyn Any + Send>)>
+/// ```
+///
 /// The macro implements these methods:
 ///
 /// ```ignore
@@ -243,6 +290,212 @@ pub mod unsafe_self_cell;
 /// fn into_owner(self) -> $Owner
 /// ```
 ///
+/// ```ignore
+/// // Reuses the allocation, returns the previous owner. Aborts should
+/// // dependent_builder panic.
+/// fn replace_owner(
+///     &mut self,
+///     owner: $Owner,
+///     dependent_builder: impl for<'a> ::core::ops::FnOnce(&'a $Owner) -> $Dependent<'a>
+/// ) -> $Owner
+/// ```
+///
+/// ```ignore
+/// // Consumes self, so that it can be dropped on error or panic.
+/// fn try_replace_owner<Err>(
+///     self,
+///     owner: $Owner,
+///     dependent_builder: impl for<'a> ::core::ops::FnOnce(&'a $Owner) -> Result<$Dependent<'a>, Err>
+/// ) -> Result<(Self, $Owner), Err>
+/// ```
+///
+/// ```ignore
+/// // Returns `(previous owner, owner, error)` on error, in that order.
+/// fn try_replace_owner_or_recover<Err>(
+///     self,
+///     owner: $Owner,
+///     dependent_builder: impl for<'a> ::core::ops::FnOnce(&'a $Owner) -> Result<$Dependent<'a>, Err>
+/// ) -> Result<(Self, $Owner), ($Owner, $Owner, Err)>
+/// ```
+///
+/// ```ignore
+/// // Drops the dependent, edits owner and rebuilds the dependent. Aborts
+/// // should edit or dependent_builder panic.
+/// fn with_owner_mut(
+///     &mut self,
+///     edit: impl ::core::ops::FnOnce(&mut $Owner),
+///     dependent_builder: impl for<'a> ::core::ops::FnOnce(&'a $Owner) -> $Dependent<'a>
+/// )
+/// ```
+///
+/// `try_with_owner_mut` and `try_with_owner_mut_or_recover` take a fallible
+/// `dependent_builder` like `try_new` and `try_new_or_recover`. Like the
+/// `try_replace_owner` family they consume and return `self`, a cell without
+/// dependent can't be handed back, so on error or panic it is dropped instead.
+///
+/// With `#[$Covariance:ident, stable_deref]` the macro implements the same
+/// functions as without it, but the `dependent_builder` of `new`, `try_new`
+/// and `try_new_or_recover` borrows the `Deref` target of owner:
+///
+/// ```ignore
+/// fn new(
+///     owner: $Owner,
+///     dependent_builder: impl for<'a> ::core::ops::FnOnce(&'a <$Owner as Deref>::Target) -> $Dependent<'a>
+/// ) -> Self
+/// ```
+///
+/// With `#[$Covariance:ident, shared]` the macro implements `new`, `try_new`,
+/// `try_new_or_recover`, `borrow_owner`, `borrow_dependent` and
+/// `with_dependent` as above, `Clone` and instead of `into_owner`:
+///
+/// ```ignore
+/// // Only succeeds if self is the only handle, otherwise returns self.
+/// fn try_into_owner(self) -> Result<$Owner, Self>
+/// ```
+///
+/// With `#[$Covariance:ident, pinned]` the macro instead implements:
+///
+/// ```ignore
+/// fn uninit() -> Self
+/// ```
+///
+/// ```ignore
+/// fn is_initialized(&self) -> bool
+/// ```
+///
+/// ```ignore
+/// // Panics if already initialized.
+/// fn init(
+///     self: Pin<&mut Self>,
+///     owner: $Owner,
+///     dependent_builder: impl for<'a> ::core::ops::FnOnce(&'a $Owner) -> $Dependent<'a>
+/// )
+/// ```
+///
+/// ```ignore
+/// fn try_init<Err>(
+///     self: Pin<&mut Self>,
+///     owner: $Owner,
+///     dependent_builder: impl for<'a> ::core::ops::FnOnce(&'a $Owner) -> Result<$Dependent<'a>, Err>
+/// ) -> Result<(), Err>
+/// ```
+///
+/// ```ignore
+/// fn try_init_or_recover<Err>(
+///     self: Pin<&mut Self>,
+///     owner: $Owner,
+///     dependent_builder: impl for<'a> ::core::ops::FnOnce(&'a $Owner) -> Result<$Dependent<'a>, Err>
+/// ) -> Result<(), ($Owner, Err)>
+/// ```
+///
+/// ```ignore
+/// // Panics if not initialized, leaves the struct uninitialized.
+/// fn take_owner(self: Pin<&mut Self>) -> $Owner
+/// ```
+///
+/// `borrow_owner`, `borrow_dependent` and `with_dependent` are the same as
+/// above but panic if the struct is not initialized, `with_dependent_mut`
+/// takes `self: Pin<&mut Self>`. Example usage:
+///
+/// ```ignore
+/// let mut cell = core::pin::pin!(AstCell::uninit());
+/// cell.as_mut().init(owner, |owner| Ast::from(owner));
+/// assert_eq!(cell.borrow_dependent(), &expected_ast);
+/// ```
+///
+///
+/// Instead of a single `dependent` the macro accepts an ordered list of
+/// named dependents, each may borrow owner and all dependents before it:
+///
+/// ```ignore
+/// self_cell!(
+///     struct AstCell {
+///         owner: String,
+///
+///         dependents: {
+///             #[covariant]
+///             tokens: Tokens,
+///
+///             #[covariant]
+///             ast: Ast,
+///         }
+///     }
+/// );
+/// ```
+///
+/// For which the macro implements `borrow_owner`, `into_owner`, `try_new`
+/// and `try_new_or_recover` with one builder per dependent, and:
+///
+/// ```ignore
+/// fn new(
+///     owner: $Owner,
+///     tokens: impl for<'a> ::core::ops::FnOnce(&'a $Owner) -> Tokens<'a>,
+///     ast: impl for<'a> ::core::ops::FnOnce(&'a $Owner, &'a Tokens<'a>) -> Ast<'a>,
+/// ) -> Self
+/// ```
+///
+/// ```ignore
+/// // Only available for covariant dependents, named after the dependent.
+/// fn tokens<'a>(&'a self) -> &'a Tokens<'a>
+/// ```
+///
+/// ```ignore
+/// fn with_dependents<'outer_fn, Ret>(
+///     &'outer_fn self,
+///     func: impl for<'a> ::core::ops::FnOnce(&'a $Owner, &'a Tokens<'a>, &'outer_fn Ast<'a>) -> Ret
+/// ) -> Ret
+/// ```
+///
+/// ```ignore
+/// // Only the last dependent can be mutated.
+/// fn with_dependents_mut<'outer_fn, Ret>(
+///     &'outer_fn mut self,
+///     func: impl for<'a> ::core::ops::FnOnce(&'a $Owner, &'a Tokens<'a>, &'outer_fn mut Ast<'a>) -> Ret
+/// ) -> Ret
+/// ```
+///
+/// Instead of a single `owner` the macro accepts several named owners, e.g.
+/// `owners: { dictionary: Dictionary, text: String, }`. The constructors
+/// `new`, `try_new` and `try_new_or_recover` then take each owner as separate
+/// parameter and the `dependent_builder` gets a shared reference to each of
+/// them. `borrow_dependent`, `with_dependent` and `with_dependent_mut` are the
+/// same as above, except that the closures get all owners. Instead of
+/// `borrow_owner` and `into_owner` the macro implements:
+///
+/// ```ignore
+/// // Named after the owner.
+/// fn dictionary<'a>(&'a self) -> &'a Dictionary
+/// ```
+///
+/// ```ignore
+/// fn borrow_owners<'a>(&'a self) -> &'a (Dictionary, String)
+/// ```
+///
+/// ```ignore
+/// fn into_owners(self) -> (Dictionary, String)
+/// ```
+///
+/// Owner and dependent can have custom field names, which then show up in the
+/// `Debug` output. `macro_rules` can't build new identifiers, so the accessor
+/// names are listed next to each field:
+///
+/// ```ignore
+/// self_cell!(
+///     pub struct Config {
+///         #[owner(borrow = borrow_source, into = into_source)]
+///         source: String,
+///
+///         #[covariant]
+///         #[dependent(borrow = borrow_ast, with = with_ast, with_mut = with_ast_mut)]
+///         ast: Ast,
+///     }
+/// );
+/// ```
+///
+/// This replaces `borrow_owner`, `into_owner`, `borrow_dependent`,
+/// `with_dependent` and `with_dependent_mut` with the given names, everything
+/// else stays the same. `borrow` of the dependent is only required and used
+/// for `covariant` dependents.
 ///
 /// ### Parameters:
 ///
@@ -254,13 +507,35 @@ pub mod unsafe_self_cell;
 ///   `$(#[$StructMeta:meta])*` allows you specify further meta items for this
 ///   struct, eg. `#[doc(hidden)] struct AstCell`.
 ///
+///   The struct may declare type parameters, const generics and a `where`
+///   clause after the optional owner lifetime, eg. `struct DocCell<'a, T:
+///   Parse, const N: usize> where T: Clone`. They are passed on to the impl
+///   blocks, `Drop` and the `AutomaticDerive`. Each parameter and each `where`
+///   predicate takes at most one bound path, repeat the predicate for more
+///   bounds. Type parameters have to be `'static`, like `$Owner`, the macro
+///   adds that bound. Generic parameters and custom field names are only
+///   supported without the optional markers and without `dependents` or
+///   `owners`.
+///
 /// - `$Owner:ty` Type of owner. This has to have a `'static` lifetime. Example:
 ///   `String`.
 ///
+/// - `owners: { $($owner_field:ident: $Owner:ty,)+ }` Replaces `owner` with
+///   several owners that are stored in the same heap allocation, the
+///   dependent may borrow from all of them. They are dropped in declaration
+///   order after the dependent. The optional markers and `allocator` are not
+///   supported for multiple owners, `AutomaticDerive` compare and hash all
+///   owners.
+///
 /// - `$Dependent:ident` Name of the dependent type without specified lifetime.
-///   This can't be a nested type name. As workaround either create a type alias
-///   `type Dep<'a> = Option<Vec<&'a str>>;` or create a new-type `struct
-///   Dep<'a>(Option<Vec<&'a str>>);`. Example: `Ast`.
+///   This can also be a path, and further generic arguments may follow the
+///   borrowed lifetime, which is written as `'_`. Example: `Ast`,
+///   `crate::ast::Ast` or `Ast<'_, T>`. Any other type is written with a
+///   named borrowed lifetime. Example: `for<'a> Option<Vec<&'a str>>`. Such a
+///   dependent is named through a trait implemented for the struct, which
+///   makes the struct invariant in its lifetime and type parameters. With
+///   `dependents` this form isn't supported, the struct could only name one
+///   of them, use a type alias `type Dep<'a> = Option<Vec<&'a str>>;` there.
 ///
 ///   `$Covariance:ident` Marker declaring if `$Dependent` is
 ///   [covariant](https://doc.rust-lang.org/nightly/nomicon/subtyping.html).
@@ -285,28 +560,148 @@ pub mod unsafe_self_cell;
 ///   pointers to a value not the other way around.
 ///
 ///   `#[$Covariance:ident, async_builder]` Optional marker that tells the macro to
-///   generate `async` construction functions. `new`, `try_new` and `try_new_or_recover`
-///   will all be `async` functions taking `async` closures as `dependent_builder`
-///   functions.
+///   generate `async` construction functions. All constructors, e.g. `new`,
+///   `try_new` and `try_new_or_recover`, will be `async` functions taking
+///   `async` closures as `dependent_builder` functions.
+///
+///   `#[$Covariance:ident, stable_deref]` Optional marker that stores owner
+///   inline instead of moving it into a heap allocation. `$Owner` has to
+///   implement [`StableDeref`], which is the case for heap handles like
+///   `String`, `Vec<T>`, `Rc<T>` and `Arc<T>`. The dependent borrows from the
+///   `Deref` target of owner, which doesn't move when the struct is moved.
+///   This saves an allocation and a pointer indirection per access.
+///   `allocator` and `async_builder` are not supported for stable deref cells.
+///
+///   `#[$Covariance:ident, shared]` Optional marker that makes the struct a
+///   reference counted handle, like `Arc`. The count is stored in the same
+///   heap allocation as owner and dependent. `Clone` only increments the count,
+///   and like `Arc` aborts the process if it overflows. `into_owner` becomes
+///   `try_into_owner`, which only succeeds for the last handle. There is no `with_dependent_mut`. The struct is only `Send`
+///   and `Sync` if owner and dependent are both `Send` and `Sync`. `allocator`
+///   and `async_builder` are not supported for shared cells.
+///
+///   `#[$Covariance:ident, pinned]` Optional marker that tells the macro to
+///   store owner and dependent inline instead of in a heap allocation. The
+///   struct is created uninitialized with `uninit` and initialized in place
+///   with `init` once it is pinned. Works without the `alloc` feature. The
+///   struct is never `Unpin`, implementing `Unpin` for it fails to compile.
+///   `allocator`, `async_builder` and `AutomaticDerive` are not supported for
+///   pinned cells.
 ///
-/// - `impl {$($AutomaticDerive:ident),*},` Optional comma separated list of
-///   optional automatic trait implementations. Possible Values:
+/// - `dependents: { $(#[$Covariance:ident] $field:ident: $Dependent:ident,)+ }`
+///   Replaces `dependent` with an ordered list of dependents that are stored
+///   in the same heap allocation and dropped in reverse order. Each
+///   `$Dependent` is built by the builder parameter `$field`, which gets a
+///   shared reference to owner and to all earlier dependents. All dependents
+///   except the last have to be `covariant`, which ensures that an earlier
+///   dependent can't hold on to a later one that is dropped before it.
+///   `allocator`, `async_builder` and the other optional markers are not
+///   supported for multiple dependents.
+///
+/// - `allocator: $Allocator:ty,` Optional allocator used for the heap
+///   allocation, declared after the dependent. Requires the `allocator_api2`
+///   feature, `$Allocator` has to implement
+///   [`allocator_api2::alloc::Allocator`](https://docs.rs/allocator-api2). The
+///   allocator is stored in the struct and used again to free the allocation
+///   in `drop` and `into_owner`. Instead of `new`, `try_new` and
+///   `try_new_or_recover` the macro then implements `new_in`, `try_new_in`,
+///   `try_new_or_recover_in`, `try_new_alloc_in`,
+///   `try_new_or_recover_alloc_in`, `try_new_borrowed_err_in` and
+///   `new_catch_unwind_in`, which take the allocator as second parameter.
+///   Example: `allocator: &'a Bump,` in `struct AstCell<'a>`.
+///
+/// - `methods { $($method:ident: $MethodVis),* }` Optional list declared after
+///   the struct that overrides `$Vis` for single generated methods. Each
+///   `$MethodVis` is either a visibility, use `pub(self)` for private, or
+///   `none` to not generate the method at all. Example: `methods {
+///   borrow_owner: pub(crate), with_dependent_mut: none, into_owner: none }`.
+///   Supported are `borrow_owner`, `borrow_dependent`, `with_dependent`,
+///   `with_dependent_mut`, `into_owner` and the `replace_owner` and
+///   `with_owner_mut` families, each under its default name also if custom
+///   field names are used. Constructors always use `$Vis`. `PartialEq`,
+///   `PartialOrd`, `Ord`, `Hash`, `Clone` and `Serialize` use `borrow_owner`,
+///   `Debug`, `Display`, `Error` and the `dependent` and `both` comparison
+///   modes use `with_dependent`, so these can't be `none` if the trait is
+///   automatically implemented. Only supported without the optional markers
+///   and without `dependents` or `owners`.
+///
+/// - `impl {$($AutomaticDerive:ident $(($($DeriveArgs:tt)*))?),*},` Optional
+///   comma separated list of optional automatic trait implementations, some
+///   take arguments in parentheses. Possible Values:
 ///
 ///   * **Debug**: Prints the debug representation of owner and dependent.
 ///     Example: `AstCell { owner: "fox = cat + dog", dependent: Ast(["fox",
-///     "cat", "dog"]) }`
+///     "cat", "dog"]) }`. `Debug(dependent_only)` and `Debug(owner_only)`
+///     only print one side, and only require that side to implement `Debug`.
+///     `Debug(owner_len)` prints the length of an `AsRef<[u8]>` owner instead
+///     of its content, eg. `AstCell { owner_len: 15, dependent: ... }`.
+///     Options are not available with `dependents` or `owners`.
 ///
 ///   * **PartialEq**: Logic `*self.borrow_owner() == *other.borrow_owner()`,
 ///     this assumes that `Dependent<'a>::From<&'a Owner>` is deterministic, so
-///     that only comparing owner is enough.
+///     that only comparing owner is enough. Also implements
+///     `PartialEq<$Owner>`, so a cell can be compared to an owner directly.
+///     `PartialEq(owner)` is the same. `PartialEq(dependent)` instead
+///     compares the dependents via `with_dependent`, for dependents that
+///     carry more than what's derived from owner, eg. options given to the
+///     builder. `PartialEq(both)` compares owner and dependent. These two
+///     require `Dependent<'a>: PartialEq` and a `covariant` dependent.
+///
+///   * **Eq**: Will implement the trait marker `Eq` for `$StructName`,
+///     requires `$Owner: Eq`. Use `Eq(dependent)` or `Eq(both)` together with
+///     the matching `PartialEq` mode, these require `Dependent<'a>: Eq`.
 ///
-///   * **Eq**: Will implement the trait marker `Eq` for `$StructName`. Beware
-///     if you select this `Eq` will be implemented regardless if `$Owner`
-///     implements `Eq`, that's an unfortunate technical limitation.
+///   * **PartialOrd**: Logic
+///     `self.borrow_owner().partial_cmp(other.borrow_owner())`, requires
+///     `$Owner: PartialOrd`.
+///
+///   * **Ord**: Logic `self.borrow_owner().cmp(other.borrow_owner())`,
+///     requires `$Owner: Ord`. Together with `Eq` this allows using cells as
+///     keys of a `BTreeMap`.
 ///
 ///   * **Hash**: Logic `self.borrow_owner().hash(state);`, this assumes that
 ///     `Dependent<'a>::From<&'a Owner>` is deterministic, so that only hashing
-///     owner is enough.
+///     owner is enough. Takes the same modes as `PartialEq`, `Hash(dependent)`
+///     and `Hash(both)` hash via `with_dependent`. Pick the same mode for
+///     `PartialEq`, `Eq` and `Hash`. The `dependent` and `both` modes are
+///     not available with `dependents` or `owners`.
+///
+///   * **Clone**: Logic `Self::new(self.borrow_owner().clone(), |owner|
+///     owner.into())`, requires `$Owner: Clone` and `Dependent<'a>: From<&'a
+///     Owner>`, or `From<&'a Owner::Target>` with `stable_deref`. For generic
+///     owners the latter can't be expressed on the impl, use the next form.
+///     `Clone(with = rebuild_ast)` instead calls the given builder, a
+///     function or closure like the `dependent_builder` of `new`. Useful for
+///     dependents that aren't built with `From`, eg. with an owner lifetime.
+///     `Clone(rebase)` doesn't build the dependent again, it copies the
+///     dependent and rewrites its references from the old to the cloned
+///     owner. Requires `$Owner: RebaseOwner` and `Dependent<'a>:
+///     Rebase<$Owner>`, see [`Rebase`] and [`impl_rebase!`]. References the
+///     owner doesn't implement [`RebaseRef`] for are rejected at compile
+///     time, eg. a `&u32` into a `Vec<u8>`, which may not be aligned in the
+///     clone. Not available with `shared`, which is `Clone` already,
+///     `async_builder`, `allocator`, `dependents` or `owners`, `rebase` also
+///     not with `stable_deref`.
+///
+///   * **Display**: Logic `self.with_dependent(|_, dependent|
+///     dependent.fmt(f))`, requires `Dependent<'a>: Display`. Useful for cells
+///     whose dependent is an error that borrows from owner.
+///
+///   * **Error**: Implements `std::error::Error` with `source` forwarded to
+///     the dependent, requires the `std` feature and `Dependent<'a>: Error`.
+///     The cell has to implement `Debug` and `Display` too, eg. with `impl
+///     {Debug, Display, Error}`.
+///
+///   * **Serialize**: Logic `self.borrow_owner().serialize(serializer)`,
+///     requires the `serde` feature and `$Owner: Serialize`.
+///
+///   * **Deserialize**: Deserializes the owner and builds the dependent with
+///     `Self::new(owner, |owner| owner.into())`, requires the `serde` feature
+///     and `$Owner: Deserialize<'de>`. Like `Clone` it takes a builder with
+///     `Deserialize(with = build_ast)`. `Deserialize(try_with = parse_ast)`
+///     uses `try_new` instead and turns the error of the builder, which has to
+///     implement `Display`, into the error of the deserializer. Not available
+///     with `pinned`, `async_builder`, `allocator`, `dependents` or `owners`.
 ///
 ///   All `AutomaticDerive` are optional and you can implement you own version
 ///   of these traits. The declared struct is part of your module and you are
@@ -314,405 +709,3513 @@ pub mod unsafe_self_cell;
 ///   internals is only possible via unsafe functions, so you can't accidentally
 ///   use them in safe code.
 ///
-///   There is limited nested cell support. Eg, having an owner with non static
+///   Nested cells are supported, eg. having an owner with non static
 ///   references. Eg `struct ChildCell<'a> { owner: &'a String, ...`. You can
-///   use any lifetime name you want, except `_q` and only a single lifetime is
-///   supported, and can only be used in the owner. Due to macro_rules
-///   limitations, no `AutomaticDerive` are supported if an owner lifetime is
-///   provided.
+///   use any lifetime names you want, except `_q` and `_pin`, and list
+///   several of them, eg. `struct PairCell<'a, 'b> { owner: (&'a str, &'b
+///   str), ...`, also together with the optional markers, `dependents` and
+///   `owners`. These lifetimes can only be used in the owner. Lifetimes have
+///   to come before type and const parameters. `AutomaticDerive` work the
+///   same way for such structs, with the bounds on owner and dependent placed
+///   on the impl.
 ///
 #[macro_export]
 macro_rules! self_cell {
 (
     $(#[$StructMeta:meta])*
-    $Vis:vis struct $StructName:ident $(<$OwnerLifetime:lifetime>)? {
+    $Vis:vis struct $StructName:ident $(<$OwnerLifetime:lifetime $(, $MoreLifetime:lifetime)*>)? {
         owner: $Owner:ty,
 
-
-        #[$Covariance:ident $(, $AsyncBuilder:ident)?]
-        dependent: $Dependent:ident,
+        #[$Covariance:ident, pinned]
+        dependent: $Dependent:tt,
     }
-
-    $(impl {$($AutomaticDerive:ident),*})?
 ) => {
-    #[repr(transparent)]
     $(#[$StructMeta])*
-    $Vis struct $StructName $(<$OwnerLifetime>)? {
-        unsafe_self_cell: $crate::unsafe_self_cell::UnsafeSelfCell<
-            $StructName$(<$OwnerLifetime>)?,
+    $Vis struct $StructName $(<$OwnerLifetime $(, $MoreLifetime)*>)? {
+        unsafe_self_cell: $crate::unsafe_self_cell::PinnedSelfCell<
+            $StructName$(<$OwnerLifetime $(, $MoreLifetime)*>)?,
             $Owner,
-            $Dependent<'static>
+            $crate::_self_cell_dependent!($Dependent, 'static)
         >,
 
-        $(owner_marker: $crate::_covariant_owner_marker!($Covariance, $OwnerLifetime) ,)?
+        $(owner_marker: $crate::_covariant_owner_marker!($Covariance, $OwnerLifetime $(, $MoreLifetime)*) ,)?
     }
 
-    impl <$($OwnerLifetime)?> $StructName <$($OwnerLifetime)?> {
-        $crate::_self_cell_new!($Vis, $Owner $(=> $OwnerLifetime)?, $Dependent $(, $AsyncBuilder)?);
+    $crate::_self_cell_dependent_impl!($Dependent, [$($OwnerLifetime $(, $MoreLifetime)*)?], []);
+
+    impl <$($OwnerLifetime $(, $MoreLifetime)*)?> $StructName <$($OwnerLifetime $(, $MoreLifetime)*)?> {
+        /// Constructs a new uninitialized self-referential struct.
+        ///
+        /// It has to be pinned and initialized with `init` before owner or dependent can be
+        /// accessed.
+        $Vis fn uninit() -> Self {
+            Self {
+                unsafe_self_cell: $crate::unsafe_self_cell::PinnedSelfCell::uninit(),
+                $(owner_marker: $crate::_covariant_owner_marker_ctor!($OwnerLifetime) ,)?
+            }
+        }
+
+        /// Returns `true` if owner and dependent are initialized.
+        $Vis fn is_initialized(&self) -> bool {
+            self.unsafe_self_cell.is_initialized()
+        }
+
+        /// Initializes the pinned self-referential struct in place.
+        ///
+        /// The provided `owner` will be moved into the struct. Followed by construction of the
+        /// dependent value, by calling `dependent_builder` with a shared reference to the owner
+        /// that remains valid for as long as the struct stays initialized.
+        ///
+        /// # Panics
+        ///
+        /// Panics if the struct is already initialized.
+        $Vis fn init(
+            self: ::core::pin::Pin<&mut Self>,
+            owner: $Owner,
+            dependent_builder: impl for<'_q> ::core::ops::FnOnce(&'_q $Owner) -> $crate::_self_cell_dependent!($Dependent, '_q)
+        ) {
+            type JoinedCell<'_q $(, $OwnerLifetime $(, $MoreLifetime)*)?> =
+                    $crate::unsafe_self_cell::JoinedCell<$Owner, $crate::_self_cell_dependent!($Dependent, '_q)>;
+
+            let unsafe_self_cell = &self.as_ref().get_ref().unsafe_self_cell;
+
+            // unsafe placed here to make sure the body macro can't be abused.
+            let result = unsafe {
+                $crate::_self_cell_pinned_init_body!(
+                    JoinedCell,
+                    unsafe_self_cell,
+                    owner,
+                    |owner| ::core::result::Result::Ok::<_, ::core::convert::Infallible>(
+                        dependent_builder(owner)
+                    )
+                )
+            };
+
+            match result {
+                ::core::result::Result::Ok(()) => {}
+                ::core::result::Result::Err((_, err)) => match err {},
+            }
+        }
+
+        /// Initializes the pinned self-referential struct in place or returns an error.
+        ///
+        /// Consumes owner on error, the struct remains uninitialized.
+        ///
+        /// # Panics
+        ///
+        /// Panics if the struct is already initialized.
+        $Vis fn try_init<Err>(
+            self: ::core::pin::Pin<&mut Self>,
+            owner: $Owner,
+            dependent_builder:
+                impl for<'_q> ::core::ops::FnOnce(&'_q $Owner) -> ::core::result::Result<$crate::_self_cell_dependent!($Dependent, '_q), Err>
+        ) -> ::core::result::Result<(), Err> {
+            match self.try_init_or_recover(owner, dependent_builder) {
+                ::core::result::Result::Ok(()) => ::core::result::Result::Ok(()),
+                ::core::result::Result::Err((_, err)) => ::core::result::Result::Err(err),
+            }
+        }
+
+        /// Initializes the pinned self-referential struct in place or returns an error.
+        ///
+        /// Returns owner and error as tuple on error, the struct remains uninitialized.
+        ///
+        /// # Panics
+        ///
+        /// Panics if the struct is already initialized.
+        $Vis fn try_init_or_recover<Err>(
+            self: ::core::pin::Pin<&mut Self>,
+            owner: $Owner,
+            dependent_builder:
+                impl for<'_q> ::core::ops::FnOnce(&'_q $Owner) -> ::core::result::Result<$crate::_self_cell_dependent!($Dependent, '_q), Err>
+        ) -> ::core::result::Result<(), ($Owner, Err)> {
+            type JoinedCell<'_q $(, $OwnerLifetime $(, $MoreLifetime)*)?> =
+                    $crate::unsafe_self_cell::JoinedCell<$Owner, $crate::_self_cell_dependent!($Dependent, '_q)>;
 
-        $crate::_self_cell_try_new!($Vis, $Owner $(=> $OwnerLifetime)?, $Dependent $(, $AsyncBuilder)?);
+            let unsafe_self_cell = &self.as_ref().get_ref().unsafe_self_cell;
 
-        $crate::_self_cell_try_new_or_recover!($Vis, $Owner $(=> $OwnerLifetime)?, $Dependent $(, $AsyncBuilder)?);
+            // unsafe placed here to make sure the body macro can't be abused.
+            unsafe {
+                $crate::_self_cell_pinned_init_body!(
+                    JoinedCell,
+                    unsafe_self_cell,
+                    owner,
+                    dependent_builder
+                )
+            }
+        }
 
         /// Borrows owner.
+        ///
+        /// # Panics
+        ///
+        /// Panics if the struct is not initialized.
         $Vis fn borrow_owner<'_q>(&'_q self) -> &'_q $Owner {
-            unsafe { self.unsafe_self_cell.borrow_owner::<$Dependent<'_q>>() }
+            unsafe { self.unsafe_self_cell.borrow_owner::<$crate::_self_cell_dependent!($Dependent, '_q)>() }
         }
 
         /// Calls given closure `func` with a shared reference to dependent.
+        ///
+        /// # Panics
+        ///
+        /// Panics if the struct is not initialized.
         $Vis fn with_dependent<'outer_fn, Ret>(
             &'outer_fn self,
-            func: impl for<'_q> ::core::ops::FnOnce(&'_q $Owner, &'outer_fn $Dependent<'_q>
+            func: impl for<'_q> ::core::ops::FnOnce(&'_q $Owner, &'outer_fn $crate::_self_cell_dependent!($Dependent, '_q)
         ) -> Ret) -> Ret {
             unsafe {
                 func(
-                    self.unsafe_self_cell.borrow_owner::<$Dependent>(),
+                    self.unsafe_self_cell.borrow_owner::<$crate::_self_cell_dependent!($Dependent, '_)>(),
                     self.unsafe_self_cell.borrow_dependent()
                 )
             }
         }
 
         /// Calls given closure `func` with an unique reference to dependent.
+        ///
+        /// # Panics
+        ///
+        /// Panics if the struct is not initialized.
         $Vis fn with_dependent_mut<'outer_fn, Ret>(
-            &'outer_fn mut self,
-            func: impl for<'_q> ::core::ops::FnOnce(&'_q $Owner, &'outer_fn mut $Dependent<'_q>) -> Ret
+            self: ::core::pin::Pin<&'outer_fn mut Self>,
+            func: impl for<'_q> ::core::ops::FnOnce(&'_q $Owner, &'outer_fn mut $crate::_self_cell_dependent!($Dependent, '_q)) -> Ret
         ) -> Ret {
+            let unsafe_self_cell = &self.into_ref().get_ref().unsafe_self_cell;
+
             let (owner, dependent) = unsafe {
-                    self.unsafe_self_cell.borrow_mut()
+                    unsafe_self_cell.borrow_mut()
             };
 
             func(owner, dependent)
         }
 
-        $crate::_covariant_access!($Covariance, $Vis, $Dependent);
-
-        /// Consumes `self` and returns the the owner.
-        $Vis fn into_owner(self) -> $Owner {
-            // This is only safe to do with repr(transparent).
-            let unsafe_self_cell = unsafe { ::core::mem::transmute::<
-                Self,
-                $crate::unsafe_self_cell::UnsafeSelfCell<
-                    $StructName$(<$OwnerLifetime>)?,
-                    $Owner,
-                    $Dependent<'static>
-                >
-            >(self) };
+        $crate::_covariant_access!($Covariance, $Vis, [], [borrow_dependent], $Dependent);
 
-            let owner = unsafe { unsafe_self_cell.into_owner::<$Dependent>() };
+        /// Drops dependent and returns the owner, leaving the struct uninitialized.
+        ///
+        /// # Panics
+        ///
+        /// Panics if the struct is not initialized.
+        $Vis fn take_owner(self: ::core::pin::Pin<&mut Self>) -> $Owner {
+            let unsafe_self_cell = &self.as_ref().get_ref().unsafe_self_cell;
 
-            owner
+            unsafe { unsafe_self_cell.take_owner::<$crate::_self_cell_dependent!($Dependent, '_)>() }
         }
     }
 
-    impl $(<$OwnerLifetime>)? Drop for $StructName $(<$OwnerLifetime>)? {
+    // Conflicts with any user implementation of Unpin, which would allow moving the initialized
+    // struct out of its Pin.
+    impl <'_pin $(, $OwnerLifetime $(, $MoreLifetime)*)?> ::core::marker::Unpin for $StructName $(<$OwnerLifetime $(, $MoreLifetime)*>)?
+    where
+        $crate::unsafe_self_cell::PinnedGuard<'_pin, ::core::marker::PhantomPinned>:
+            ::core::marker::Unpin
+    {
+    }
+
+    impl $(<$OwnerLifetime $(, $MoreLifetime)*>)? Drop for $StructName $(<$OwnerLifetime $(, $MoreLifetime)*>)? {
         fn drop(&mut self) {
             unsafe {
-                self.unsafe_self_cell.drop_joined::<$Dependent>();
+                self.unsafe_self_cell.drop_joined::<$crate::_self_cell_dependent!($Dependent, '_)>();
             }
         }
     }
+};
+(
+    $(#[$StructMeta:meta])*
+    $Vis:vis struct $StructName:ident $(<$OwnerLifetime:lifetime $(, $MoreLifetime:lifetime)*>)? {
+        owner: $Owner:ty,
 
-    // The user has to choose which traits can and should be automatically
-    // implemented for the cell.
-    $($(
-        $crate::_impl_automatic_derive!($AutomaticDerive, $StructName);
-    )*)*
+        #[$Covariance:ident, pinned]
+        dependent: for<$DependentLifetime:lifetime> $Dependent:ty,
+    }
+
+    $($Rest:tt)*
+) => {
+    $crate::self_cell!(
+        $(#[$StructMeta])*
+        $Vis struct $StructName $(<$OwnerLifetime $(, $MoreLifetime)*>)? {
+            owner: $Owner,
+
+            #[$Covariance, pinned]
+            dependent: {for [$DependentLifetime] [$Dependent] [$StructName $(<$OwnerLifetime $(, $MoreLifetime)*>)?]},
+        }
+
+        $($Rest)*
+    );
 };
-}
+(
+    $(#[$StructMeta:meta])*
+    $Vis:vis struct $StructName:ident $(<$OwnerLifetime:lifetime $(, $MoreLifetime:lifetime)*>)? {
+        owner: $Owner:ty,
 
-#[doc(hidden)]
-#[macro_export]
-macro_rules! _covariant_access {
-    (covariant, $Vis:vis, $Dependent:ident) => {
-        /// Borrows dependent.
-        $Vis fn borrow_dependent<'_q>(&'_q self) -> &'_q $Dependent<'_q> {
-            fn _assert_covariance<'x: 'y, 'y>(x: &'y $Dependent<'x>) -> &'y $Dependent<'y> {
-                //  This function only compiles for covariant types.
-                x // Change the macro invocation to not_covariant.
-            }
+        #[$Covariance:ident, pinned]
+        dependent: $($DependentPath:ident)::+ $(<
+            $DependentLifetime:lifetime $(, $DependentArg:ty)* $(,)?
+        >)?,
+    }
+
+    $($Rest:tt)*
+) => {
+    $crate::self_cell!(
+        $(#[$StructMeta])*
+        $Vis struct $StructName $(<$OwnerLifetime $(, $MoreLifetime)*>)? {
+            owner: $Owner,
 
-            unsafe { self.unsafe_self_cell.borrow_dependent() }
+            #[$Covariance, pinned]
+            dependent: {[$($DependentPath)::+] [$($($DependentArg),*)?]},
         }
-    };
-    (not_covariant, $Vis:vis, $Dependent:ident) => {
-        // For types that are not covariant it's unsafe to allow
-        // returning direct references.
-        // For example a lifetime that is too short could be chosen:
-        // See https://github.com/Voultapher/self_cell/issues/5
-    };
-    ($x:ident, $Vis:vis, $Dependent:ident) => {
-        compile_error!("This macro only accepts `covariant` or `not_covariant`");
-    };
-}
 
-#[doc(hidden)]
-#[macro_export]
+        $($Rest)*
+    );
+};
+(
+    $(#[$StructMeta:meta])*
+    $Vis:vis struct $StructName:ident $(<$OwnerLifetime:lifetime $(, $MoreLifetime:lifetime)*>)? {
+        owner: $Owner:ty,
+
+        #[$Covariance:ident, stable_deref]
+        dependent: $Dependent:tt,
+    }
+
+    $(impl {$($AutomaticDerive:ident $(($($DeriveArgs:tt)*))?),*})?
+) => {
+    #[repr(transparent)]
+    $(#[$StructMeta])*
+    $Vis struct $StructName $(<$OwnerLifetime $(, $MoreLifetime)*>)? {
+        unsafe_self_cell: $crate::unsafe_self_cell::StableDerefSelfCell<
+            $StructName$(<$OwnerLifetime $(, $MoreLifetime)*>)?,
+            $Owner,
+            $crate::_self_cell_dependent!($Dependent, 'static)
+        >,
+
+        $(owner_marker: $crate::_covariant_owner_marker!($Covariance, $OwnerLifetime $(, $MoreLifetime)*) ,)?
+    }
+
+    $crate::_self_cell_dependent_impl!($Dependent, [$($OwnerLifetime $(, $MoreLifetime)*)?], []);
+
+    impl <$($OwnerLifetime $(, $MoreLifetime)*)?> $StructName <$($OwnerLifetime $(, $MoreLifetime)*)?> {
+        /// Constructs a new self-referential struct.
+        ///
+        /// The provided `owner` is stored inline, no additional heap allocation is made. The
+        /// dependent value is constructed by calling `dependent_builder` with a shared reference
+        /// to the `Deref` target of owner, which remains valid for the lifetime of the
+        /// constructed struct.
+        $Vis fn new(
+            owner: $Owner,
+            dependent_builder: impl for<'_q> ::core::ops::FnOnce(
+                &'_q <$Owner as ::core::ops::Deref>::Target
+            ) -> $crate::_self_cell_dependent!($Dependent, '_q)
+        ) -> Self {
+            // unsafe placed here to make sure the body macro can't be abused.
+            let result = unsafe {
+                $crate::_self_cell_stable_deref_body!(
+                    owner
+                    $(=> $OwnerLifetime)?,
+                    |target| ::core::result::Result::Ok::<_, ::core::convert::Infallible>(
+                        dependent_builder(target)
+                    )
+                )
+            };
+
+            match result {
+                ::core::result::Result::Ok(cell) => cell,
+                ::core::result::Result::Err((_, err)) => match err {},
+            }
+        }
+
+        /// Constructs a new self-referential struct or returns an error.
+        ///
+        /// Consumes owner on error.
+        $Vis fn try_new<Err>(
+            owner: $Owner,
+            dependent_builder: impl for<'_q> ::core::ops::FnOnce(
+                &'_q <$Owner as ::core::ops::Deref>::Target
+            ) -> ::core::result::Result<$crate::_self_cell_dependent!($Dependent, '_q), Err>
+        ) -> ::core::result::Result<Self, Err> {
+            match Self::try_new_or_recover(owner, dependent_builder) {
+                ::core::result::Result::Ok(cell) => ::core::result::Result::Ok(cell),
+                ::core::result::Result::Err((_, err)) => ::core::result::Result::Err(err),
+            }
+        }
+
+        /// Constructs a new self-referential struct or returns an error.
+        ///
+        /// Returns owner and error as tuple on error.
+        $Vis fn try_new_or_recover<Err>(
+            owner: $Owner,
+            dependent_builder: impl for<'_q> ::core::ops::FnOnce(
+                &'_q <$Owner as ::core::ops::Deref>::Target
+            ) -> ::core::result::Result<$crate::_self_cell_dependent!($Dependent, '_q), Err>
+        ) -> ::core::result::Result<Self, ($Owner, Err)> {
+            // unsafe placed here to make sure the body macro can't be abused.
+            unsafe {
+                $crate::_self_cell_stable_deref_body!(
+                    owner
+                    $(=> $OwnerLifetime)?,
+                    dependent_builder
+                )
+            }
+        }
+
+        /// Borrows owner.
+        $Vis fn borrow_owner<'_q>(&'_q self) -> &'_q $Owner {
+            unsafe { self.unsafe_self_cell.borrow_owner::<$crate::_self_cell_dependent!($Dependent, '_q)>() }
+        }
+
+        /// Calls given closure `func` with a shared reference to dependent.
+        $Vis fn with_dependent<'outer_fn, Ret>(
+            &'outer_fn self,
+            func: impl for<'_q> ::core::ops::FnOnce(&'_q $Owner, &'outer_fn $crate::_self_cell_dependent!($Dependent, '_q)
+        ) -> Ret) -> Ret {
+            unsafe {
+                func(
+                    self.unsafe_self_cell.borrow_owner::<$crate::_self_cell_dependent!($Dependent, '_)>(),
+                    self.unsafe_self_cell.borrow_dependent()
+                )
+            }
+        }
+
+        /// Calls given closure `func` with an unique reference to dependent.
+        $Vis fn with_dependent_mut<'outer_fn, Ret>(
+            &'outer_fn mut self,
+            func: impl for<'_q> ::core::ops::FnOnce(&'_q $Owner, &'outer_fn mut $crate::_self_cell_dependent!($Dependent, '_q)) -> Ret
+        ) -> Ret {
+            let (owner, dependent) = unsafe {
+                    self.unsafe_self_cell.borrow_mut()
+            };
+
+            func(owner, dependent)
+        }
+
+        $crate::_covariant_access!($Covariance, $Vis, [], [borrow_dependent], $Dependent);
+
+        /// Consumes `self` and returns the the owner.
+        $Vis fn into_owner(self) -> $Owner {
+            // This is only safe to do with repr(transparent).
+            let unsafe_self_cell = unsafe { ::core::mem::transmute::<
+                Self,
+                $crate::unsafe_self_cell::StableDerefSelfCell<
+                    $StructName$(<$OwnerLifetime $(, $MoreLifetime)*>)?,
+                    $Owner,
+                    $crate::_self_cell_dependent!($Dependent, 'static)
+                >
+            >(self) };
+
+            let owner = unsafe { unsafe_self_cell.into_owner::<$crate::_self_cell_dependent!($Dependent, '_)>() };
+
+            owner
+        }
+    }
+
+    impl $(<$OwnerLifetime $(, $MoreLifetime)*>)? Drop for $StructName $(<$OwnerLifetime $(, $MoreLifetime)*>)? {
+        fn drop(&mut self) {
+            unsafe {
+                self.unsafe_self_cell.drop_joined::<$crate::_self_cell_dependent!($Dependent, '_)>();
+            }
+        }
+    }
+
+    // The user has to choose which traits can and should be automatically
+    // implemented for the cell.
+    $crate::_impl_automatic_derive!(
+        [$($($AutomaticDerive $(($($DeriveArgs)*))?),*)?],
+        $StructName,
+        {owner borrow_owner, dependent with_dependent},
+        {[$($OwnerLifetime $(, $MoreLifetime)*)?] [$($OwnerLifetime $(, $MoreLifetime)*)?] []},
+        $Owner,
+        $Dependent
+    );
+};
+(
+    $(#[$StructMeta:meta])*
+    $Vis:vis struct $StructName:ident $(<$OwnerLifetime:lifetime $(, $MoreLifetime:lifetime)*>)? {
+        owner: $Owner:ty,
+
+        #[$Covariance:ident, stable_deref]
+        dependent: for<$DependentLifetime:lifetime> $Dependent:ty,
+    }
+
+    $($Rest:tt)*
+) => {
+    $crate::self_cell!(
+        $(#[$StructMeta])*
+        $Vis struct $StructName $(<$OwnerLifetime $(, $MoreLifetime)*>)? {
+            owner: $Owner,
+
+            #[$Covariance, stable_deref]
+            dependent: {for [$DependentLifetime] [$Dependent] [$StructName $(<$OwnerLifetime $(, $MoreLifetime)*>)?]},
+        }
+
+        $($Rest)*
+    );
+};
+(
+    $(#[$StructMeta:meta])*
+    $Vis:vis struct $StructName:ident $(<$OwnerLifetime:lifetime $(, $MoreLifetime:lifetime)*>)? {
+        owner: $Owner:ty,
+
+        #[$Covariance:ident, stable_deref]
+        dependent: $($DependentPath:ident)::+ $(<
+            $DependentLifetime:lifetime $(, $DependentArg:ty)* $(,)?
+        >)?,
+    }
+
+    $($Rest:tt)*
+) => {
+    $crate::self_cell!(
+        $(#[$StructMeta])*
+        $Vis struct $StructName $(<$OwnerLifetime $(, $MoreLifetime)*>)? {
+            owner: $Owner,
+
+            #[$Covariance, stable_deref]
+            dependent: {[$($DependentPath)::+] [$($($DependentArg),*)?]},
+        }
+
+        $($Rest)*
+    );
+};
+(
+    $(#[$StructMeta:meta])*
+    $Vis:vis struct $StructName:ident $(<$OwnerLifetime:lifetime $(, $MoreLifetime:lifetime)*>)? {
+        owner: $Owner:ty,
+
+        #[$Covariance:ident, shared]
+        dependent: $Dependent:tt,
+    }
+
+    $(impl {$($AutomaticDerive:ident $(($($DeriveArgs:tt)*))?),*})?
+) => {
+    $crate::_self_cell_require_alloc!();
+
+    #[repr(transparent)]
+    $(#[$StructMeta])*
+    $Vis struct $StructName $(<$OwnerLifetime $(, $MoreLifetime)*>)? {
+        unsafe_self_cell: $crate::unsafe_self_cell::SharedSelfCell<
+            $StructName$(<$OwnerLifetime $(, $MoreLifetime)*>)?,
+            $Owner,
+            $crate::_self_cell_dependent!($Dependent, 'static)
+        >,
+
+        $(owner_marker: $crate::_covariant_owner_marker!($Covariance, $OwnerLifetime $(, $MoreLifetime)*) ,)?
+    }
+
+    $crate::_self_cell_dependent_impl!($Dependent, [$($OwnerLifetime $(, $MoreLifetime)*)?], []);
+
+    impl <$($OwnerLifetime $(, $MoreLifetime)*)?> $StructName <$($OwnerLifetime $(, $MoreLifetime)*)?> {
+        /// Constructs a new shared self-referential struct.
+        ///
+        /// The provided `owner` will be moved into a heap allocated box, next to the reference
+        /// count. Followed by construction of the dependent value, by calling `dependent_builder`
+        /// with a shared reference to the owner that remains valid for the lifetime of the
+        /// constructed struct and all its clones.
+        $Vis fn new(
+            owner: $Owner,
+            dependent_builder: impl for<'_q> ::core::ops::FnOnce(&'_q $Owner) -> $crate::_self_cell_dependent!($Dependent, '_q)
+        ) -> Self {
+            type JoinedCell<'_q $(, $OwnerLifetime $(, $MoreLifetime)*)?> = $crate::unsafe_self_cell::JoinedCell<
+                $crate::unsafe_self_cell::SharedOwner<$Owner>,
+                $crate::_self_cell_dependent!($Dependent, '_q)
+            >;
+
+            // unsafe placed here to make sure the body macro can't be abused.
+            unsafe {
+                $crate::_self_cell_new_body!(
+                    JoinedCell,
+                    $crate::unsafe_self_cell::SharedOwner::new(owner),
+                    $crate::unsafe_self_cell::DefaultAllocator,
+                    $crate::alloc::alloc::handle_alloc_error(
+                        $crate::alloc::alloc::Layout::new::<JoinedCell>()
+                    )
+                    $(=> $OwnerLifetime)?,
+                    dependent_builder
+                )
+            }
+        }
+
+        /// Constructs a new shared self-referential struct or returns an error.
+        ///
+        /// Consumes owner on error.
+        $Vis fn try_new<Err>(
+            owner: $Owner,
+            dependent_builder:
+                impl for<'_q> ::core::ops::FnOnce(&'_q $Owner) -> ::core::result::Result<$crate::_self_cell_dependent!($Dependent, '_q), Err>
+        ) -> ::core::result::Result<Self, Err> {
+            type JoinedCell<'_q $(, $OwnerLifetime $(, $MoreLifetime)*)?> = $crate::unsafe_self_cell::JoinedCell<
+                $crate::unsafe_self_cell::SharedOwner<$Owner>,
+                $crate::_self_cell_dependent!($Dependent, '_q)
+            >;
+
+            // unsafe placed here to make sure the body macro can't be abused.
+            unsafe {
+                $crate::_self_cell_try_new_body!(
+                    JoinedCell,
+                    $crate::unsafe_self_cell::SharedOwner::new(owner),
+                    $crate::unsafe_self_cell::DefaultAllocator,
+                    $crate::alloc::alloc::handle_alloc_error(
+                        $crate::alloc::alloc::Layout::new::<JoinedCell>()
+                    )
+                    $(=> $OwnerLifetime)?,
+                    dependent_builder
+                )
+            }
+        }
+
+        /// Constructs a new shared self-referential struct or returns an error.
+        ///
+        /// Returns owner and error as tuple on error.
+        $Vis fn try_new_or_recover<Err>(
+            owner: $Owner,
+            dependent_builder:
+                impl for<'_q> ::core::ops::FnOnce(&'_q $Owner) -> ::core::result::Result<$crate::_self_cell_dependent!($Dependent, '_q), Err>
+        ) -> ::core::result::Result<Self, ($Owner, Err)> {
+            type JoinedCell<'_q $(, $OwnerLifetime $(, $MoreLifetime)*)?> = $crate::unsafe_self_cell::JoinedCell<
+                $crate::unsafe_self_cell::SharedOwner<$Owner>,
+                $crate::_self_cell_dependent!($Dependent, '_q)
+            >;
+
+            // unsafe placed here to make sure the body macro can't be abused.
+            let result = unsafe {
+                $crate::_self_cell_try_new_or_recover_body!(
+                    JoinedCell,
+                    $crate::unsafe_self_cell::SharedOwner::new(owner),
+                    $crate::unsafe_self_cell::DefaultAllocator,
+                    $crate::alloc::alloc::handle_alloc_error(
+                        $crate::alloc::alloc::Layout::new::<JoinedCell>()
+                    )
+                    $(=> $OwnerLifetime)?,
+                    dependent_builder
+                )
+            };
+
+            result.map_err(|(shared_owner, err)| (shared_owner.into_inner(), err))
+        }
+
+        /// Borrows owner.
+        $Vis fn borrow_owner<'_q>(&'_q self) -> &'_q $Owner {
+            unsafe { self.unsafe_self_cell.borrow_owner::<$crate::_self_cell_dependent!($Dependent, '_q)>() }
+        }
+
+        /// Calls given closure `func` with a shared reference to dependent.
+        $Vis fn with_dependent<'outer_fn, Ret>(
+            &'outer_fn self,
+            func: impl for<'_q> ::core::ops::FnOnce(&'_q $Owner, &'outer_fn $crate::_self_cell_dependent!($Dependent, '_q)
+        ) -> Ret) -> Ret {
+            unsafe {
+                func(
+                    self.unsafe_self_cell.borrow_owner::<$crate::_self_cell_dependent!($Dependent, '_)>(),
+                    self.unsafe_self_cell.borrow_dependent()
+                )
+            }
+        }
+
+        $crate::_covariant_access!($Covariance, $Vis, [], [borrow_dependent], $Dependent);
+
+        /// Consumes `self` and returns the the owner, if `self` is the only handle.
+        ///
+        /// Otherwise `self` is returned unchanged.
+        $Vis fn try_into_owner(self) -> ::core::result::Result<$Owner, Self> {
+            if !unsafe { self.unsafe_self_cell.is_unique::<$crate::_self_cell_dependent!($Dependent, '_)>() } {
+                return ::core::result::Result::Err(self);
+            }
+
+            // This is only safe to do with repr(transparent).
+            let unsafe_self_cell = unsafe { ::core::mem::transmute::<
+                Self,
+                $crate::unsafe_self_cell::SharedSelfCell<
+                    $StructName$(<$OwnerLifetime $(, $MoreLifetime)*>)?,
+                    $Owner,
+                    $crate::_self_cell_dependent!($Dependent, 'static)
+                >
+            >(self) };
+
+            let owner = unsafe { unsafe_self_cell.into_owner::<$crate::_self_cell_dependent!($Dependent, '_)>() };
+
+            ::core::result::Result::Ok(owner)
+        }
+    }
+
+    impl $(<$OwnerLifetime $(, $MoreLifetime)*>)? Clone for $StructName $(<$OwnerLifetime $(, $MoreLifetime)*>)? {
+        /// Returns a new handle to the same owner and dependent, only the
+        /// reference count is incremented.
+        fn clone(&self) -> Self {
+            Self {
+                unsafe_self_cell: unsafe { self.unsafe_self_cell.clone_shared::<$crate::_self_cell_dependent!($Dependent, '_)>() },
+                $(owner_marker: $crate::_covariant_owner_marker_ctor!($OwnerLifetime) ,)?
+            }
+        }
+    }
+
+    impl $(<$OwnerLifetime $(, $MoreLifetime)*>)? Drop for $StructName $(<$OwnerLifetime $(, $MoreLifetime)*>)? {
+        fn drop(&mut self) {
+            unsafe {
+                self.unsafe_self_cell.drop_shared::<$crate::_self_cell_dependent!($Dependent, '_)>();
+            }
+        }
+    }
+
+    // The user has to choose which traits can and should be automatically
+    // implemented for the cell.
+    $crate::_impl_automatic_derive!(
+        [$($($AutomaticDerive $(($($DeriveArgs)*))?),*)?],
+        $StructName,
+        {owner borrow_owner, dependent with_dependent},
+        {[$($OwnerLifetime $(, $MoreLifetime)*)?] [$($OwnerLifetime $(, $MoreLifetime)*)?] []},
+        $Owner,
+        $Dependent
+    );
+};
+(
+    $(#[$StructMeta:meta])*
+    $Vis:vis struct $StructName:ident $(<$OwnerLifetime:lifetime $(, $MoreLifetime:lifetime)*>)? {
+        owner: $Owner:ty,
+
+        #[$Covariance:ident, shared]
+        dependent: for<$DependentLifetime:lifetime> $Dependent:ty,
+    }
+
+    $($Rest:tt)*
+) => {
+    $crate::self_cell!(
+        $(#[$StructMeta])*
+        $Vis struct $StructName $(<$OwnerLifetime $(, $MoreLifetime)*>)? {
+            owner: $Owner,
+
+            #[$Covariance, shared]
+            dependent: {for [$DependentLifetime] [$Dependent] [$StructName $(<$OwnerLifetime $(, $MoreLifetime)*>)?]},
+        }
+
+        $($Rest)*
+    );
+};
+(
+    $(#[$StructMeta:meta])*
+    $Vis:vis struct $StructName:ident $(<$OwnerLifetime:lifetime $(, $MoreLifetime:lifetime)*>)? {
+        owner: $Owner:ty,
+
+        #[$Covariance:ident, shared]
+        dependent: $($DependentPath:ident)::+ $(<
+            $DependentLifetime:lifetime $(, $DependentArg:ty)* $(,)?
+        >)?,
+    }
+
+    $($Rest:tt)*
+) => {
+    $crate::self_cell!(
+        $(#[$StructMeta])*
+        $Vis struct $StructName $(<$OwnerLifetime $(, $MoreLifetime)*>)? {
+            owner: $Owner,
+
+            #[$Covariance, shared]
+            dependent: {[$($DependentPath)::+] [$($($DependentArg),*)?]},
+        }
+
+        $($Rest)*
+    );
+};
+(
+    $(#[$StructMeta:meta])*
+    $Vis:vis struct $StructName:ident $(<$OwnerLifetime:lifetime $(, $MoreLifetime:lifetime)*>)? {
+        owner: $Owner:ty,
+
+        dependents: {
+            $(
+                #[$Covariance:ident]
+                $field:ident: $Dependent:tt,
+            )+
+        }
+    }
+
+    $(impl {$($AutomaticDerive:ident $(($($DeriveArgs:tt)*))?),*})?
+) => {
+    $crate::_self_cell_require_alloc!();
+
+    #[repr(transparent)]
+    $(#[$StructMeta])*
+    $Vis struct $StructName $(<$OwnerLifetime $(, $MoreLifetime)*>)? {
+        unsafe_self_cell: $crate::unsafe_self_cell::UnsafeSelfCell<
+            $StructName$(<$OwnerLifetime $(, $MoreLifetime)*>)?,
+            $Owner,
+            $crate::_self_cell_dependents_type!('static; $($Dependent),+)
+        >,
+
+        // The owner lifetime is only covariant if all dependents are, to keep
+        // things simple it's always treated as invariant.
+        $(owner_marker: $crate::_covariant_owner_marker!(not_covariant, $OwnerLifetime $(, $MoreLifetime)*) ,)?
+    }
+
+    impl <$($OwnerLifetime $(, $MoreLifetime)*)?> $StructName <$($OwnerLifetime $(, $MoreLifetime)*)?> {
+        $crate::_self_cell_dependents!(
+            @munch ($Vis, $Owner, [$($OwnerLifetime $(, $MoreLifetime)*)?], [$($Dependent),+])
+            [] [] [] []
+            $(#[$Covariance] $field: $Dependent,)+
+        );
+
+        /// Borrows owner.
+        $Vis fn borrow_owner<'_q>(&'_q self) -> &'_q $Owner {
+            unsafe { &*self.unsafe_self_cell.field_pointers().0 }
+        }
+
+        /// Consumes `self` and returns the the owner.
+        $Vis fn into_owner(self) -> $Owner {
+            // This is only safe to do with repr(transparent).
+            let unsafe_self_cell = unsafe { ::core::mem::transmute::<
+                Self,
+                $crate::unsafe_self_cell::UnsafeSelfCell<
+                    $StructName$(<$OwnerLifetime $(, $MoreLifetime)*>)?,
+                    $Owner,
+                    $crate::_self_cell_dependents_type!('static; $($Dependent),+)
+                >
+            >(self) };
+
+            unsafe { unsafe_self_cell.into_owner_list() }
+        }
+    }
+
+    impl $(<$OwnerLifetime $(, $MoreLifetime)*>)? Drop for $StructName $(<$OwnerLifetime $(, $MoreLifetime)*>)? {
+        fn drop(&mut self) {
+            unsafe {
+                self.unsafe_self_cell.drop_joined_list();
+            }
+        }
+    }
+
+    // The user has to choose which traits can and should be automatically
+    // implemented for the cell.
+    $crate::_impl_automatic_derive_dependents!(
+        [$($($AutomaticDerive $(($($DeriveArgs)*))?),*)?],
+        $StructName,
+        {[$($OwnerLifetime $(, $MoreLifetime)*)?] [$($OwnerLifetime $(, $MoreLifetime)*)?] []},
+        [$($field),+],
+        $Owner,
+        [$($Dependent),+]
+    );
+};
+(
+    $(#[$StructMeta:meta])*
+    $Vis:vis struct $StructName:ident $(<$OwnerLifetime:lifetime $(, $MoreLifetime:lifetime)*>)? {
+        owner: $Owner:ty,
+
+        dependents: {
+            $(
+                #[$Covariance:ident]
+                $field:ident: $($DependentPath:ident)::+ $(<
+                    $DependentLifetime:lifetime $(, $DependentArg:ty)* $(,)?
+                >)?,
+            )+
+        }
+    }
+
+    $($Rest:tt)*
+) => {
+    $crate::self_cell!(
+        $(#[$StructMeta])*
+        $Vis struct $StructName $(<$OwnerLifetime $(, $MoreLifetime)*>)? {
+            owner: $Owner,
+
+            dependents: {
+                $(
+                    #[$Covariance]
+                    $field: {[$($DependentPath)::+] [$($($DependentArg),*)?]},
+                )+
+            }
+        }
+
+        $($Rest)*
+    );
+};
+(
+    $(#[$StructMeta:meta])*
+    $Vis:vis struct $StructName:ident $(<$OwnerLifetime:lifetime $(, $MoreLifetime:lifetime)*>)? {
+        owners: {
+            $($owner_field:ident: $Owner:ty,)+
+        }
+
+        #[$Covariance:ident]
+        dependent: $Dependent:tt,
+    }
+
+    $(impl {$($AutomaticDerive:ident $(($($DeriveArgs:tt)*))?),*})?
+) => {
+    $crate::_self_cell_require_alloc!();
+
+    #[repr(transparent)]
+    $(#[$StructMeta])*
+    $Vis struct $StructName $(<$OwnerLifetime $(, $MoreLifetime)*>)? {
+        unsafe_self_cell: $crate::unsafe_self_cell::UnsafeSelfCell<
+            $StructName$(<$OwnerLifetime $(, $MoreLifetime)*>)?,
+            ($($Owner,)+),
+            $crate::_self_cell_dependent!($Dependent, 'static)
+        >,
+
+        $(owner_marker: $crate::_covariant_owner_marker!($Covariance, $OwnerLifetime $(, $MoreLifetime)*) ,)?
+    }
+
+    $crate::_self_cell_dependent_impl!($Dependent, [$($OwnerLifetime $(, $MoreLifetime)*)?], []);
+
+    impl <$($OwnerLifetime $(, $MoreLifetime)*)?> $StructName <$($OwnerLifetime $(, $MoreLifetime)*)?> {
+        /// Constructs a new self-referential struct.
+        ///
+        /// The provided owners will be moved into a heap allocated box. Followed by construction
+        /// of the dependent value, by calling `dependent_builder` with a shared reference to each
+        /// owner that remains valid for the lifetime of the constructed struct.
+        $Vis fn new(
+            $($owner_field: $Owner,)+
+            dependent_builder: impl for<'_q> ::core::ops::FnOnce($(&'_q $Owner),+) -> $crate::_self_cell_dependent!($Dependent, '_q)
+        ) -> Self {
+            type JoinedCell<'_q $(, $OwnerLifetime $(, $MoreLifetime)*)?> =
+                $crate::unsafe_self_cell::JoinedCell<($($Owner,)+), $crate::_self_cell_dependent!($Dependent, '_q)>;
+
+            // unsafe placed here to make sure the body macro can't be abused.
+            unsafe {
+                $crate::_self_cell_new_body!(
+                    JoinedCell,
+                    ($($owner_field,)+),
+                    $crate::unsafe_self_cell::DefaultAllocator,
+                    $crate::alloc::alloc::handle_alloc_error(
+                        $crate::alloc::alloc::Layout::new::<JoinedCell>()
+                    )
+                    $(=> $OwnerLifetime)?,
+                    |owners| {
+                        let &($(ref $owner_field,)+) = owners;
+                        dependent_builder($($owner_field),+)
+                    }
+                )
+            }
+        }
+
+        /// Constructs a new self-referential struct or returns an error.
+        ///
+        /// Consumes owners on error.
+        $Vis fn try_new<Err>(
+            $($owner_field: $Owner,)+
+            dependent_builder: impl for<'_q> ::core::ops::FnOnce($(&'_q $Owner),+)
+                -> ::core::result::Result<$crate::_self_cell_dependent!($Dependent, '_q), Err>
+        ) -> ::core::result::Result<Self, Err> {
+            type JoinedCell<'_q $(, $OwnerLifetime $(, $MoreLifetime)*)?> =
+                $crate::unsafe_self_cell::JoinedCell<($($Owner,)+), $crate::_self_cell_dependent!($Dependent, '_q)>;
+
+            // unsafe placed here to make sure the body macro can't be abused.
+            unsafe {
+                $crate::_self_cell_try_new_body!(
+                    JoinedCell,
+                    ($($owner_field,)+),
+                    $crate::unsafe_self_cell::DefaultAllocator,
+                    $crate::alloc::alloc::handle_alloc_error(
+                        $crate::alloc::alloc::Layout::new::<JoinedCell>()
+                    )
+                    $(=> $OwnerLifetime)?,
+                    |owners| {
+                        let &($(ref $owner_field,)+) = owners;
+                        dependent_builder($($owner_field),+)
+                    }
+                )
+            }
+        }
+
+        /// Constructs a new self-referential struct or returns an error.
+        ///
+        /// Returns owners and error as tuple on error.
+        $Vis fn try_new_or_recover<Err>(
+            $($owner_field: $Owner,)+
+            dependent_builder: impl for<'_q> ::core::ops::FnOnce($(&'_q $Owner),+)
+                -> ::core::result::Result<$crate::_self_cell_dependent!($Dependent, '_q), Err>
+        ) -> ::core::result::Result<Self, (($($Owner,)+), Err)> {
+            type JoinedCell<'_q $(, $OwnerLifetime $(, $MoreLifetime)*)?> =
+                $crate::unsafe_self_cell::JoinedCell<($($Owner,)+), $crate::_self_cell_dependent!($Dependent, '_q)>;
+
+            // unsafe placed here to make sure the body macro can't be abused.
+            unsafe {
+                $crate::_self_cell_try_new_or_recover_body!(
+                    JoinedCell,
+                    ($($owner_field,)+),
+                    $crate::unsafe_self_cell::DefaultAllocator,
+                    $crate::alloc::alloc::handle_alloc_error(
+                        $crate::alloc::alloc::Layout::new::<JoinedCell>()
+                    )
+                    $(=> $OwnerLifetime)?,
+                    |owners| {
+                        let &($(ref $owner_field,)+) = owners;
+                        dependent_builder($($owner_field),+)
+                    }
+                )
+            }
+        }
+
+        /// Borrows all owners as tuple.
+        $Vis fn borrow_owners<'_q>(&'_q self) -> &'_q ($($Owner,)+) {
+            unsafe { self.unsafe_self_cell.borrow_owner::<$crate::_self_cell_dependent!($Dependent, '_q)>() }
+        }
+
+        $crate::_self_cell_owner_access!(
+            $Vis, [$($owner_field),+], $($owner_field: $Owner),+
+        );
+
+        /// Calls given closure `func` with a shared reference to each owner and dependent.
+        $Vis fn with_dependent<'outer_fn, Ret>(
+            &'outer_fn self,
+            func: impl for<'_q> ::core::ops::FnOnce($(&'_q $Owner,)+ &'outer_fn $crate::_self_cell_dependent!($Dependent, '_q)
+        ) -> Ret) -> Ret {
+            let ($($owner_field,)+) = self.borrow_owners();
+
+            func($($owner_field,)+ unsafe { self.unsafe_self_cell.borrow_dependent() })
+        }
+
+        /// Calls given closure `func` with an unique reference to dependent.
+        $Vis fn with_dependent_mut<'outer_fn, Ret>(
+            &'outer_fn mut self,
+            func: impl for<'_q> ::core::ops::FnOnce($(&'_q $Owner,)+ &'outer_fn mut $crate::_self_cell_dependent!($Dependent, '_q))
+                -> Ret
+        ) -> Ret {
+            let (owners, dependent) = unsafe {
+                    self.unsafe_self_cell.borrow_mut()
+            };
+            let ($($owner_field,)+) = owners;
+
+            func($($owner_field,)+ dependent)
+        }
+
+        $crate::_covariant_access!($Covariance, $Vis, [], [borrow_dependent], $Dependent);
+
+        /// Consumes `self` and returns the the owners as tuple.
+        $Vis fn into_owners(self) -> ($($Owner,)+) {
+            // This is only safe to do with repr(transparent).
+            let unsafe_self_cell = unsafe { ::core::mem::transmute::<
+                Self,
+                $crate::unsafe_self_cell::UnsafeSelfCell<
+                    $StructName$(<$OwnerLifetime $(, $MoreLifetime)*>)?,
+                    ($($Owner,)+),
+                    $crate::_self_cell_dependent!($Dependent, 'static)
+                >
+            >(self) };
+
+            unsafe { unsafe_self_cell.into_owner::<$crate::_self_cell_dependent!($Dependent, '_)>() }
+        }
+    }
+
+    impl $(<$OwnerLifetime $(, $MoreLifetime)*>)? Drop for $StructName $(<$OwnerLifetime $(, $MoreLifetime)*>)? {
+        fn drop(&mut self) {
+            unsafe {
+                self.unsafe_self_cell.drop_joined::<$crate::_self_cell_dependent!($Dependent, '_)>();
+            }
+        }
+    }
+
+    // The user has to choose which traits can and should be automatically
+    // implemented for the cell.
+    $crate::_impl_automatic_derive_owners!(
+        [$($($AutomaticDerive $(($($DeriveArgs)*))?),*)?],
+        $StructName,
+        {[$($OwnerLifetime $(, $MoreLifetime)*)?] [$($OwnerLifetime $(, $MoreLifetime)*)?] []},
+        [$($owner_field),+],
+        ($($Owner,)+),
+        $Dependent
+    );
+};
+(
+    $(#[$StructMeta:meta])*
+    $Vis:vis struct $StructName:ident $(<$OwnerLifetime:lifetime $(, $MoreLifetime:lifetime)*>)? {
+        owners: {
+            $($owner_field:ident: $Owner:ty,)+
+        }
+
+        #[$Covariance:ident]
+        dependent: for<$DependentLifetime:lifetime> $Dependent:ty,
+    }
+
+    $($Rest:tt)*
+) => {
+    $crate::self_cell!(
+        $(#[$StructMeta])*
+        $Vis struct $StructName $(<$OwnerLifetime $(, $MoreLifetime)*>)? {
+            owners: {
+                $($owner_field: $Owner,)+
+            }
+
+            #[$Covariance]
+            dependent: {for [$DependentLifetime] [$Dependent] [$StructName $(<$OwnerLifetime $(, $MoreLifetime)*>)?]},
+        }
+
+        $($Rest)*
+    );
+};
+(
+    $(#[$StructMeta:meta])*
+    $Vis:vis struct $StructName:ident $(<$OwnerLifetime:lifetime $(, $MoreLifetime:lifetime)*>)? {
+        owners: {
+            $($owner_field:ident: $Owner:ty,)+
+        }
+
+        #[$Covariance:ident]
+        dependent: $($DependentPath:ident)::+ $(<
+            $DependentLifetime:lifetime $(, $DependentArg:ty)* $(,)?
+        >)?,
+    }
+
+    $($Rest:tt)*
+) => {
+    $crate::self_cell!(
+        $(#[$StructMeta])*
+        $Vis struct $StructName $(<$OwnerLifetime $(, $MoreLifetime)*>)? {
+            owners: {
+                $($owner_field: $Owner,)+
+            }
+
+            #[$Covariance]
+            dependent: {[$($DependentPath)::+] [$($($DependentArg),*)?]},
+        }
+
+        $($Rest)*
+    );
+};
+(
+    $(#[$StructMeta:meta])*
+    $Vis:vis struct $StructName:ident {
+        $($body:tt)*
+    }
+
+    $(methods $Methods:tt)?
+
+    $(impl {$($AutomaticDerive:ident $(($($DeriveArgs:tt)*))?),*})?
+) => {
+    // Structs without generics are forwarded to the main arm below with an
+    // empty parameter list.
+    $crate::self_cell!(
+        $(#[$StructMeta])*
+        $Vis struct $StructName<> {
+            $($body)*
+        }
+
+        $(methods $Methods)?
+
+        $(impl {$($AutomaticDerive $(($($DeriveArgs)*))?),*})?
+    );
+};
+(
+    $(#[$StructMeta:meta])*
+    $Vis:vis struct $StructName:ident<
+        $($OwnerLifetime:lifetime $(, $MoreLifetime:lifetime)*)? $(,)?
+        $($Param:ident $($ConstParam:ident)? $(: $ParamBound:path)?),*
+    > $(where $($WhereTy:ty: $WhereBound:path),* $(,)?)? {
+        owner: $Owner:ty,
+
+
+        #[$Covariance:ident $(, $AsyncBuilder:ident)?]
+        dependent: for<$DependentLifetime:lifetime> $Dependent:ty,
+
+        $(allocator: $Allocator:ty,)?
+    }
+
+    $(methods $Methods:tt)?
+
+    $(impl {$($AutomaticDerive:ident $(($($DeriveArgs:tt)*))?),*})?
+) => {
+    $crate::_self_cell_heap!(
+        $(#[$StructMeta])*
+        $Vis struct $StructName<
+            $($OwnerLifetime, $($MoreLifetime,)*)? $($Param $($ConstParam)? $(: $ParamBound)?),*
+        > $(where $($WhereTy: $WhereBound),*)? {
+            #[owner(borrow_owner, into_owner)]
+            owner: $Owner,
+
+
+            #[$Covariance $(, $AsyncBuilder)?]
+            #[dependent([borrow_dependent], with_dependent, with_dependent_mut)]
+            dependent: {for [$DependentLifetime] [$Dependent] [
+                $StructName<
+                    $($OwnerLifetime, $($MoreLifetime,)*)?
+                    $($crate::_self_cell_generic_arg!($Param $($ConstParam)?)),*
+                >
+            ]},
+
+            $(allocator: $Allocator,)?
+        }
+
+        methods [$($Methods)?]
+
+        $(impl {$($AutomaticDerive $(($($DeriveArgs)*))?),*})?
+    );
+};
+(
+    $(#[$StructMeta:meta])*
+    $Vis:vis struct $StructName:ident<
+        $($OwnerLifetime:lifetime $(, $MoreLifetime:lifetime)*)? $(,)?
+        $($Param:ident $($ConstParam:ident)? $(: $ParamBound:path)?),*
+    > $(where $($WhereTy:ty: $WhereBound:path),* $(,)?)? {
+        owner: $Owner:ty,
+
+
+        #[$Covariance:ident $(, $AsyncBuilder:ident)?]
+        dependent: $($DependentPath:ident)::+ $(<
+            $DependentLifetime:lifetime $(, $DependentArg:ty)* $(,)?
+        >)?,
+
+        $(allocator: $Allocator:ty,)?
+    }
+
+    $(methods $Methods:tt)?
+
+    $(impl {$($AutomaticDerive:ident $(($($DeriveArgs:tt)*))?),*})?
+) => {
+    $crate::_self_cell_heap!(
+        $(#[$StructMeta])*
+        $Vis struct $StructName<
+            $($OwnerLifetime, $($MoreLifetime,)*)? $($Param $($ConstParam)? $(: $ParamBound)?),*
+        > $(where $($WhereTy: $WhereBound),*)? {
+            #[owner(borrow_owner, into_owner)]
+            owner: $Owner,
+
+
+            #[$Covariance $(, $AsyncBuilder)?]
+            #[dependent([borrow_dependent], with_dependent, with_dependent_mut)]
+            dependent: {[$($DependentPath)::+] [$($($DependentArg),*)?]},
+
+            $(allocator: $Allocator,)?
+        }
+
+        methods [$($Methods)?]
+
+        $(impl {$($AutomaticDerive $(($($DeriveArgs)*))?),*})?
+    );
+};
+(
+    $(#[$StructMeta:meta])*
+    $Vis:vis struct $StructName:ident<
+        $($OwnerLifetime:lifetime $(, $MoreLifetime:lifetime)*)? $(,)?
+        $($Param:ident $($ConstParam:ident)? $(: $ParamBound:path)?),*
+    > $(where $($WhereTy:ty: $WhereBound:path),* $(,)?)? {
+        #[owner(borrow = $BorrowOwner:ident, into = $IntoOwner:ident $(,)?)]
+        $OwnerField:ident: $Owner:ty,
+
+
+        #[$Covariance:ident $(, $AsyncBuilder:ident)?]
+        #[dependent(
+            $(borrow = $BorrowDependent:ident,)?
+            with = $WithDependent:ident,
+            with_mut = $WithDependentMut:ident $(,)?
+        )]
+        $DependentField:ident: for<$DependentLifetime:lifetime> $Dependent:ty,
+
+        $(allocator: $Allocator:ty,)?
+    }
+
+    $(methods $Methods:tt)?
+
+    $(impl {$($AutomaticDerive:ident $(($($DeriveArgs:tt)*))?),*})?
+) => {
+    $crate::_self_cell_heap!(
+        $(#[$StructMeta])*
+        $Vis struct $StructName<
+            $($OwnerLifetime, $($MoreLifetime,)*)? $($Param $($ConstParam)? $(: $ParamBound)?),*
+        > $(where $($WhereTy: $WhereBound),*)? {
+            #[owner($BorrowOwner, $IntoOwner)]
+            $OwnerField: $Owner,
+
+
+            #[$Covariance $(, $AsyncBuilder)?]
+            #[dependent([$($BorrowDependent)?], $WithDependent, $WithDependentMut)]
+            $DependentField: {for [$DependentLifetime] [$Dependent] [
+                $StructName<
+                    $($OwnerLifetime, $($MoreLifetime,)*)?
+                    $($crate::_self_cell_generic_arg!($Param $($ConstParam)?)),*
+                >
+            ]},
+
+            $(allocator: $Allocator,)?
+        }
+
+        methods [$($Methods)?]
+
+        $(impl {$($AutomaticDerive $(($($DeriveArgs)*))?),*})?
+    );
+};
+(
+    $(#[$StructMeta:meta])*
+    $Vis:vis struct $StructName:ident<
+        $($OwnerLifetime:lifetime $(, $MoreLifetime:lifetime)*)? $(,)?
+        $($Param:ident $($ConstParam:ident)? $(: $ParamBound:path)?),*
+    > $(where $($WhereTy:ty: $WhereBound:path),* $(,)?)? {
+        #[owner(borrow = $BorrowOwner:ident, into = $IntoOwner:ident $(,)?)]
+        $OwnerField:ident: $Owner:ty,
+
+
+        #[$Covariance:ident $(, $AsyncBuilder:ident)?]
+        #[dependent(
+            $(borrow = $BorrowDependent:ident,)?
+            with = $WithDependent:ident,
+            with_mut = $WithDependentMut:ident $(,)?
+        )]
+        $DependentField:ident: $($DependentPath:ident)::+ $(<
+            $DependentLifetime:lifetime $(, $DependentArg:ty)* $(,)?
+        >)?,
+
+        $(allocator: $Allocator:ty,)?
+    }
+
+    $(methods $Methods:tt)?
+
+    $(impl {$($AutomaticDerive:ident $(($($DeriveArgs:tt)*))?),*})?
+) => {
+    $crate::_self_cell_heap!(
+        $(#[$StructMeta])*
+        $Vis struct $StructName<
+            $($OwnerLifetime, $($MoreLifetime,)*)? $($Param $($ConstParam)? $(: $ParamBound)?),*
+        > $(where $($WhereTy: $WhereBound),*)? {
+            #[owner($BorrowOwner, $IntoOwner)]
+            $OwnerField: $Owner,
+
+
+            #[$Covariance $(, $AsyncBuilder)?]
+            #[dependent([$($BorrowDependent)?], $WithDependent, $WithDependentMut)]
+            $DependentField: {[$($DependentPath)::+] [$($($DependentArg),*)?]},
+
+            $(allocator: $Allocator,)?
+        }
+
+        methods [$($Methods)?]
+
+        $(impl {$($AutomaticDerive $(($($DeriveArgs)*))?),*})?
+    );
+};
+}
+
+#[doc(hidden)]
+#[macro_export]
+macro_rules! _self_cell_heap {
+    (
+        $(#[$StructMeta:meta])*
+        $Vis:vis struct $StructName:ident<
+            $($OwnerLifetime:lifetime $(, $MoreLifetime:lifetime)*)? $(,)?
+            $($Param:ident $($ConstParam:ident)? $(: $ParamBound:path)?),*
+        > $(where $($WhereTy:ty: $WhereBound:path),* $(,)?)? {
+            #[owner($BorrowOwner:ident, $IntoOwner:ident)]
+            $OwnerField:ident: $Owner:ty,
+
+
+            #[$Covariance:ident $(, $AsyncBuilder:ident)?]
+            #[dependent($BorrowDependent:tt, $WithDependent:ident, $WithDependentMut:ident)]
+            $DependentField:ident: $Dependent:tt,
+
+            $(allocator: $Allocator:ty,)?
+        }
+
+        methods [$($Methods:tt)?]
+
+        $(impl {$($AutomaticDerive:ident $(($($DeriveArgs:tt)*))?),*})?
+    ) => {
+        $crate::_self_cell_require_alloc!();
+        $crate::_self_cell_check_methods!($($Methods)?);
+
+        #[repr(transparent)]
+        $(#[$StructMeta])*
+        $Vis struct $StructName<
+            $($OwnerLifetime, $($MoreLifetime,)*)? $($Param $($ConstParam)? $(: $ParamBound)?),*
+        > where
+            $($($WhereTy: $WhereBound,)*)?
+            $($crate::_self_cell_param_type!($Param $($ConstParam)?): 'static,)*
+        {
+            unsafe_self_cell: $crate::unsafe_self_cell::UnsafeSelfCell<
+                $StructName<
+                    $($OwnerLifetime, $($MoreLifetime,)*)? $($crate::_self_cell_generic_arg!($Param $($ConstParam)?)),*
+                >,
+                $Owner,
+                $crate::_self_cell_dependent!($Dependent, 'static),
+                $crate::_self_cell_allocator!($($Allocator)?)
+            >,
+
+            $(owner_marker: $crate::_covariant_owner_marker!($Covariance, $OwnerLifetime $(, $MoreLifetime)*) ,)?
+        }
+
+        $crate::_self_cell_dependent_impl!(
+            $Dependent,
+            [$($OwnerLifetime, $($MoreLifetime,)*)? $($Param $($ConstParam)? $(: $ParamBound)?),*],
+            [
+                $($($WhereTy: $WhereBound,)*)?
+                $($crate::_self_cell_param_type!($Param $($ConstParam)?): 'static,)*
+            ]
+        );
+
+        impl<$($OwnerLifetime, $($MoreLifetime,)*)? $($Param $($ConstParam)? $(: $ParamBound)?),*> $StructName<
+            $($OwnerLifetime, $($MoreLifetime,)*)? $($crate::_self_cell_generic_arg!($Param $($ConstParam)?)),*
+        > where
+            $($($WhereTy: $WhereBound,)*)?
+            $($crate::_self_cell_param_type!($Param $($ConstParam)?): 'static,)*
+        {
+            $crate::_self_cell_constructors!(
+                $Vis, [$($Allocator)?], $Owner $(=> $OwnerLifetime)?, $Dependent $(, $AsyncBuilder)?
+            );
+
+            $crate::_self_cell_method!(borrow_owner, $Vis, [$($Methods)?], {
+                /// Borrows owner.
+                fn $BorrowOwner<'_q>(&'_q self) -> &'_q $Owner {
+                    unsafe {
+                        self.unsafe_self_cell
+                            .borrow_owner::<$crate::_self_cell_dependent!($Dependent, '_q)>()
+                    }
+                }
+            });
+
+            $crate::_self_cell_method!(with_dependent, $Vis, [$($Methods)?], {
+                /// Calls given closure `func` with a shared reference to dependent.
+                fn $WithDependent<'outer_fn, Ret>(
+                    &'outer_fn self,
+                    func: impl for<'_q> ::core::ops::FnOnce(
+                        &'_q $Owner,
+                        &'outer_fn $crate::_self_cell_dependent!($Dependent, '_q)
+                    ) -> Ret
+                ) -> Ret {
+                    unsafe {
+                        func(
+                            self.unsafe_self_cell
+                                .borrow_owner::<$crate::_self_cell_dependent!($Dependent, '_)>(),
+                            self.unsafe_self_cell.borrow_dependent()
+                        )
+                    }
+                }
+            });
+
+            $crate::_self_cell_method!(with_dependent_mut, $Vis, [$($Methods)?], {
+                /// Calls given closure `func` with an unique reference to dependent.
+                fn $WithDependentMut<'outer_fn, Ret>(
+                    &'outer_fn mut self,
+                    func: impl for<'_q> ::core::ops::FnOnce(
+                        &'_q $Owner,
+                        &'outer_fn mut $crate::_self_cell_dependent!($Dependent, '_q)
+                    ) -> Ret
+                ) -> Ret {
+                    let (owner, dependent) = unsafe {
+                            self.unsafe_self_cell.borrow_mut()
+                    };
+
+                    func(owner, dependent)
+                }
+            });
+
+            $crate::_covariant_access!($Covariance, $Vis, [$($Methods)?], $BorrowDependent, $Dependent);
+
+            $crate::_self_cell_method!(into_owner, $Vis, [$($Methods)?], {
+                /// Consumes `self` and returns the the owner.
+                fn $IntoOwner(self) -> $Owner {
+                    // This is only safe to do with repr(transparent).
+                    let unsafe_self_cell = unsafe { ::core::mem::transmute::<
+                        Self,
+                        $crate::unsafe_self_cell::UnsafeSelfCell<
+                            Self,
+                            $Owner,
+                            $crate::_self_cell_dependent!($Dependent, 'static),
+                            $crate::_self_cell_allocator!($($Allocator)?)
+                        >
+                    >(self) };
+
+                    let owner = unsafe { unsafe_self_cell.into_owner::<$crate::_self_cell_dependent!($Dependent, '_)>() };
+
+                    owner
+                }
+            });
+
+            $crate::_self_cell_replace_owner!($Vis, [$($Methods)?], $Owner $(=> $OwnerLifetime)?, $Dependent);
+
+            $crate::_self_cell_with_owner_mut!($Vis, [$($Methods)?], $Owner $(=> $OwnerLifetime)?, $Dependent);
+        }
+
+        impl<$($OwnerLifetime, $($MoreLifetime,)*)? $($Param $($ConstParam)? $(: $ParamBound)?),*> Drop for $StructName<
+            $($OwnerLifetime, $($MoreLifetime,)*)? $($crate::_self_cell_generic_arg!($Param $($ConstParam)?)),*
+        > where
+            $($($WhereTy: $WhereBound,)*)?
+            $($crate::_self_cell_param_type!($Param $($ConstParam)?): 'static,)*
+        {
+            fn drop(&mut self) {
+                unsafe {
+                    self.unsafe_self_cell.drop_joined::<$crate::_self_cell_dependent!($Dependent, '_)>();
+                }
+            }
+        }
+
+        // The user has to choose which traits can and should be automatically
+        // implemented for the cell.
+        $crate::_impl_automatic_derive!(
+            [$($($AutomaticDerive $(($($DeriveArgs)*))?),*)?],
+            $StructName,
+            {$OwnerField $BorrowOwner, $DependentField $WithDependent},
+            {
+                [$($OwnerLifetime, $($MoreLifetime,)*)? $($Param $($ConstParam)? $(: $ParamBound)?,)*]
+                [$($OwnerLifetime, $($MoreLifetime,)*)? $($crate::_self_cell_generic_arg!($Param $($ConstParam)?),)*]
+                [
+                    $($($WhereTy: $WhereBound,)*)?
+                    $($crate::_self_cell_param_type!($Param $($ConstParam)?): 'static,)*
+                ]
+            },
+            $Owner,
+            $Dependent
+        );
+    };
+}
+
+#[cfg(feature = "alloc")]
+#[doc(hidden)]
+#[macro_export]
+macro_rules! _self_cell_require_alloc {
+    () => {};
+}
+
+#[cfg(not(feature = "alloc"))]
+#[doc(hidden)]
+#[macro_export]
+macro_rules! _self_cell_require_alloc {
+    () => {
+        compile_error!(
+            "self_cell: heap allocated cells require the `alloc` feature, use `#[covariant, pinned]` or `#[not_covariant, pinned]` instead"
+        );
+    };
+}
+
+#[cfg(feature = "std")]
+#[doc(hidden)]
+#[macro_export]
+macro_rules! _self_cell_std {
+    ($($Item:tt)*) => {
+        $($Item)*
+    };
+}
+
+#[cfg(not(feature = "std"))]
+#[doc(hidden)]
+#[macro_export]
+macro_rules! _self_cell_std {
+    ($($Item:tt)*) => {
+        compile_error!("self_cell: automatic Error impls require the `std` feature");
+    };
+}
+
+#[cfg(feature = "serde")]
+#[doc(hidden)]
+#[macro_export]
+macro_rules! _self_cell_serde {
+    ($($Item:tt)*) => {
+        $($Item)*
+    };
+}
+
+#[cfg(not(feature = "serde"))]
+#[doc(hidden)]
+#[macro_export]
+macro_rules! _self_cell_serde {
+    ($($Item:tt)*) => {
+        compile_error!(
+            "self_cell: automatic Serialize and Deserialize impls require the `serde` feature"
+        );
+    };
+}
+
+#[doc(hidden)]
+#[macro_export]
+macro_rules! _self_cell_generic_arg {
+    (const $ConstParam:ident) => {
+        $ConstParam
+    };
+    ($Param:ident) => {
+        $Param
+    };
+}
+
+#[doc(hidden)]
+#[macro_export]
+macro_rules! _self_cell_param_type {
+    // Const parameters can't hold a lifetime, `()` keeps their `'static`
+    // predicate trivially true.
+    (const $ConstParam:ident) => {
+        ()
+    };
+    ($Param:ident) => {
+        $Param
+    };
+}
+
+#[doc(hidden)]
+#[macro_export]
+macro_rules! _self_cell_dependent {
+    // Dependent named by a single identifier, eg. `Ast`.
+    ($Dependent:ident, $Lifetime:lifetime) => {
+        $Dependent<$Lifetime>
+    };
+    // Dependent named by a path and further generic arguments after the
+    // borrowed lifetime, eg. `crate::ast::Ast<'_, T>`.
+    ({[$($DependentPath:tt)+] [$($DependentArg:ty),*]}, $Lifetime:lifetime) => {
+        $($DependentPath)+<$Lifetime $(, $DependentArg)*>
+    };
+    // Any other type with a named borrowed lifetime, eg. `for<'a> Option<&'a str>`,
+    // see _self_cell_dependent_impl.
+    ({for [$DependentLifetime:lifetime] [$Dependent:ty] [$($SelfTy:tt)+]}, $Lifetime:lifetime) => {
+        <$($SelfTy)+ as $crate::unsafe_self_cell::DependentType<$Lifetime>>::Dependent
+    };
+}
+
+#[doc(hidden)]
+#[macro_export]
+macro_rules! _self_cell_dependent_impl {
+    // Implements DependentType for the cell struct, for dependents declared
+    // with `for<'a> Type`.
+    (
+        {for [$DependentLifetime:lifetime] [$Dependent:ty] [$($SelfTy:tt)+]},
+        [$($Params:tt)*],
+        [$($Where:tt)*]
+    ) => {
+        impl<$DependentLifetime, $($Params)*> $crate::unsafe_self_cell::DependentType<$DependentLifetime>
+            for $($SelfTy)+
+        where
+            $($Where)*
+        {
+            type Dependent = $Dependent;
+        }
+    };
+    ($Dependent:tt, $Params:tt, $Where:tt) => {};
+}
+
+#[doc(hidden)]
+#[macro_export]
+macro_rules! _self_cell_method {
+    // Emits the method `$Item` with the visibility the user chose for
+    // `$Method` in `methods { .. }`, `$Vis` if it isn't listed and nothing
+    // for `none`. Method names can't be compared generically, so each one
+    // has its own arms.
+    ($Method:ident, $Vis:vis, [], $Item:tt) => {
+        $crate::_self_cell_method!(@emit $Vis, $Item);
+    };
+    ($Method:ident, $Vis:vis, [{$($Methods:tt)*}], $Item:tt) => {
+        $crate::_self_cell_method!(@find $Method, $Vis, [$($Methods)*], $Item);
+    };
+    (@find $Method:ident, $Vis:vis, [$(,)?], $Item:tt) => {
+        $crate::_self_cell_method!(@emit $Vis, $Item);
+    };
+    (@find borrow_owner, $Vis:vis, [borrow_owner: none $(, $($Rest:tt)*)?], $Item:tt) => {};
+    (@find borrow_owner, $Vis:vis, [borrow_owner: $MethodVis:vis $(, $($Rest:tt)*)?], $Item:tt) => {
+        $crate::_self_cell_method!(@emit $MethodVis, $Item);
+    };
+    (@find borrow_dependent, $Vis:vis, [borrow_dependent: none $(, $($Rest:tt)*)?], $Item:tt) => {};
+    (@find borrow_dependent, $Vis:vis, [borrow_dependent: $MethodVis:vis $(, $($Rest:tt)*)?], $Item:tt) => {
+        $crate::_self_cell_method!(@emit $MethodVis, $Item);
+    };
+    (@find with_dependent, $Vis:vis, [with_dependent: none $(, $($Rest:tt)*)?], $Item:tt) => {};
+    (@find with_dependent, $Vis:vis, [with_dependent: $MethodVis:vis $(, $($Rest:tt)*)?], $Item:tt) => {
+        $crate::_self_cell_method!(@emit $MethodVis, $Item);
+    };
+    (@find with_dependent_mut, $Vis:vis, [with_dependent_mut: none $(, $($Rest:tt)*)?], $Item:tt) => {};
+    (@find with_dependent_mut, $Vis:vis, [with_dependent_mut: $MethodVis:vis $(, $($Rest:tt)*)?], $Item:tt) => {
+        $crate::_self_cell_method!(@emit $MethodVis, $Item);
+    };
+    (@find into_owner, $Vis:vis, [into_owner: none $(, $($Rest:tt)*)?], $Item:tt) => {};
+    (@find into_owner, $Vis:vis, [into_owner: $MethodVis:vis $(, $($Rest:tt)*)?], $Item:tt) => {
+        $crate::_self_cell_method!(@emit $MethodVis, $Item);
+    };
+    (@find replace_owner, $Vis:vis, [replace_owner: none $(, $($Rest:tt)*)?], $Item:tt) => {};
+    (@find replace_owner, $Vis:vis, [replace_owner: $MethodVis:vis $(, $($Rest:tt)*)?], $Item:tt) => {
+        $crate::_self_cell_method!(@emit $MethodVis, $Item);
+    };
+    (@find try_replace_owner, $Vis:vis, [try_replace_owner: none $(, $($Rest:tt)*)?], $Item:tt) => {};
+    (@find try_replace_owner, $Vis:vis, [try_replace_owner: $MethodVis:vis $(, $($Rest:tt)*)?], $Item:tt) => {
+        $crate::_self_cell_method!(@emit $MethodVis, $Item);
+    };
+    (@find try_replace_owner_or_recover, $Vis:vis, [try_replace_owner_or_recover: none $(, $($Rest:tt)*)?], $Item:tt) => {};
+    (@find try_replace_owner_or_recover, $Vis:vis, [try_replace_owner_or_recover: $MethodVis:vis $(, $($Rest:tt)*)?], $Item:tt) => {
+        $crate::_self_cell_method!(@emit $MethodVis, $Item);
+    };
+    (@find with_owner_mut, $Vis:vis, [with_owner_mut: none $(, $($Rest:tt)*)?], $Item:tt) => {};
+    (@find with_owner_mut, $Vis:vis, [with_owner_mut: $MethodVis:vis $(, $($Rest:tt)*)?], $Item:tt) => {
+        $crate::_self_cell_method!(@emit $MethodVis, $Item);
+    };
+    (@find try_with_owner_mut, $Vis:vis, [try_with_owner_mut: none $(, $($Rest:tt)*)?], $Item:tt) => {};
+    (@find try_with_owner_mut, $Vis:vis, [try_with_owner_mut: $MethodVis:vis $(, $($Rest:tt)*)?], $Item:tt) => {
+        $crate::_self_cell_method!(@emit $MethodVis, $Item);
+    };
+    (@find try_with_owner_mut_or_recover, $Vis:vis, [try_with_owner_mut_or_recover: none $(, $($Rest:tt)*)?], $Item:tt) => {};
+    (@find try_with_owner_mut_or_recover, $Vis:vis, [try_with_owner_mut_or_recover: $MethodVis:vis $(, $($Rest:tt)*)?], $Item:tt) => {
+        $crate::_self_cell_method!(@emit $MethodVis, $Item);
+    };
+    (@find $Method:ident, $Vis:vis, [$Other:ident: none $(, $($Rest:tt)*)?], $Item:tt) => {
+        $crate::_self_cell_method!(@find $Method, $Vis, [$($($Rest)*)?], $Item);
+    };
+    (@find $Method:ident, $Vis:vis, [$Other:ident: $OtherVis:vis $(, $($Rest:tt)*)?], $Item:tt) => {
+        $crate::_self_cell_method!(@find $Method, $Vis, [$($($Rest)*)?], $Item);
+    };
+    (@emit $Vis:vis, {$(#[$FnMeta:meta])* fn $($Fn:tt)*}) => {
+        $(#[$FnMeta])*
+        $Vis fn $($Fn)*
+    };
+}
+
+#[doc(hidden)]
+#[macro_export]
+macro_rules! _self_cell_check_methods {
+    () => {};
+    ({}) => {};
+    ({$Method:ident: none $(, $($Rest:tt)*)?}) => {
+        $crate::_self_cell_check_methods!(@name $Method);
+        $crate::_self_cell_check_methods!({$($($Rest)*)?});
+    };
+    ({$Method:ident: $MethodVis:vis $(, $($Rest:tt)*)?}) => {
+        $crate::_self_cell_check_methods!(@name $Method);
+        $crate::_self_cell_check_methods!({$($($Rest)*)?});
+    };
+    (@name borrow_owner) => {};
+    (@name borrow_dependent) => {};
+    (@name with_dependent) => {};
+    (@name with_dependent_mut) => {};
+    (@name into_owner) => {};
+    (@name replace_owner) => {};
+    (@name try_replace_owner) => {};
+    (@name try_replace_owner_or_recover) => {};
+    (@name with_owner_mut) => {};
+    (@name try_with_owner_mut) => {};
+    (@name try_with_owner_mut_or_recover) => {};
+    (@name $x:ident) => {
+        compile_error!(concat!("Unknown method in `methods`: ", stringify!($x)));
+    };
+}
+
+#[doc(hidden)]
+#[macro_export]
+macro_rules! _covariant_access {
+    (covariant, $Vis:vis, $Methods:tt, [$BorrowDependent:ident], $Dependent:ident) => {
+        $crate::_self_cell_method!(borrow_dependent, $Vis, $Methods, {
+            /// Borrows dependent.
+            fn $BorrowDependent<'_q>(&'_q self) -> &'_q $Dependent<'_q> {
+                fn _assert_covariance<'x: 'y, 'y>(x: &'y $Dependent<'x>) -> &'y $Dependent<'y> {
+                    //  This function only compiles for covariant types.
+                    x // Change the macro invocation to not_covariant.
+                }
+
+                unsafe { self.unsafe_self_cell.borrow_dependent() }
+            }
+        });
+    };
+    (covariant, $Vis:vis, $Methods:tt, [$BorrowDependent:ident], {[$Dependent:ident] []}) => {
+        $crate::_covariant_access!(covariant, $Vis, $Methods, [$BorrowDependent], $Dependent);
+    };
+    (covariant, $Vis:vis, $Methods:tt, [$BorrowDependent:ident], $Dependent:tt) => {
+        $crate::_self_cell_method!(borrow_dependent, $Vis, $Methods, {
+            /// Borrows dependent.
+            fn $BorrowDependent<'_q>(
+                &'_q self
+            ) -> &'_q $crate::_self_cell_dependent!($Dependent, '_q) {
+                // A nested function can't name the generic arguments of the
+                // dependent, so the same check is done with a function pointer.
+                let _assert_covariance: fn(
+                    &'_q $crate::_self_cell_dependent!($Dependent, 'static)
+                ) -> &'_q $crate::_self_cell_dependent!($Dependent, '_q) =
+                    //  This closure only compiles for covariant types.
+                    |x| x; // Change the macro invocation to not_covariant.
+
+                unsafe { self.unsafe_self_cell.borrow_dependent() }
+            }
+        });
+    };
+    (covariant, $Vis:vis, $Methods:tt, [], $Dependent:tt) => {
+        compile_error!("A covariant dependent with a custom field name needs a `borrow = ...` accessor name");
+    };
+    (not_covariant, $Vis:vis, $Methods:tt, $BorrowDependent:tt, $Dependent:tt) => {
+        // For types that are not covariant it's unsafe to allow
+        // returning direct references.
+        // For example a lifetime that is too short could be chosen:
+        // See https://github.com/Voultapher/self_cell/issues/5
+    };
+    ($x:ident, $Vis:vis, $Methods:tt, $BorrowDependent:tt, $Dependent:tt) => {
+        compile_error!("This macro only accepts `covariant` or `not_covariant`");
+    };
+}
+
+#[doc(hidden)]
+#[macro_export]
+macro_rules! _covariant_access_dependents {
+    (covariant, $Vis:vis, $field:ident, $Dependent:ident, [$($path:tt)*]) => {
+        /// Borrows the dependent of the same name.
+        $Vis fn $field<'_q>(&'_q self) -> &'_q $Dependent<'_q> {
+            fn _assert_covariance<'x: 'y, 'y>(x: &'y $Dependent<'x>) -> &'y $Dependent<'y> {
+                //  This function only compiles for covariant types.
+                x // Change the macro invocation to not_covariant.
+            }
+
+            unsafe {
+                let (_, dependents_ptr) = self.unsafe_self_cell.field_pointers();
+
+                &*(&(*dependents_ptr)$($path)* as *const $Dependent<'static>
+                    as *const $Dependent<'_q>)
+            }
+        }
+    };
+    (covariant, $Vis:vis, $field:ident, {[$Dependent:ident] []}, $path:tt) => {
+        $crate::_covariant_access_dependents!(covariant, $Vis, $field, $Dependent, $path);
+    };
+    (covariant, $Vis:vis, $field:ident, $Dependent:tt, [$($path:tt)*]) => {
+        /// Borrows the dependent of the same name.
+        $Vis fn $field<'_q>(&'_q self) -> &'_q $crate::_self_cell_dependent!($Dependent, '_q) {
+            // See _covariant_access, a nested function can't name the generic
+            // arguments of the dependent.
+            let _assert_covariance: fn(
+                &'_q $crate::_self_cell_dependent!($Dependent, 'static)
+            ) -> &'_q $crate::_self_cell_dependent!($Dependent, '_q) =
+                //  This closure only compiles for covariant types.
+                |x| x; // Change the macro invocation to not_covariant.
+
+            unsafe {
+                let (_, dependents_ptr) = self.unsafe_self_cell.field_pointers();
+
+                &*(&(*dependents_ptr)$($path)*
+                    as *const $crate::_self_cell_dependent!($Dependent, 'static)
+                    as *const $crate::_self_cell_dependent!($Dependent, '_q))
+            }
+        }
+    };
+    (not_covariant, $Vis:vis, $field:ident, $Dependent:tt, [$($path:tt)*]) => {
+        // For types that are not covariant it's unsafe to allow
+        // returning direct references.
+        // For example a lifetime that is too short could be chosen:
+        // See https://github.com/Voultapher/self_cell/issues/5
+    };
+    ($x:ident, $Vis:vis, $field:ident, $Dependent:tt, [$($path:tt)*]) => {
+        compile_error!("This macro only accepts `covariant` or `not_covariant`");
+    };
+}
+
+// The owners are stored as tuple, which can't be indexed by a macro
+// repetition, so the accessor destructures all of them.
+#[doc(hidden)]
+#[macro_export]
+macro_rules! _self_cell_owner_access {
+    // All owner names are needed in each accessor, so they are passed on as
+    // a single token tree.
+    ($Vis:vis, $all_fields:tt, $($field:ident: $Owner:ty),+) => {
+        $(
+            $crate::_self_cell_owner_access!(@accessor $Vis, $field: $Owner, $all_fields);
+        )+
+    };
+    (@accessor $Vis:vis, $field:ident: $Owner:ty, [$($all_fields:ident),+]) => {
+        /// Borrows the owner of the same name.
+        $Vis fn $field<'_q>(&'_q self) -> &'_q $Owner {
+            #[allow(unused_variables)]
+            let ($($all_fields,)+) = self.borrow_owners();
+
+            $field
+        }
+    };
+}
+
+#[doc(hidden)]
+#[macro_export]
 macro_rules! _covariant_owner_marker {
-    (covariant, $OwnerLifetime:lifetime) => {
+    (covariant, $($OwnerLifetime:lifetime),+) => {
         // Ensure that contravariant owners don't imply covariance
         // over the dependent. See issue https://github.com/Voultapher/self_cell/issues/18
-        ::core::marker::PhantomData<&$OwnerLifetime ()>
+        ::core::marker::PhantomData<($(&$OwnerLifetime (),)+)>
+    };
+    (not_covariant, $($OwnerLifetime:lifetime),+) => {
+        // See the discussion in https://github.com/Voultapher/self_cell/pull/29
+        //
+        // If the dependent is non_covariant, mark the owner as invariant over its
+        // lifetimes. Otherwise unsound use is possible.
+        ::core::marker::PhantomData<($(fn(&$OwnerLifetime ()) -> &$OwnerLifetime (),)+)>
+    };
+    ($x:ident, $($OwnerLifetime:lifetime),+) => {
+        compile_error!("This macro only accepts `covariant` or `not_covariant`");
+    };
+}
+
+#[doc(hidden)]
+#[macro_export]
+macro_rules! _covariant_owner_marker_ctor {
+    ($OwnerLifetime:lifetime) => {
+        // Helper to optionally expand into PhantomData for construction.
+        ::core::marker::PhantomData
+    };
+}
+
+#[doc(hidden)]
+#[macro_export]
+macro_rules! _self_cell_allocator {
+    () => {
+        $crate::unsafe_self_cell::DefaultAllocator
+    };
+    ($Allocator:ty) => {
+        $Allocator
+    };
+}
+
+#[doc(hidden)]
+#[macro_export]
+macro_rules! _self_cell_allocator_value {
+    () => {
+        $crate::unsafe_self_cell::DefaultAllocator
+    };
+    ($allocator:ident) => {
+        $allocator
+    };
+}
+
+#[doc(hidden)]
+#[macro_export]
+macro_rules! _self_cell_constructors {
+    ($Vis:vis, [], $Owner:ty $(=> $OwnerLifetime:lifetime)?, $Dependent:tt $(, $AsyncBuilder:ident)?) => {
+        $crate::_self_cell_new!(
+            $Vis, new, [], $Owner $(=> $OwnerLifetime)?, $Dependent $(, $AsyncBuilder)?
+        );
+
+        $crate::_self_cell_try_new!(
+            $Vis, try_new, [], $Owner $(=> $OwnerLifetime)?, $Dependent $(, $AsyncBuilder)?
+        );
+
+        $crate::_self_cell_try_new_or_recover!(
+            $Vis, try_new_or_recover, [], $Owner $(=> $OwnerLifetime)?, $Dependent $(, $AsyncBuilder)?
+        );
+
+        $crate::_self_cell_try_new_alloc!(
+            $Vis, try_new_alloc, [], $Owner $(=> $OwnerLifetime)?, $Dependent $(, $AsyncBuilder)?
+        );
+
+        $crate::_self_cell_try_new_or_recover_alloc!(
+            $Vis, try_new_or_recover_alloc, [], $Owner $(=> $OwnerLifetime)?, $Dependent $(, $AsyncBuilder)?
+        );
+
+        $crate::_self_cell_try_new_borrowed_err!(
+            $Vis, try_new_borrowed_err, [], $Owner $(=> $OwnerLifetime)?, $Dependent $(, $AsyncBuilder)?
+        );
+
+        $crate::_self_cell_new_catch_unwind!(
+            $Vis, new_catch_unwind, try_new_or_recover, [], $Owner, $Dependent $(, $AsyncBuilder)?
+        );
+    };
+    ($Vis:vis, [$Allocator:ty], $Owner:ty $(=> $OwnerLifetime:lifetime)?, $Dependent:tt $(, $AsyncBuilder:ident)?) => {
+        $crate::_self_cell_new!(
+            $Vis,
+            new_in,
+            [
+                #[doc = ""]
+                #[doc = "The heap allocation is made with `allocator`, which is stored in the struct"]
+                #[doc = "and used again to free the allocation."]
+                allocator: $Allocator
+            ],
+            $Owner $(=> $OwnerLifetime)?,
+            $Dependent $(, $AsyncBuilder)?
+        );
+
+        $crate::_self_cell_try_new!(
+            $Vis,
+            try_new_in,
+            [
+                #[doc = ""]
+                #[doc = "The heap allocation is made with `allocator`, which is stored in the struct"]
+                #[doc = "and used again to free the allocation."]
+                allocator: $Allocator
+            ],
+            $Owner $(=> $OwnerLifetime)?,
+            $Dependent $(, $AsyncBuilder)?
+        );
+
+        $crate::_self_cell_try_new_or_recover!(
+            $Vis,
+            try_new_or_recover_in,
+            [
+                #[doc = ""]
+                #[doc = "The heap allocation is made with `allocator`, which is stored in the struct"]
+                #[doc = "and used again to free the allocation. On error `allocator` is dropped."]
+                allocator: $Allocator
+            ],
+            $Owner $(=> $OwnerLifetime)?,
+            $Dependent $(, $AsyncBuilder)?
+        );
+
+        $crate::_self_cell_try_new_alloc!(
+            $Vis,
+            try_new_alloc_in,
+            [
+                #[doc = ""]
+                #[doc = "The heap allocation is made with `allocator`, which is stored in the struct"]
+                #[doc = "and used again to free the allocation. On error `allocator` is dropped."]
+                allocator: $Allocator
+            ],
+            $Owner $(=> $OwnerLifetime)?,
+            $Dependent $(, $AsyncBuilder)?
+        );
+
+        $crate::_self_cell_try_new_or_recover_alloc!(
+            $Vis,
+            try_new_or_recover_alloc_in,
+            [
+                #[doc = ""]
+                #[doc = "The heap allocation is made with `allocator`, which is stored in the struct"]
+                #[doc = "and used again to free the allocation. On error `allocator` is dropped."]
+                allocator: $Allocator
+            ],
+            $Owner $(=> $OwnerLifetime)?,
+            $Dependent $(, $AsyncBuilder)?
+        );
+
+        $crate::_self_cell_try_new_borrowed_err!(
+            $Vis,
+            try_new_borrowed_err_in,
+            [
+                #[doc = ""]
+                #[doc = "The heap allocation is made with `allocator`, which is stored in the struct"]
+                #[doc = "and used again to free the allocation. On error it is stored in the"]
+                #[doc = "`ErrorCell` instead."]
+                allocator: $Allocator
+            ],
+            $Owner $(=> $OwnerLifetime)?,
+            $Dependent $(, $AsyncBuilder)?
+        );
+
+        $crate::_self_cell_new_catch_unwind!(
+            $Vis,
+            new_catch_unwind_in,
+            try_new_or_recover_in,
+            [
+                #[doc = ""]
+                #[doc = "The heap allocation is made with `allocator`, which is stored in the struct"]
+                #[doc = "and used again to free the allocation. On panic `allocator` is dropped."]
+                allocator: $Allocator
+            ],
+            $Owner,
+            $Dependent $(, $AsyncBuilder)?
+        );
     };
-    (not_covariant, $OwnerLifetime:lifetime) => {
-        // See the discussion in https://github.com/Voultapher/self_cell/pull/29
-        //
-        // If the dependent is non_covariant, mark the owner as invariant over its
-        // lifetime. Otherwise unsound use is possible.
-        ::core::marker::PhantomData<fn(&$OwnerLifetime ()) -> &$OwnerLifetime ()>
+}
+
+#[doc(hidden)]
+#[macro_export]
+macro_rules! _self_cell_new {
+    (
+        $Vis:vis,
+        $FnName:ident,
+        [$(#[$AllocatorDoc:meta])* $($allocator:ident: $Allocator:ty)?],
+        $Owner:ty $(=> $OwnerLifetime:lifetime)?,
+        $Dependent:tt
+    ) => {
+        /// Constructs a new self-referential struct.
+        ///
+        /// The provided `owner` will be moved into a heap allocated box. Followed by construction
+        /// of the dependent value, by calling `dependent_builder` with a shared reference to the
+        /// owner that remains valid for the lifetime of the constructed struct.
+        $(#[$AllocatorDoc])*
+        $Vis fn $FnName(
+            owner: $Owner,
+            $($allocator: $Allocator,)?
+            dependent_builder: impl for<'_q> ::core::ops::FnOnce(&'_q $Owner) -> $crate::_self_cell_dependent!($Dependent, '_q)
+        ) -> Self {
+            // JoinedCell is spelled out, a local type alias can't name the
+            // generic parameters of the struct.
+
+            // unsafe placed here to make sure the body macro can't be abused.
+            unsafe {
+                $crate::_self_cell_new_body!(
+                    $crate::unsafe_self_cell::JoinedCell<$Owner, $crate::_self_cell_dependent!($Dependent, '_)>,
+                    owner,
+                    $crate::_self_cell_allocator_value!($($allocator)?),
+                    $crate::alloc::alloc::handle_alloc_error($crate::alloc::alloc::Layout::new::<
+                        $crate::unsafe_self_cell::JoinedCell<$Owner, $crate::_self_cell_dependent!($Dependent, '_)>
+                    >())
+                    $(=> $OwnerLifetime)?,
+                    dependent_builder
+                )
+            }
+        }
     };
-    ($x:ident, $OwnerLifetime:lifetime) => {
-        compile_error!("This macro only accepts `covariant` or `not_covariant`");
+    (
+        $Vis:vis,
+        $FnName:ident,
+        [$(#[$AllocatorDoc:meta])* $($allocator:ident: $Allocator:ty)?],
+        $Owner:ty $(=> $OwnerLifetime:lifetime)?,
+        $Dependent:tt,
+        async_builder
+    ) => {
+        /// Constructs a new self-referential struct.
+        ///
+        /// The provided `owner` will be moved into a heap allocated box. Followed by construction
+        /// of the dependent value, by calling the async closure `dependent_builder` with a shared
+        /// reference to the owner that remains valid for the lifetime of the constructed struct.
+        $(#[$AllocatorDoc])*
+        $Vis async fn $FnName(
+            owner: $Owner,
+            $($allocator: $Allocator,)?
+            dependent_builder: impl for<'_q> ::core::ops::AsyncFnOnce(&'_q $Owner) -> $crate::_self_cell_dependent!($Dependent, '_q)
+        ) -> Self {
+            // unsafe placed here to make sure the body macro can't be abused.
+            unsafe {
+                $crate::_self_cell_new_body!(
+                    $crate::unsafe_self_cell::JoinedCell<$Owner, $crate::_self_cell_dependent!($Dependent, '_)>,
+                    owner,
+                    $crate::_self_cell_allocator_value!($($allocator)?),
+                    $crate::alloc::alloc::handle_alloc_error($crate::alloc::alloc::Layout::new::<
+                        $crate::unsafe_self_cell::JoinedCell<$Owner, $crate::_self_cell_dependent!($Dependent, '_)>
+                    >())
+                    $(=> $OwnerLifetime)?,
+                    dependent_builder,
+                    async_builder
+                )
+            }
+        }
+    };
+    ($Vis:vis, $FnName:ident, [$($Allocator:tt)*], $Owner:ty, $Dependent:tt, $x:ident) => {
+        compile_error!("This macro only accepts `async_builder`");
+    };
+}
+
+#[doc(hidden)]
+#[macro_export]
+macro_rules! _self_cell_new_body {
+    (
+        $JoinedCell:ty,
+        $owner:expr,
+        $allocator:expr,
+        $alloc_failure:expr
+        $(=> $OwnerLifetime:lifetime)?,
+        $dependent_builder:expr
+        $(, $AsyncBuilder:ident)?
+    ) => {{
+        // All this has to happen here, because there is not good way
+        // of passing the appropriate logic into UnsafeSelfCell::new
+        // short of assuming Dependent<'static> is the same as
+        // Dependent<'_q>, which I'm not confident is safe.
+
+        // For this API to be safe there has to be no safe way to
+        // capture additional references in `dependent_builder` and then
+        // return them as part of Dependent. Eg. it should be impossible
+        // to express: '_q should outlive 'x here `fn
+        // bad<'_q>(outside_ref: &'_q String) -> impl for<'x> ::core::ops::FnOnce(&'x
+        // Owner) -> Dependent<'x>`.
+
+        let layout = $crate::alloc::alloc::Layout::new::<$JoinedCell>();
+
+        let allocator = $allocator;
+
+        let joined_void_ptr =
+            match $crate::unsafe_self_cell::allocate_joined::<$JoinedCell, _>(&allocator, layout) {
+                ::core::option::Option::Some(joined_void_ptr) => joined_void_ptr,
+                // owner has not been moved yet, so the failure path may still use it.
+                ::core::option::Option::None => $alloc_failure,
+            };
+
+        let joined_ptr = joined_void_ptr.cast::<$JoinedCell>();
+
+        let (owner_ptr, dependent_ptr) = <$JoinedCell>::_field_pointers(joined_ptr.as_ptr());
+
+        // Move owner into newly allocated space.
+        owner_ptr.write($owner);
+
+        // Drop guard that cleans up should building the dependent panic.
+        let drop_guard =
+            $crate::unsafe_self_cell::OwnerAndCellDropGuard::new(joined_ptr, &allocator);
+
+        // Initialize dependent with owner reference in final place.
+        dependent_ptr.write($crate::_await_opt!($dependent_builder(&*owner_ptr) $(, $AsyncBuilder)?));
+        ::core::mem::forget(drop_guard);
+
+        Self {
+            // From is the identity for heap cells and wraps the cell for shared cells.
+            unsafe_self_cell: ::core::convert::From::from(
+                $crate::unsafe_self_cell::UnsafeSelfCell::new(joined_void_ptr, allocator)
+            ),
+            $(owner_marker: $crate::_covariant_owner_marker_ctor!($OwnerLifetime) ,)?
+        }
+    }}
+}
+
+#[doc(hidden)]
+#[macro_export]
+macro_rules! _self_cell_try_new {
+    (
+        $Vis:vis,
+        $FnName:ident,
+        [$(#[$AllocatorDoc:meta])* $($allocator:ident: $Allocator:ty)?],
+        $Owner:ty $(=> $OwnerLifetime:lifetime)?,
+        $Dependent:tt
+    ) => {
+        /// Constructs a new self-referential struct or returns an error.
+        ///
+        /// Consumes owner on error.
+        $(#[$AllocatorDoc])*
+        $Vis fn $FnName<Err>(
+            owner: $Owner,
+            $($allocator: $Allocator,)?
+            dependent_builder:
+                impl for<'_q> ::core::ops::FnOnce(&'_q $Owner) -> ::core::result::Result<$crate::_self_cell_dependent!($Dependent, '_q), Err>
+        ) -> ::core::result::Result<Self, Err> {
+            // unsafe placed here to make sure the body macro can't be abused.
+            unsafe {
+                $crate::_self_cell_try_new_body!(
+                    $crate::unsafe_self_cell::JoinedCell<$Owner, $crate::_self_cell_dependent!($Dependent, '_)>,
+                    owner,
+                    $crate::_self_cell_allocator_value!($($allocator)?),
+                    $crate::alloc::alloc::handle_alloc_error($crate::alloc::alloc::Layout::new::<
+                        $crate::unsafe_self_cell::JoinedCell<$Owner, $crate::_self_cell_dependent!($Dependent, '_)>
+                    >())
+                    $(=> $OwnerLifetime)?,
+                    dependent_builder
+                )
+            }
+        }
+    };
+    (
+        $Vis:vis,
+        $FnName:ident,
+        [$(#[$AllocatorDoc:meta])* $($allocator:ident: $Allocator:ty)?],
+        $Owner:ty $(=> $OwnerLifetime:lifetime)?,
+        $Dependent:tt,
+        async_builder
+    ) => {
+        /// Constructs a new self-referential struct or returns an error.
+        ///
+        /// Consumes owner on error.
+        $(#[$AllocatorDoc])*
+        $Vis async fn $FnName<Err>(
+            owner: $Owner,
+            $($allocator: $Allocator,)?
+            dependent_builder:
+                impl for<'_q> ::core::ops::AsyncFnOnce(&'_q $Owner) -> ::core::result::Result<$crate::_self_cell_dependent!($Dependent, '_q), Err>
+        ) -> ::core::result::Result<Self, Err> {
+            // unsafe placed here to make sure the body macro can't be abused.
+            unsafe {
+                $crate::_self_cell_try_new_body!(
+                    $crate::unsafe_self_cell::JoinedCell<$Owner, $crate::_self_cell_dependent!($Dependent, '_)>,
+                    owner,
+                    $crate::_self_cell_allocator_value!($($allocator)?),
+                    $crate::alloc::alloc::handle_alloc_error($crate::alloc::alloc::Layout::new::<
+                        $crate::unsafe_self_cell::JoinedCell<$Owner, $crate::_self_cell_dependent!($Dependent, '_)>
+                    >())
+                    $(=> $OwnerLifetime)?,
+                    dependent_builder,
+                    async_builder
+                )
+            }
+        }
+    };
+    ($Vis:vis, $FnName:ident, [$($Allocator:tt)*], $Owner:ty, $Dependent:tt, $x:ident) => {
+        compile_error!("This macro only accepts `async_builder`");
+    };
+}
+
+#[doc(hidden)]
+#[macro_export]
+macro_rules! _self_cell_try_new_body {
+    (
+        $JoinedCell:ty,
+        $owner:expr,
+        $allocator:expr,
+        $alloc_failure:expr
+        $(=> $OwnerLifetime:lifetime)?,
+        $dependent_builder:expr
+        $(, $AsyncBuilder:ident)?
+    ) => {{
+        // See fn new for more explanation.
+
+        let layout = $crate::alloc::alloc::Layout::new::<$JoinedCell>();
+
+        let allocator = $allocator;
+
+        let joined_void_ptr =
+            match $crate::unsafe_self_cell::allocate_joined::<$JoinedCell, _>(&allocator, layout) {
+                ::core::option::Option::Some(joined_void_ptr) => joined_void_ptr,
+                // owner has not been moved yet, so the failure path may still use it.
+                ::core::option::Option::None => $alloc_failure,
+            };
+
+        let joined_ptr = joined_void_ptr.cast::<$JoinedCell>();
+
+        let (owner_ptr, dependent_ptr) = <$JoinedCell>::_field_pointers(joined_ptr.as_ptr());
+
+        // Move owner into newly allocated space.
+        owner_ptr.write($owner);
+
+        // Drop guard that cleans up should building the dependent panic.
+        let drop_guard =
+            $crate::unsafe_self_cell::OwnerAndCellDropGuard::new(joined_ptr, &allocator);
+
+        match $crate::_await_opt!($dependent_builder(&*owner_ptr) $(, $AsyncBuilder)?) {
+            ::core::result::Result::Ok(dependent) => {
+                dependent_ptr.write(dependent);
+                ::core::mem::forget(drop_guard);
+
+                ::core::result::Result::Ok(Self {
+                    unsafe_self_cell: ::core::convert::From::from(
+                        $crate::unsafe_self_cell::UnsafeSelfCell::new(joined_void_ptr, allocator)
+                    ),
+                    $(owner_marker: $crate::_covariant_owner_marker_ctor!($OwnerLifetime) ,)?
+                })
+            }
+            ::core::result::Result::Err(err) => ::core::result::Result::Err(err)
+        }
+    }}
+}
+
+#[cfg(feature = "std")]
+#[doc(hidden)]
+#[macro_export]
+macro_rules! _self_cell_new_catch_unwind {
+    (
+        $Vis:vis,
+        $FnName:ident,
+        $TryNewOrRecover:ident,
+        [$(#[$AllocatorDoc:meta])* $($allocator:ident: $Allocator:ty)?],
+        $Owner:ty,
+        $Dependent:tt
+    ) => {
+        /// Constructs a new self-referential struct, catching a panic of
+        /// `dependent_builder`.
+        ///
+        /// Returns owner and the panic payload as tuple if `dependent_builder`
+        /// panics. Like `std::panic::catch_unwind` with `AssertUnwindSafe`, owner
+        /// may be left in an inconsistent state if it has interior mutability.
+        $(#[$AllocatorDoc])*
+        $Vis fn $FnName(
+            owner: $Owner,
+            $($allocator: $Allocator,)?
+            dependent_builder: impl for<'_q> ::core::ops::FnOnce(&'_q $Owner) -> $crate::_self_cell_dependent!($Dependent, '_q)
+        ) -> ::core::result::Result<
+            Self,
+            (
+                $Owner,
+                $crate::std::boxed::Box<dyn ::core::any::Any + ::core::marker::Send + 'static>,
+            ),
+        > {
+            Self::$TryNewOrRecover(owner, $($allocator,)? |owner: &$Owner| {
+                $crate::std::panic::catch_unwind($crate::std::panic::AssertUnwindSafe(|| {
+                    dependent_builder(owner)
+                }))
+            })
+        }
+    };
+    // catch_unwind can't catch panics of async builders.
+    (
+        $Vis:vis,
+        $FnName:ident,
+        $TryNewOrRecover:ident,
+        [$($Allocator:tt)*],
+        $Owner:ty,
+        $Dependent:tt,
+        async_builder
+    ) => {};
+    ($Vis:vis, $FnName:ident, $TryNewOrRecover:ident, [$($Allocator:tt)*], $Owner:ty, $Dependent:tt, $x:ident) => {
+        compile_error!("This macro only accepts `async_builder`");
+    };
+}
+
+#[cfg(not(feature = "std"))]
+#[doc(hidden)]
+#[macro_export]
+macro_rules! _self_cell_new_catch_unwind {
+    ($($Args:tt)*) => {};
+}
+
+#[doc(hidden)]
+#[macro_export]
+macro_rules! _self_cell_try_new_borrowed_err {
+    (
+        $Vis:vis,
+        $FnName:ident,
+        [$(#[$AllocatorDoc:meta])* $($allocator:ident: $Allocator:ty)?],
+        $Owner:ty $(=> $OwnerLifetime:lifetime)?,
+        $Dependent:tt
+    ) => {
+        /// Constructs a new self-referential struct or returns an error that
+        /// may borrow from owner.
+        ///
+        /// `E` names the error type for each lifetime of the borrowed owner, see
+        /// `self_cell::BorrowedError`. On error the returned `self_cell::ErrorCell`
+        /// keeps owner in the heap allocation that was made for the struct.
+        $(#[$AllocatorDoc])*
+        $Vis fn $FnName<E: for<'_q> $crate::BorrowedError<'_q>>(
+            owner: $Owner,
+            $($allocator: $Allocator,)?
+            dependent_builder:
+                impl for<'_q> ::core::ops::FnOnce(&'_q $Owner) -> ::core::result::Result<
+                    $crate::_self_cell_dependent!($Dependent, '_q),
+                    <E as $crate::BorrowedError<'_q>>::Error
+                >
+        ) -> ::core::result::Result<
+            Self,
+            $crate::ErrorCell<$Owner, E, $crate::_self_cell_allocator!($($Allocator)?)>
+        > {
+            // unsafe placed here to make sure the body macro can't be abused.
+            unsafe {
+                $crate::_self_cell_try_new_borrowed_err_body!(
+                    $crate::unsafe_self_cell::JoinedCell<$Owner, $crate::_self_cell_dependent!($Dependent, '_)>,
+                    owner,
+                    $crate::_self_cell_allocator_value!($($allocator)?),
+                    $crate::alloc::alloc::handle_alloc_error($crate::alloc::alloc::Layout::new::<
+                        $crate::unsafe_self_cell::JoinedCell<$Owner, $crate::_self_cell_dependent!($Dependent, '_)>
+                    >())
+                    $(=> $OwnerLifetime)?,
+                    dependent_builder
+                )
+            }
+        }
+    };
+    (
+        $Vis:vis,
+        $FnName:ident,
+        [$(#[$AllocatorDoc:meta])* $($allocator:ident: $Allocator:ty)?],
+        $Owner:ty $(=> $OwnerLifetime:lifetime)?,
+        $Dependent:tt,
+        async_builder
+    ) => {
+        /// Constructs a new self-referential struct or returns an error that
+        /// may borrow from owner.
+        ///
+        /// `E` names the error type for each lifetime of the borrowed owner, see
+        /// `self_cell::BorrowedError`. On error the returned `self_cell::ErrorCell`
+        /// keeps owner in the heap allocation that was made for the struct.
+        $(#[$AllocatorDoc])*
+        $Vis async fn $FnName<E: for<'_q> $crate::BorrowedError<'_q>>(
+            owner: $Owner,
+            $($allocator: $Allocator,)?
+            dependent_builder:
+                impl for<'_q> ::core::ops::AsyncFnOnce(&'_q $Owner) -> ::core::result::Result<
+                    $crate::_self_cell_dependent!($Dependent, '_q),
+                    <E as $crate::BorrowedError<'_q>>::Error
+                >
+        ) -> ::core::result::Result<
+            Self,
+            $crate::ErrorCell<$Owner, E, $crate::_self_cell_allocator!($($Allocator)?)>
+        > {
+            // unsafe placed here to make sure the body macro can't be abused.
+            unsafe {
+                $crate::_self_cell_try_new_borrowed_err_body!(
+                    $crate::unsafe_self_cell::JoinedCell<$Owner, $crate::_self_cell_dependent!($Dependent, '_)>,
+                    owner,
+                    $crate::_self_cell_allocator_value!($($allocator)?),
+                    $crate::alloc::alloc::handle_alloc_error($crate::alloc::alloc::Layout::new::<
+                        $crate::unsafe_self_cell::JoinedCell<$Owner, $crate::_self_cell_dependent!($Dependent, '_)>
+                    >())
+                    $(=> $OwnerLifetime)?,
+                    dependent_builder,
+                    async_builder
+                )
+            }
+        }
+    };
+    ($Vis:vis, $FnName:ident, [$($Allocator:tt)*], $Owner:ty, $Dependent:tt, $x:ident) => {
+        compile_error!("This macro only accepts `async_builder`");
+    };
+}
+
+#[doc(hidden)]
+#[macro_export]
+macro_rules! _self_cell_try_new_borrowed_err_body {
+    (
+        $JoinedCell:ty,
+        $owner:expr,
+        $allocator:expr,
+        $alloc_failure:expr
+        $(=> $OwnerLifetime:lifetime)?,
+        $dependent_builder:expr
+        $(, $AsyncBuilder:ident)?
+    ) => {{
+        // See fn new for more explanation.
+
+        let layout = $crate::alloc::alloc::Layout::new::<$JoinedCell>();
+
+        let allocator = $allocator;
+
+        let joined_void_ptr =
+            match $crate::unsafe_self_cell::allocate_joined::<$JoinedCell, _>(&allocator, layout) {
+                ::core::option::Option::Some(joined_void_ptr) => joined_void_ptr,
+                // owner has not been moved yet, so the failure path may still use it.
+                ::core::option::Option::None => $alloc_failure,
+            };
+
+        let joined_ptr = joined_void_ptr.cast::<$JoinedCell>();
+
+        let (owner_ptr, dependent_ptr) = <$JoinedCell>::_field_pointers(joined_ptr.as_ptr());
+
+        // Move owner into newly allocated space.
+        owner_ptr.write($owner);
+
+        // Drop guard that cleans up should building the dependent panic.
+        let drop_guard =
+            $crate::unsafe_self_cell::OwnerAndCellDropGuard::new(joined_ptr, &allocator);
+
+        match $crate::_await_opt!($dependent_builder(&*owner_ptr) $(, $AsyncBuilder)?) {
+            ::core::result::Result::Ok(dependent) => {
+                dependent_ptr.write(dependent);
+                ::core::mem::forget(drop_guard);
+
+                ::core::result::Result::Ok(Self {
+                    unsafe_self_cell: ::core::convert::From::from(
+                        $crate::unsafe_self_cell::UnsafeSelfCell::new(joined_void_ptr, allocator)
+                    ),
+                    $(owner_marker: $crate::_covariant_owner_marker_ctor!($OwnerLifetime) ,)?
+                })
+            }
+            ::core::result::Result::Err(err) => {
+                // The error may borrow from owner, so owner stays where it is,
+                // the ErrorCell takes over the allocation.
+                ::core::mem::forget(drop_guard);
+
+                ::core::result::Result::Err($crate::ErrorCell::new(
+                    joined_void_ptr,
+                    layout,
+                    allocator,
+                    owner_ptr,
+                    err,
+                ))
+            }
+        }
+    }}
+}
+
+#[doc(hidden)]
+#[macro_export]
+macro_rules! _self_cell_try_new_or_recover {
+    (
+        $Vis:vis,
+        $FnName:ident,
+        [$(#[$AllocatorDoc:meta])* $($allocator:ident: $Allocator:ty)?],
+        $Owner:ty $(=> $OwnerLifetime:lifetime)?,
+        $Dependent:tt
+    ) => {
+        /// Constructs a new self-referential struct or returns an error.
+        ///
+        /// Returns owner and error as tuple on error.
+        $(#[$AllocatorDoc])*
+        $Vis fn $FnName<Err>(
+            owner: $Owner,
+            $($allocator: $Allocator,)?
+            dependent_builder:
+                impl for<'_q> ::core::ops::FnOnce(&'_q $Owner) -> ::core::result::Result<$crate::_self_cell_dependent!($Dependent, '_q), Err>
+    ) -> ::core::result::Result<Self, ($Owner, Err)> {
+            // unsafe placed here to make sure the body macro can't be abused.
+            unsafe {
+                $crate::_self_cell_try_new_or_recover_body!(
+                    $crate::unsafe_self_cell::JoinedCell<$Owner, $crate::_self_cell_dependent!($Dependent, '_)>,
+                    owner,
+                    $crate::_self_cell_allocator_value!($($allocator)?),
+                    $crate::alloc::alloc::handle_alloc_error($crate::alloc::alloc::Layout::new::<
+                        $crate::unsafe_self_cell::JoinedCell<$Owner, $crate::_self_cell_dependent!($Dependent, '_)>
+                    >())
+                    $(=> $OwnerLifetime)?,
+                    dependent_builder
+                )
+            }
+        }
+    };
+    (
+        $Vis:vis,
+        $FnName:ident,
+        [$(#[$AllocatorDoc:meta])* $($allocator:ident: $Allocator:ty)?],
+        $Owner:ty $(=> $OwnerLifetime:lifetime)?,
+        $Dependent:tt,
+        async_builder
+    ) => {
+        /// Constructs a new self-referential struct or returns an error.
+        ///
+        /// Returns owner and error as tuple on error.
+        $(#[$AllocatorDoc])*
+        $Vis async fn $FnName<Err>(
+            owner: $Owner,
+            $($allocator: $Allocator,)?
+            dependent_builder:
+                impl for<'_q> ::core::ops::AsyncFnOnce(&'_q $Owner) -> ::core::result::Result<$crate::_self_cell_dependent!($Dependent, '_q), Err>
+        ) -> ::core::result::Result<Self, ($Owner, Err)> {
+            // unsafe placed here to make sure the body macro can't be abused.
+            unsafe {
+                $crate::_self_cell_try_new_or_recover_body!(
+                    $crate::unsafe_self_cell::JoinedCell<$Owner, $crate::_self_cell_dependent!($Dependent, '_)>,
+                    owner,
+                    $crate::_self_cell_allocator_value!($($allocator)?),
+                    $crate::alloc::alloc::handle_alloc_error($crate::alloc::alloc::Layout::new::<
+                        $crate::unsafe_self_cell::JoinedCell<$Owner, $crate::_self_cell_dependent!($Dependent, '_)>
+                    >())
+                    $(=> $OwnerLifetime)?,
+                    dependent_builder,
+                    async_builder
+                )
+            }
+        }
+    };
+    ($Vis:vis, $FnName:ident, [$($Allocator:tt)*], $Owner:ty, $Dependent:tt, $x:ident) => {
+        compile_error!("This macro only accepts `async_builder`");
     };
 }
 
 #[doc(hidden)]
 #[macro_export]
-macro_rules! _covariant_owner_marker_ctor {
-    ($OwnerLifetime:lifetime) => {
-        // Helper to optionally expand into PhantomData for construction.
-        ::core::marker::PhantomData
+macro_rules! _self_cell_try_new_or_recover_body {
+    (
+        $JoinedCell:ty,
+        $owner:expr,
+        $allocator:expr,
+        $alloc_failure:expr
+        $(=> $OwnerLifetime:lifetime)?,
+        $dependent_builder:expr
+        $(, $AsyncBuilder:ident)?
+    ) => {{
+        let layout = $crate::alloc::alloc::Layout::new::<$JoinedCell>();
+
+        let allocator = $allocator;
+
+        let joined_void_ptr =
+            match $crate::unsafe_self_cell::allocate_joined::<$JoinedCell, _>(&allocator, layout) {
+                ::core::option::Option::Some(joined_void_ptr) => joined_void_ptr,
+                // owner has not been moved yet, so the failure path may still use it.
+                ::core::option::Option::None => $alloc_failure,
+            };
+
+        let joined_ptr = joined_void_ptr.cast::<$JoinedCell>();
+
+        let (owner_ptr, dependent_ptr) = <$JoinedCell>::_field_pointers(joined_ptr.as_ptr());
+
+        // Move owner into newly allocated space.
+        owner_ptr.write($owner);
+
+        // Drop guard that cleans up should building the dependent panic.
+        let drop_guard =
+            $crate::unsafe_self_cell::OwnerAndCellDropGuard::new(joined_ptr, &allocator);
+
+        match $crate::_await_opt!($dependent_builder(&*owner_ptr) $(, $AsyncBuilder)?) {
+            ::core::result::Result::Ok(dependent) => {
+                dependent_ptr.write(dependent);
+                ::core::mem::forget(drop_guard);
+
+                ::core::result::Result::Ok(Self {
+                    unsafe_self_cell: ::core::convert::From::from(
+                        $crate::unsafe_self_cell::UnsafeSelfCell::new(joined_void_ptr, allocator)
+                    ),
+                    $(owner_marker: $crate::_covariant_owner_marker_ctor!($OwnerLifetime) ,)?
+                })
+            }
+            ::core::result::Result::Err(err) => {
+                // In contrast to into_owner ptr::read, here no dependent
+                // ever existed in this function and so we are sure its
+                // drop impl can't access owner after the read.
+                // And err can't return a reference to owner.
+                let owner_on_err = ::core::ptr::read(owner_ptr);
+
+                // Allowing drop_guard to finish would let it double free owner.
+                // So we dealloc the JoinedCell here manually.
+                ::core::mem::forget(drop_guard);
+                $crate::unsafe_self_cell::deallocate_joined(
+                    &allocator,
+                    joined_void_ptr,
+                    layout,
+                );
+
+                ::core::result::Result::Err((owner_on_err, err))
+            }
+        }
+    }}
+}
+
+#[doc(hidden)]
+#[macro_export]
+macro_rules! _self_cell_try_new_alloc {
+    (
+        $Vis:vis,
+        $FnName:ident,
+        [$(#[$AllocatorDoc:meta])* $($allocator:ident: $Allocator:ty)?],
+        $Owner:ty $(=> $OwnerLifetime:lifetime)?,
+        $Dependent:tt
+    ) => {
+        /// Constructs a new self-referential struct or returns the owner if the heap allocation
+        /// failed.
+        ///
+        /// In contrast to `new`, running out of memory doesn't call `handle_alloc_error`. Instead
+        /// the untouched owner is returned together with `self_cell::AllocError`. The
+        /// `dependent_builder` is only called once the allocation succeeded.
+        $(#[$AllocatorDoc])*
+        $Vis fn $FnName(
+            owner: $Owner,
+            $($allocator: $Allocator,)?
+            dependent_builder: impl for<'_q> ::core::ops::FnOnce(&'_q $Owner) -> $crate::_self_cell_dependent!($Dependent, '_q)
+        ) -> ::core::result::Result<Self, ($Owner, $crate::AllocError)> {
+            // unsafe placed here to make sure the body macro can't be abused.
+            let cell = unsafe {
+                $crate::_self_cell_new_body!(
+                    $crate::unsafe_self_cell::JoinedCell<$Owner, $crate::_self_cell_dependent!($Dependent, '_)>,
+                    owner,
+                    $crate::_self_cell_allocator_value!($($allocator)?),
+                    return ::core::result::Result::Err((owner, $crate::AllocError))
+                    $(=> $OwnerLifetime)?,
+                    dependent_builder
+                )
+            };
+
+            ::core::result::Result::Ok(cell)
+        }
+    };
+    (
+        $Vis:vis,
+        $FnName:ident,
+        [$(#[$AllocatorDoc:meta])* $($allocator:ident: $Allocator:ty)?],
+        $Owner:ty $(=> $OwnerLifetime:lifetime)?,
+        $Dependent:tt,
+        async_builder
+    ) => {
+        /// Constructs a new self-referential struct or returns the owner if the heap allocation
+        /// failed.
+        ///
+        /// In contrast to `new`, running out of memory doesn't call `handle_alloc_error`. Instead
+        /// the untouched owner is returned together with `self_cell::AllocError`. The async
+        /// closure `dependent_builder` is only called once the allocation succeeded.
+        $(#[$AllocatorDoc])*
+        $Vis async fn $FnName(
+            owner: $Owner,
+            $($allocator: $Allocator,)?
+            dependent_builder: impl for<'_q> ::core::ops::AsyncFnOnce(&'_q $Owner) -> $crate::_self_cell_dependent!($Dependent, '_q)
+        ) -> ::core::result::Result<Self, ($Owner, $crate::AllocError)> {
+            // unsafe placed here to make sure the body macro can't be abused.
+            let cell = unsafe {
+                $crate::_self_cell_new_body!(
+                    $crate::unsafe_self_cell::JoinedCell<$Owner, $crate::_self_cell_dependent!($Dependent, '_)>,
+                    owner,
+                    $crate::_self_cell_allocator_value!($($allocator)?),
+                    return ::core::result::Result::Err((owner, $crate::AllocError))
+                    $(=> $OwnerLifetime)?,
+                    dependent_builder,
+                    async_builder
+                )
+            };
+
+            ::core::result::Result::Ok(cell)
+        }
+    };
+    ($Vis:vis, $FnName:ident, [$($Allocator:tt)*], $Owner:ty, $Dependent:tt, $x:ident) => {
+        compile_error!("This macro only accepts `async_builder`");
     };
 }
 
 #[doc(hidden)]
 #[macro_export]
-macro_rules! _self_cell_new {
-    ($Vis:vis, $Owner:ty $(=> $OwnerLifetime:lifetime)?, $Dependent:ident) => {
-        /// Constructs a new self-referential struct.
+macro_rules! _self_cell_try_new_or_recover_alloc {
+    (
+        $Vis:vis,
+        $FnName:ident,
+        [$(#[$AllocatorDoc:meta])* $($allocator:ident: $Allocator:ty)?],
+        $Owner:ty $(=> $OwnerLifetime:lifetime)?,
+        $Dependent:tt
+    ) => {
+        /// Constructs a new self-referential struct or returns an error.
         ///
-        /// The provided `owner` will be moved into a heap allocated box. Followed by construction
-        /// of the dependent value, by calling `dependent_builder` with a shared reference to the
-        /// owner that remains valid for the lifetime of the constructed struct.
-        $Vis fn new(
+        /// Returns owner and error as tuple on error. The error is
+        /// `self_cell::TryNewError::Alloc` if the heap allocation failed, in which case
+        /// `dependent_builder` is never called, and `self_cell::TryNewError::Builder` if
+        /// `dependent_builder` returned an error.
+        $(#[$AllocatorDoc])*
+        $Vis fn $FnName<Err>(
             owner: $Owner,
-            dependent_builder: impl for<'_q> ::core::ops::FnOnce(&'_q $Owner) -> $Dependent<'_q>
-        ) -> Self {
-            type JoinedCell<'_q $(, $OwnerLifetime)?> =
-                    $crate::unsafe_self_cell::JoinedCell<$Owner, $Dependent<'_q>>;
-
+            $($allocator: $Allocator,)?
+            dependent_builder:
+                impl for<'_q> ::core::ops::FnOnce(&'_q $Owner) -> ::core::result::Result<$crate::_self_cell_dependent!($Dependent, '_q), Err>
+        ) -> ::core::result::Result<Self, ($Owner, $crate::TryNewError<Err>)> {
             // unsafe placed here to make sure the body macro can't be abused.
-            unsafe {
-                $crate::_self_cell_new_body!(JoinedCell, owner $(=> $OwnerLifetime)?, dependent_builder)
-            }
+            let result = unsafe {
+                $crate::_self_cell_try_new_or_recover_body!(
+                    $crate::unsafe_self_cell::JoinedCell<$Owner, $crate::_self_cell_dependent!($Dependent, '_)>,
+                    owner,
+                    $crate::_self_cell_allocator_value!($($allocator)?),
+                    return ::core::result::Result::Err((
+                        owner,
+                        $crate::TryNewError::Alloc($crate::AllocError),
+                    ))
+                    $(=> $OwnerLifetime)?,
+                    dependent_builder
+                )
+            };
+
+            result.map_err(|(owner, err)| (owner, $crate::TryNewError::Builder(err)))
         }
     };
-    ($Vis:vis, $Owner:ty $(=> $OwnerLifetime:lifetime)?, $Dependent:ident, async_builder) => {
-        /// Constructs a new self-referential struct.
+    (
+        $Vis:vis,
+        $FnName:ident,
+        [$(#[$AllocatorDoc:meta])* $($allocator:ident: $Allocator:ty)?],
+        $Owner:ty $(=> $OwnerLifetime:lifetime)?,
+        $Dependent:tt,
+        async_builder
+    ) => {
+        /// Constructs a new self-referential struct or returns an error.
         ///
-        /// The provided `owner` will be moved into a heap allocated box. Followed by construction
-        /// of the dependent value, by calling the async closure `dependent_builder` with a shared
-        /// reference to the owner that remains valid for the lifetime of the constructed struct.
-        $Vis async fn new(
+        /// Returns owner and error as tuple on error. The error is
+        /// `self_cell::TryNewError::Alloc` if the heap allocation failed, in which case
+        /// `dependent_builder` is never called, and `self_cell::TryNewError::Builder` if
+        /// `dependent_builder` returned an error.
+        $(#[$AllocatorDoc])*
+        $Vis async fn $FnName<Err>(
             owner: $Owner,
-            dependent_builder: impl for<'_q> ::core::ops::AsyncFnOnce(&'_q $Owner) -> $Dependent<'_q>
-        ) -> Self {
-            type JoinedCell<'_q $(, $OwnerLifetime)?> =
-                    $crate::unsafe_self_cell::JoinedCell<$Owner, $Dependent<'_q>>;
-
+            $($allocator: $Allocator,)?
+            dependent_builder:
+                impl for<'_q> ::core::ops::AsyncFnOnce(&'_q $Owner) -> ::core::result::Result<$crate::_self_cell_dependent!($Dependent, '_q), Err>
+        ) -> ::core::result::Result<Self, ($Owner, $crate::TryNewError<Err>)> {
             // unsafe placed here to make sure the body macro can't be abused.
-            unsafe {
-                $crate::_self_cell_new_body!(JoinedCell, owner $(=> $OwnerLifetime)?, dependent_builder, async_builder)
-            }
+            let result = unsafe {
+                $crate::_self_cell_try_new_or_recover_body!(
+                    $crate::unsafe_self_cell::JoinedCell<$Owner, $crate::_self_cell_dependent!($Dependent, '_)>,
+                    owner,
+                    $crate::_self_cell_allocator_value!($($allocator)?),
+                    return ::core::result::Result::Err((
+                        owner,
+                        $crate::TryNewError::Alloc($crate::AllocError),
+                    ))
+                    $(=> $OwnerLifetime)?,
+                    dependent_builder,
+                    async_builder
+                )
+            };
+
+            result.map_err(|(owner, err)| (owner, $crate::TryNewError::Builder(err)))
         }
     };
-    ($Vis:vis, $Owner:ty, $Dependent:ident, $x:ident) => {
+    ($Vis:vis, $FnName:ident, [$($Allocator:tt)*], $Owner:ty, $Dependent:tt, $x:ident) => {
         compile_error!("This macro only accepts `async_builder`");
     };
 }
 
 #[doc(hidden)]
 #[macro_export]
-macro_rules! _self_cell_new_body {
-    ($JoinedCell:ty, $owner:expr $(=> $OwnerLifetime:lifetime)?, $dependent_builder:expr $(, $AsyncBuilder:ident)?) => {{
-        // All this has to happen here, because there is not good way
-        // of passing the appropriate logic into UnsafeSelfCell::new
-        // short of assuming Dependent<'static> is the same as
-        // Dependent<'_q>, which I'm not confident is safe.
+macro_rules! _self_cell_stable_deref_body {
+    (
+        $owner:expr
+        $(=> $OwnerLifetime:lifetime)?,
+        $dependent_builder:expr
+    ) => {{
+        // See _self_cell_new_body, the same reasoning applies here. Only that
+        // dependent borrows from the Deref target of owner, which StableDeref
+        // guarantees to stay in place while owner is moved into the struct.
+        let owner = $owner;
 
-        // For this API to be safe there has to be no safe way to
-        // capture additional references in `dependent_builder` and then
-        // return them as part of Dependent. Eg. it should be impossible
-        // to express: '_q should outlive 'x here `fn
-        // bad<'_q>(outside_ref: &'_q String) -> impl for<'x> ::core::ops::FnOnce(&'x
-        // Owner) -> Dependent<'x>`.
+        let target_ptr: *const _ = ::core::ops::Deref::deref(&owner);
 
-        let layout = $crate::alloc::alloc::Layout::new::<$JoinedCell>();
-        assert!(layout.size() != 0);
+        match $dependent_builder(&*target_ptr) {
+            ::core::result::Result::Ok(dependent) => ::core::result::Result::Ok(Self {
+                unsafe_self_cell: $crate::unsafe_self_cell::StableDerefSelfCell::new(
+                    owner,
+                    dependent,
+                ),
+                $(owner_marker: $crate::_covariant_owner_marker_ctor!($OwnerLifetime) ,)?
+            }),
+            // err can't return a reference to the target of owner.
+            ::core::result::Result::Err(err) => ::core::result::Result::Err((owner, err)),
+        }
+    }}
+}
 
-        let joined_void_ptr = ::core::ptr::NonNull::new($crate::alloc::alloc::alloc(layout)).unwrap();
+#[doc(hidden)]
+#[macro_export]
+macro_rules! _self_cell_pinned_init_body {
+    (
+        $JoinedCell:ty,
+        $unsafe_self_cell:expr,
+        $owner:expr,
+        $dependent_builder:expr
+    ) => {{
+        // See _self_cell_new_body, the same reasoning applies here, only that
+        // the JoinedCell lives inline in the pinned struct.
 
-        let mut joined_ptr = joined_void_ptr.cast::<$JoinedCell>();
+        let joined_ptr = $unsafe_self_cell.uninit_joined_void_ptr() as *mut $JoinedCell;
 
-        let (owner_ptr, dependent_ptr) = <$JoinedCell>::_field_pointers(joined_ptr.as_ptr());
+        let (owner_ptr, dependent_ptr) = <$JoinedCell>::_field_pointers(joined_ptr);
 
-        // Move owner into newly allocated space.
+        // Move owner into its final place.
         owner_ptr.write($owner);
 
         // Drop guard that cleans up should building the dependent panic.
-        let drop_guard =
-            $crate::unsafe_self_cell::OwnerAndCellDropGuard::new(joined_ptr);
+        let drop_guard = $crate::unsafe_self_cell::DropInPlaceGuard::new(owner_ptr);
 
-        // Initialize dependent with owner reference in final place.
-        dependent_ptr.write($crate::_await_opt!($dependent_builder(&*owner_ptr) $(, $AsyncBuilder)?));
-        ::core::mem::forget(drop_guard);
+        match $dependent_builder(&*owner_ptr) {
+            ::core::result::Result::Ok(dependent) => {
+                dependent_ptr.write(dependent);
+                ::core::mem::forget(drop_guard);
 
-        Self {
-            unsafe_self_cell: $crate::unsafe_self_cell::UnsafeSelfCell::new(
-                joined_void_ptr,
-            ),
-            $(owner_marker: $crate::_covariant_owner_marker_ctor!($OwnerLifetime) ,)?
+                $unsafe_self_cell.set_initialized();
+
+                ::core::result::Result::Ok(())
+            }
+            ::core::result::Result::Err(err) => {
+                // No dependent ever existed, so owner can be moved out again.
+                let owner_on_err = ::core::ptr::read(owner_ptr);
+                ::core::mem::forget(drop_guard);
+
+                ::core::result::Result::Err((owner_on_err, err))
+            }
         }
-    }}
+    }};
 }
 
+// Nests the dependents of a cell with multiple dependents into a
+// DependentChain, the first dependent is the outermost one.
 #[doc(hidden)]
 #[macro_export]
-macro_rules! _self_cell_try_new {
-    ($Vis:vis, $Owner:ty $(=> $OwnerLifetime:lifetime)?, $Dependent:ident) => {
+macro_rules! _self_cell_dependents_type {
+    ($Lifetime:lifetime; $Dependent:tt) => {
+        $crate::unsafe_self_cell::DependentChain<$crate::_self_cell_dependent!($Dependent, $Lifetime), ()>
+    };
+    ($Lifetime:lifetime; $Dependent:tt, $($Rest:tt),+) => {
+        $crate::unsafe_self_cell::DependentChain<
+            $crate::_self_cell_dependent!($Dependent, $Lifetime),
+            $crate::_self_cell_dependents_type!($Lifetime; $($Rest),+)
+        >
+    };
+}
+
+// Walks the list of dependents once, collecting for each of them the builder
+// parameter types and arguments of the earlier dependents and the field path
+// into the DependentChain. The last dependent is tracked separately, because
+// it's the only one that may be mutated.
+#[doc(hidden)]
+#[macro_export]
+macro_rules! _self_cell_dependents {
+    (
+        @munch $Ctx:tt
+        [$($steps:tt)*] [$($prev_ty:tt)*] [$($prev_arg:tt)*] [$($path:tt)*]
+        #[$Covariance:ident] $field:ident: $Dependent:tt,
+    ) => {
+        $crate::_self_cell_dependents!(
+            @emit $Ctx
+            [
+                $($steps)*
+                ($Covariance $field $Dependent [$($prev_ty)*] [$($prev_arg)*] [$($path)* .first])
+            ]
+            [$($steps)*]
+            ($field $Dependent [$($path)* .first])
+        );
+    };
+    (
+        @munch $Ctx:tt
+        [$($steps:tt)*] [$($prev_ty:tt)*] [$($prev_arg:tt)*] [$($path:tt)*]
+        #[$Covariance:ident] $field:ident: $Dependent:tt,
+        $($rest:tt)+
+    ) => {
+        $crate::_self_cell_dependents!(
+            @munch $Ctx
+            [
+                $($steps)*
+                ($Covariance $field $Dependent [$($prev_ty)*] [$($prev_arg)*] [$($path)* .first])
+            ]
+            [$($prev_ty)* , &'_q $crate::_self_cell_dependent!($Dependent, '_q)]
+            [$($prev_arg)* , &*$field]
+            [$($path)* .rest]
+            $($rest)+
+        );
+    };
+    (
+        @emit ($Vis:vis, $Owner:ty, [$($OwnerLifetime:lifetime $(, $MoreLifetime:lifetime)*)?], [$($AllDependent:tt),+])
+        [$(
+            ($Covariance:ident $field:ident $Dependent:tt
+                [$($prev_ty:tt)*] [$($prev_arg:tt)*] [$($path:tt)*])
+        )+]
+        [$(
+            ($EarlierCovariance:ident $earlier_field:ident $EarlierDependent:tt
+                $earlier_prev_ty:tt $earlier_prev_arg:tt [$($earlier_path:tt)*])
+        )*]
+        ($last_field:ident $LastDependent:tt [$($last_path:tt)*])
+    ) => {
+        /// Constructs a new self-referential struct.
+        ///
+        /// The provided `owner` will be moved into a heap allocated box. Followed by construction
+        /// of the dependents in order, by calling their builder with a shared reference to the
+        /// owner and to all earlier dependents, which remain valid for the lifetime of the
+        /// constructed struct.
+        $Vis fn new(
+            owner: $Owner,
+            $($field: impl for<'_q> ::core::ops::FnOnce(&'_q $Owner $($prev_ty)*) -> $crate::_self_cell_dependent!($Dependent, '_q),)+
+        ) -> Self {
+            type JoinedCell<'_q $(, $OwnerLifetime $(, $MoreLifetime)*)?> = $crate::unsafe_self_cell::JoinedCell<
+                $Owner,
+                $crate::_self_cell_dependents_type!('_q; $($AllDependent),+)
+            >;
+
+            // unsafe placed here to make sure the body macro can't be abused.
+            unsafe {
+                $crate::_self_cell_dependents_body!(
+                    new,
+                    JoinedCell,
+                    owner
+                    $(=> $OwnerLifetime)?,
+                    [$(($field [$($prev_arg)*]))+]
+                )
+            }
+        }
+
         /// Constructs a new self-referential struct or returns an error.
         ///
-        /// Consumes owner on error.
+        /// Consumes owner and the dependents built so far on error.
         $Vis fn try_new<Err>(
             owner: $Owner,
-            dependent_builder:
-                impl for<'_q> ::core::ops::FnOnce(&'_q $Owner) -> ::core::result::Result<$Dependent<'_q>, Err>
+            $($field: impl for<'_q> ::core::ops::FnOnce(&'_q $Owner $($prev_ty)*)
+                -> ::core::result::Result<$crate::_self_cell_dependent!($Dependent, '_q), Err>,)+
         ) -> ::core::result::Result<Self, Err> {
-            type JoinedCell<'_q $(, $OwnerLifetime)?> =
-                    $crate::unsafe_self_cell::JoinedCell<$Owner, $Dependent<'_q>>;
+            type JoinedCell<'_q $(, $OwnerLifetime $(, $MoreLifetime)*)?> = $crate::unsafe_self_cell::JoinedCell<
+                $Owner,
+                $crate::_self_cell_dependents_type!('_q; $($AllDependent),+)
+            >;
 
             // unsafe placed here to make sure the body macro can't be abused.
             unsafe {
-                $crate::_self_cell_try_new_body!(JoinedCell, owner $(=> $OwnerLifetime)?, dependent_builder)
+                ::core::result::Result::Ok($crate::_self_cell_dependents_body!(
+                    try_new,
+                    JoinedCell,
+                    owner
+                    $(=> $OwnerLifetime)?,
+                    [$(($field [$($prev_arg)*]))+]
+                ))
             }
         }
-    };
-    ($Vis:vis, $Owner:ty $(=> $OwnerLifetime:lifetime)?, $Dependent:ident, async_builder) => {
+
         /// Constructs a new self-referential struct or returns an error.
         ///
-        /// Consumes owner on error.
-        $Vis async fn try_new<Err>(
+        /// Returns owner and error as tuple on error, the dependents built so far are dropped.
+        $Vis fn try_new_or_recover<Err>(
             owner: $Owner,
-            dependent_builder:
-                impl for<'_q> ::core::ops::AsyncFnOnce(&'_q $Owner) -> ::core::result::Result<$Dependent<'_q>, Err>
-        ) -> ::core::result::Result<Self, Err> {
-            type JoinedCell<'_q $(, $OwnerLifetime)?> =
-                    $crate::unsafe_self_cell::JoinedCell<$Owner, $Dependent<'_q>>;
+            $($field: impl for<'_q> ::core::ops::FnOnce(&'_q $Owner $($prev_ty)*)
+                -> ::core::result::Result<$crate::_self_cell_dependent!($Dependent, '_q), Err>,)+
+        ) -> ::core::result::Result<Self, ($Owner, Err)> {
+            type JoinedCell<'_q $(, $OwnerLifetime $(, $MoreLifetime)*)?> = $crate::unsafe_self_cell::JoinedCell<
+                $Owner,
+                $crate::_self_cell_dependents_type!('_q; $($AllDependent),+)
+            >;
 
             // unsafe placed here to make sure the body macro can't be abused.
             unsafe {
-                $crate::_self_cell_try_new_body!(JoinedCell, owner $(=> $OwnerLifetime)?, dependent_builder, async_builder)
+                ::core::result::Result::Ok($crate::_self_cell_dependents_body!(
+                    try_new_or_recover,
+                    JoinedCell,
+                    owner
+                    $(=> $OwnerLifetime)?,
+                    [$(($field [$($prev_arg)*]))+]
+                ))
+            }
+        }
+
+        /// Calls given closure `func` with shared references to owner and all dependents.
+        $Vis fn with_dependents<'outer_fn, Ret>(
+            &'outer_fn self,
+            func: impl for<'_q> ::core::ops::FnOnce(
+                &'_q $Owner
+                $(, &'_q $crate::_self_cell_dependent!($EarlierDependent, '_q))*,
+                &'outer_fn $crate::_self_cell_dependent!($LastDependent, '_q)
+            ) -> Ret
+        ) -> Ret {
+            unsafe {
+                let (owner_ptr, dependents_ptr) = self.unsafe_self_cell.field_pointers();
+
+                func(
+                    &*owner_ptr
+                    $(, &*(&(*dependents_ptr)$($earlier_path)* as *const $crate::_self_cell_dependent!($EarlierDependent, 'static)
+                        as *const $crate::_self_cell_dependent!($EarlierDependent, '_)))*,
+                    &*(&(*dependents_ptr)$($last_path)* as *const $crate::_self_cell_dependent!($LastDependent, 'static)
+                        as *const $crate::_self_cell_dependent!($LastDependent, '_))
+                )
+            }
+        }
+
+        /// Calls given closure `func` with a unique reference to the last dependent.
+        ///
+        /// Earlier dependents may be borrowed by later ones, so they are only passed as
+        /// shared references. Like owner they are never changed again, which allows the last
+        /// dependent to borrow them.
+        $Vis fn with_dependents_mut<'outer_fn, Ret>(
+            &'outer_fn mut self,
+            func: impl for<'_q> ::core::ops::FnOnce(
+                &'_q $Owner
+                $(, &'_q $crate::_self_cell_dependent!($EarlierDependent, '_q))*,
+                &'outer_fn mut $crate::_self_cell_dependent!($LastDependent, '_q)
+            ) -> Ret
+        ) -> Ret {
+            unsafe {
+                let (owner_ptr, dependents_ptr) = self.unsafe_self_cell.field_pointers();
+
+                func(
+                    &*owner_ptr
+                    $(, &*(&(*dependents_ptr)$($earlier_path)* as *const $crate::_self_cell_dependent!($EarlierDependent, 'static)
+                        as *const $crate::_self_cell_dependent!($EarlierDependent, '_)))*,
+                    &mut *(&mut (*dependents_ptr)$($last_path)* as *mut $crate::_self_cell_dependent!($LastDependent, 'static)
+                        as *mut $crate::_self_cell_dependent!($LastDependent, '_))
+                )
             }
         }
+
+        $(
+            $crate::_covariant_access_dependents!(
+                $Covariance, $Vis, $field, $Dependent, [$($path)*]
+            );
+        )+
+
+        $(
+            $crate::_self_cell_earlier_dependent!($EarlierCovariance, $earlier_field);
+        )*
     };
-    ($Vis:vis, $Owner:ty, $Dependent:ident, $x:ident) => {
-        compile_error!("This macro only accepts `async_builder`");
+}
+
+// With interior mutability an earlier dependent could store a reference to a
+// later dependent, which is dropped first. Covariant types can't do that,
+// because otherwise it would be possible to store a reference with a
+// shortened lifetime.
+#[doc(hidden)]
+#[macro_export]
+macro_rules! _self_cell_earlier_dependent {
+    (covariant, $field:ident) => {};
+    ($x:ident, $field:ident) => {
+        compile_error!(concat!(
+            "All dependents except the last have to be `covariant`, found: ",
+            stringify!($field)
+        ));
     };
 }
 
 #[doc(hidden)]
 #[macro_export]
-macro_rules! _self_cell_try_new_body {
-    ($JoinedCell:ty, $owner:expr $(=> $OwnerLifetime:lifetime)?, $dependent_builder:expr $(, $AsyncBuilder:ident)?) => {{
-        // See fn new for more explanation.
+macro_rules! _self_cell_dependents_body {
+    (
+        $mode:ident,
+        $JoinedCell:ty,
+        $owner:expr
+        $(=> $OwnerLifetime:lifetime)?,
+        [$(($field:ident [$($prev_arg:tt)*]))+]
+    ) => {{
+        // See _self_cell_new_body, the same reasoning applies to each
+        // dependent builder.
 
         let layout = $crate::alloc::alloc::Layout::new::<$JoinedCell>();
-        assert!(layout.size() != 0);
 
-        let joined_void_ptr = ::core::ptr::NonNull::new($crate::alloc::alloc::alloc(layout)).unwrap();
+        let allocator = $crate::unsafe_self_cell::DefaultAllocator;
+
+        let joined_void_ptr =
+            match $crate::unsafe_self_cell::allocate_joined::<$JoinedCell, _>(&allocator, layout) {
+                ::core::option::Option::Some(joined_void_ptr) => joined_void_ptr,
+                ::core::option::Option::None => $crate::alloc::alloc::handle_alloc_error(layout),
+            };
 
-        let mut joined_ptr = joined_void_ptr.cast::<$JoinedCell>();
+        let joined_ptr = joined_void_ptr.cast::<$JoinedCell>();
 
-        let (owner_ptr, dependent_ptr) = <$JoinedCell>::_field_pointers(joined_ptr.as_ptr());
+        let (owner_ptr, dependents_ptr) = <$JoinedCell>::_field_pointers(joined_ptr.as_ptr());
 
         // Move owner into newly allocated space.
         owner_ptr.write($owner);
 
-        // Drop guard that cleans up should building the dependent panic.
-        let mut drop_guard =
-            $crate::unsafe_self_cell::OwnerAndCellDropGuard::new(joined_ptr);
+        // Drop guard that cleans up should building a dependent fail.
+        let owner_guard =
+            $crate::unsafe_self_cell::OwnerAndCellDropGuard::new(joined_ptr, &allocator);
 
-        match $crate::_await_opt!($dependent_builder(&*owner_ptr) $(, $AsyncBuilder)?) {
+        // Drop guards for the dependents built so far, the most recent one is
+        // the outermost so that they are dropped in reverse order.
+        let dependent_guards = ();
+
+        let _rest_ptr = dependents_ptr;
+
+        $(
+            let (dependent_ptr, _rest_ptr) =
+                $crate::unsafe_self_cell::DependentChain::_field_pointers(_rest_ptr);
+
+            // Initialize dependent with owner and earlier dependent references
+            // in final place.
+            let dependent = match $crate::_self_cell_build_result!(
+                $mode,
+                $field(&*owner_ptr $($prev_arg)*)
+            ) {
+                ::core::result::Result::Ok(dependent) => dependent,
+                ::core::result::Result::Err(err) => $crate::_self_cell_dependents_err!(
+                    $mode,
+                    err,
+                    dependent_guards,
+                    owner_guard,
+                    owner_ptr,
+                    allocator,
+                    joined_void_ptr,
+                    layout
+                ),
+            };
+            dependent_ptr.write(dependent);
+
+            let $field = dependent_ptr;
+            let dependent_guards = (
+                $crate::unsafe_self_cell::DropInPlaceGuard::new($field),
+                dependent_guards,
+            );
+        )+
+
+        ::core::mem::forget(dependent_guards);
+        ::core::mem::forget(owner_guard);
+
+        Self {
+            unsafe_self_cell: $crate::unsafe_self_cell::UnsafeSelfCell::new(
+                joined_void_ptr,
+                allocator
+            ),
+            $(owner_marker: $crate::_covariant_owner_marker_ctor!($OwnerLifetime) ,)?
+        }
+    }};
+}
+
+#[doc(hidden)]
+#[macro_export]
+macro_rules! _self_cell_build_result {
+    (new, $build:expr) => {
+        ::core::result::Result::Ok::<_, ::core::convert::Infallible>($build)
+    };
+    ($mode:ident, $build:expr) => {
+        $build
+    };
+}
+
+#[doc(hidden)]
+#[macro_export]
+macro_rules! _self_cell_dependents_err {
+    (
+        new,
+        $err:ident,
+        $dependent_guards:ident,
+        $owner_guard:ident,
+        $owner_ptr:ident,
+        $allocator:ident,
+        $joined_void_ptr:ident,
+        $layout:ident
+    ) => {
+        match $err {}
+    };
+    (
+        try_new,
+        $err:ident,
+        $dependent_guards:ident,
+        $owner_guard:ident,
+        $owner_ptr:ident,
+        $allocator:ident,
+        $joined_void_ptr:ident,
+        $layout:ident
+    ) => {{
+        // Drops the dependents built so far and then owner, before
+        // deallocating.
+        return ::core::result::Result::Err($err);
+    }};
+    (
+        try_new_or_recover,
+        $err:ident,
+        $dependent_guards:ident,
+        $owner_guard:ident,
+        $owner_ptr:ident,
+        $allocator:ident,
+        $joined_void_ptr:ident,
+        $layout:ident
+    ) => {{
+        // owner_guard still cleans up should dropping a dependent panic.
+        ::core::mem::drop($dependent_guards);
+        ::core::mem::forget($owner_guard);
+
+        // Move owner out so it can be returned.
+        let owner_on_err = ::core::ptr::read($owner_ptr);
+
+        // Deallocate without dropping owner.
+        $crate::unsafe_self_cell::deallocate_joined(&$allocator, $joined_void_ptr, $layout);
+
+        return ::core::result::Result::Err((owner_on_err, $err));
+    }};
+}
+
+#[doc(hidden)]
+#[macro_export]
+macro_rules! _self_cell_replace_owner {
+    ($Vis:vis, $Methods:tt, $Owner:ty $(=> $OwnerLifetime:lifetime)?, $Dependent:tt) => {
+        $crate::_self_cell_method!(replace_owner, $Vis, $Methods, {
+            /// Replaces owner with `owner` and rebuilds the dependent in place, reusing the
+            /// existing allocation. Returns the previous owner.
+            ///
+            /// # Panics
+            ///
+            /// Aborts the process should dropping the dependent or `dependent_builder` panic, as
+            /// `self` can't be left without a dependent. Use `try_replace_owner` if that has to
+            /// be avoided, which consumes `self` and drops it on panic.
+            fn replace_owner(
+                &mut self,
+                owner: $Owner,
+                dependent_builder: impl for<'_q> ::core::ops::FnOnce(&'_q $Owner) -> $crate::_self_cell_dependent!($Dependent, '_q)
+            ) -> $Owner {
+                // unsafe placed here to make sure the rebuild can't be abused.
+                unsafe {
+                    self.unsafe_self_cell.rebuild_dependent::<$crate::_self_cell_dependent!($Dependent, '_), _>(
+                        |owner_ptr, dependent_ptr| {
+                            // The dependent is gone, so nothing borrows the old owner anymore.
+                            let old_owner = ::core::ptr::read(owner_ptr);
+                            owner_ptr.write(owner);
+                            dependent_ptr.write(dependent_builder(&*owner_ptr));
+
+                            old_owner
+                        }
+                    )
+                }
+            }
+        });
+
+        $crate::_self_cell_method!(try_replace_owner, $Vis, $Methods, {
+            /// Replaces owner with `owner` and rebuilds the dependent or returns an error.
+            ///
+            /// Returns the struct together with the previous owner. Consumes `self` so that on
+            /// error, or should `dependent_builder` panic, both owners can be dropped and the
+            /// allocation freed, instead of leaving `self` without a dependent.
+            fn try_replace_owner<Err>(
+                self,
+                owner: $Owner,
+                dependent_builder:
+                    impl for<'_q> ::core::ops::FnOnce(&'_q $Owner) -> ::core::result::Result<$crate::_self_cell_dependent!($Dependent, '_q), Err>
+            ) -> ::core::result::Result<(Self, $Owner), Err> {
+                // unsafe placed here to make sure the body macro can't be abused.
+                unsafe {
+                    $crate::_self_cell_replace_owner_body!(
+                        try_new,
+                        $crate::unsafe_self_cell::JoinedCell<$Owner, $crate::_self_cell_dependent!($Dependent, '_)>,
+                        $Dependent,
+                        self,
+                        owner
+                        $(=> $OwnerLifetime)?,
+                        dependent_builder
+                    )
+                }
+            }
+        });
+
+        $crate::_self_cell_method!(try_replace_owner_or_recover, $Vis, $Methods, {
+            /// Replaces owner with `owner` and rebuilds the dependent or returns an error.
+            /// Consumes `self` like `try_replace_owner`.
+            ///
+            /// Returns `(previous_owner, owner, error)` on error, where
+            /// `previous_owner` is the owner `self` was built with and `owner`
+            /// the argument.
+            fn try_replace_owner_or_recover<Err>(
+                self,
+                owner: $Owner,
+                dependent_builder:
+                    impl for<'_q> ::core::ops::FnOnce(&'_q $Owner) -> ::core::result::Result<$crate::_self_cell_dependent!($Dependent, '_q), Err>
+            ) -> ::core::result::Result<(Self, $Owner), ($Owner, $Owner, Err)> {
+                // unsafe placed here to make sure the body macro can't be abused.
+                unsafe {
+                    $crate::_self_cell_replace_owner_body!(
+                        try_new_or_recover,
+                        $crate::unsafe_self_cell::JoinedCell<$Owner, $crate::_self_cell_dependent!($Dependent, '_)>,
+                        $Dependent,
+                        self,
+                        owner
+                        $(=> $OwnerLifetime)?,
+                        dependent_builder
+                    )
+                }
+            }
+        });
+    };
+}
+
+#[doc(hidden)]
+#[macro_export]
+macro_rules! _self_cell_replace_owner_body {
+    (
+        $mode:ident,
+        $JoinedCell:ty,
+        $Dependent:tt,
+        $this:expr,
+        $owner:expr
+        $(=> $OwnerLifetime:lifetime)?,
+        $dependent_builder:expr
+    ) => {{
+        // The struct implements Drop, so the cell can't be moved out directly.
+        let this = ::core::mem::ManuallyDrop::new($this);
+        let unsafe_self_cell = ::core::ptr::read(&this.unsafe_self_cell);
+
+        let (joined_void_ptr, allocator) =
+            unsafe_self_cell.into_parts_without_dependent::<$crate::_self_cell_dependent!($Dependent, '_)>();
+
+        let joined_ptr = joined_void_ptr.cast::<$JoinedCell>();
+
+        let (owner_ptr, dependent_ptr) = <$JoinedCell>::_field_pointers(joined_ptr.as_ptr());
+
+        // The dependent is gone, so nothing borrows the old owner anymore.
+        let old_owner = ::core::ptr::read(owner_ptr);
+        owner_ptr.write($owner);
+
+        // Drop guard that cleans up should building the dependent fail.
+        let drop_guard =
+            $crate::unsafe_self_cell::OwnerAndCellDropGuard::new(joined_ptr, &allocator);
+
+        match $crate::_self_cell_build_result!($mode, $dependent_builder(&*owner_ptr)) {
             ::core::result::Result::Ok(dependent) => {
                 dependent_ptr.write(dependent);
                 ::core::mem::forget(drop_guard);
 
-                ::core::result::Result::Ok(Self {
-                    unsafe_self_cell: $crate::unsafe_self_cell::UnsafeSelfCell::new(
-                        joined_void_ptr,
-                    ),
-                    $(owner_marker: $crate::_covariant_owner_marker_ctor!($OwnerLifetime) ,)?
-                })
+                ::core::result::Result::Ok((
+                    Self {
+                        unsafe_self_cell: $crate::unsafe_self_cell::UnsafeSelfCell::new(
+                            joined_void_ptr,
+                            allocator
+                        ),
+                        $(owner_marker: $crate::_covariant_owner_marker_ctor!($OwnerLifetime) ,)?
+                    },
+                    old_owner,
+                ))
             }
-            ::core::result::Result::Err(err) => ::core::result::Result::Err(err)
+            ::core::result::Result::Err(err) => $crate::_self_cell_rebuild_err!(
+                $mode,
+                $JoinedCell,
+                err,
+                [old_owner],
+                drop_guard,
+                owner_ptr,
+                allocator,
+                joined_void_ptr
+            ),
         }
-    }}
+    }};
+}
+
+#[doc(hidden)]
+#[macro_export]
+macro_rules! _self_cell_rebuild_err {
+    (
+        try_new_or_recover,
+        $JoinedCell:ty,
+        $err:ident,
+        [$($previous:ident),*],
+        $drop_guard:ident,
+        $owner_ptr:ident,
+        $allocator:ident,
+        $joined_void_ptr:ident
+    ) => {{
+        ::core::mem::forget($drop_guard);
+
+        // Move owner out so it can be returned.
+        let owner_on_err = ::core::ptr::read($owner_ptr);
+
+        // Deallocate without dropping owner.
+        let layout = $crate::alloc::alloc::Layout::new::<$JoinedCell>();
+        $crate::unsafe_self_cell::deallocate_joined(&$allocator, $joined_void_ptr, layout);
+
+        ::core::result::Result::Err(($($previous,)* owner_on_err, $err))
+    }};
+    (
+        $mode:ident,
+        $JoinedCell:ty,
+        $err:ident,
+        [$($previous:ident),*],
+        $drop_guard:ident,
+        $owner_ptr:ident,
+        $allocator:ident,
+        $joined_void_ptr:ident
+    ) => {{
+        // drop_guard drops owner and deallocates.
+        ::core::result::Result::Err($err)
+    }};
 }
 
 #[doc(hidden)]
 #[macro_export]
-macro_rules! _self_cell_try_new_or_recover {
-    ($Vis:vis, $Owner:ty $(=> $OwnerLifetime:lifetime)?, $Dependent:ident) => {
-        /// Constructs a new self-referential struct or returns an error.
-        ///
-        /// Returns owner and error as tuple on error.
-        $Vis fn try_new_or_recover<Err>(
-            owner: $Owner,
-            dependent_builder:
-                impl for<'_q> ::core::ops::FnOnce(&'_q $Owner) -> ::core::result::Result<$Dependent<'_q>, Err>
-    ) -> ::core::result::Result<Self, ($Owner, Err)> {
-            type JoinedCell<'_q $(, $OwnerLifetime)?> =
-                    $crate::unsafe_self_cell::JoinedCell<$Owner, $Dependent<'_q>>;
+macro_rules! _self_cell_with_owner_mut {
+    ($Vis:vis, $Methods:tt, $Owner:ty $(=> $OwnerLifetime:lifetime)?, $Dependent:tt) => {
+        $crate::_self_cell_method!(with_owner_mut, $Vis, $Methods, {
+            /// Drops the dependent, calls `edit` with an unique reference to owner and then
+            /// rebuilds the dependent in place, reusing the existing allocation.
+            ///
+            /// # Panics
+            ///
+            /// Aborts the process should dropping the dependent, `edit` or `dependent_builder`
+            /// panic, as `self` can't be left without a dependent. Use `try_with_owner_mut` if
+            /// that has to be avoided, which consumes `self` and drops it on panic.
+            fn with_owner_mut(
+                &mut self,
+                edit: impl ::core::ops::FnOnce(&mut $Owner),
+                dependent_builder: impl for<'_q> ::core::ops::FnOnce(&'_q $Owner) -> $crate::_self_cell_dependent!($Dependent, '_q)
+            ) {
+                // unsafe placed here to make sure the rebuild can't be abused.
+                unsafe {
+                    self.unsafe_self_cell.rebuild_dependent::<$crate::_self_cell_dependent!($Dependent, '_), _>(
+                        |owner_ptr, dependent_ptr| {
+                            // The dependent is gone, so nothing borrows owner anymore.
+                            edit(&mut *owner_ptr);
+                            dependent_ptr.write(dependent_builder(&*owner_ptr));
+                        }
+                    )
+                }
+            }
+        });
 
-            // unsafe placed here to make sure the body macro can't be abused.
-            unsafe {
-                $crate::_self_cell_try_new_or_recover_body!(JoinedCell, owner $(=> $OwnerLifetime)?, dependent_builder)
+        $crate::_self_cell_method!(try_with_owner_mut, $Vis, $Methods, {
+            /// Drops the dependent, calls `edit` with an unique reference to owner and then
+            /// rebuilds the dependent or returns an error.
+            ///
+            /// Consumes `self` so that on error, or should `edit` or `dependent_builder` panic,
+            /// owner can be dropped and the allocation freed, instead of leaving `self` without a
+            /// dependent.
+            fn try_with_owner_mut<Err>(
+                self,
+                edit: impl ::core::ops::FnOnce(&mut $Owner),
+                dependent_builder:
+                    impl for<'_q> ::core::ops::FnOnce(&'_q $Owner) -> ::core::result::Result<$crate::_self_cell_dependent!($Dependent, '_q), Err>
+            ) -> ::core::result::Result<Self, Err> {
+                // unsafe placed here to make sure the body macro can't be abused.
+                unsafe {
+                    $crate::_self_cell_with_owner_mut_body!(
+                        try_new,
+                        $crate::unsafe_self_cell::JoinedCell<$Owner, $crate::_self_cell_dependent!($Dependent, '_)>,
+                        $Dependent,
+                        self,
+                        edit
+                        $(=> $OwnerLifetime)?,
+                        dependent_builder
+                    )
+                }
             }
-        }
-    };
-    ($Vis:vis, $Owner:ty $(=> $OwnerLifetime:lifetime)?, $Dependent:ident, async_builder) => {
-        /// Constructs a new self-referential struct or returns an error.
-        ///
-        /// Returns owner and error as tuple on error.
-        $Vis async fn try_new_or_recover<Err>(
-            owner: $Owner,
-            dependent_builder:
-                impl for<'_q> ::core::ops::AsyncFnOnce(&'_q $Owner) -> ::core::result::Result<$Dependent<'_q>, Err>
-        ) -> ::core::result::Result<Self, ($Owner, Err)> {
-            type JoinedCell<'_q $(, $OwnerLifetime)?> =
-                    $crate::unsafe_self_cell::JoinedCell<$Owner, $Dependent<'_q>>;
+        });
 
-            // unsafe placed here to make sure the body macro can't be abused.
-            unsafe {
-                $crate::_self_cell_try_new_or_recover_body!(JoinedCell, owner $(=> $OwnerLifetime)?, dependent_builder, async_builder)
+        $crate::_self_cell_method!(try_with_owner_mut_or_recover, $Vis, $Methods, {
+            /// Drops the dependent, calls `edit` with an unique reference to owner and then
+            /// rebuilds the dependent or returns an error. Consumes `self` like
+            /// `try_with_owner_mut`.
+            ///
+            /// Returns the edited owner and error as tuple on error.
+            fn try_with_owner_mut_or_recover<Err>(
+                self,
+                edit: impl ::core::ops::FnOnce(&mut $Owner),
+                dependent_builder:
+                    impl for<'_q> ::core::ops::FnOnce(&'_q $Owner) -> ::core::result::Result<$crate::_self_cell_dependent!($Dependent, '_q), Err>
+            ) -> ::core::result::Result<Self, ($Owner, Err)> {
+                // unsafe placed here to make sure the body macro can't be abused.
+                unsafe {
+                    $crate::_self_cell_with_owner_mut_body!(
+                        try_new_or_recover,
+                        $crate::unsafe_self_cell::JoinedCell<$Owner, $crate::_self_cell_dependent!($Dependent, '_)>,
+                        $Dependent,
+                        self,
+                        edit
+                        $(=> $OwnerLifetime)?,
+                        dependent_builder
+                    )
+                }
             }
-        }
-    };
-    ($Vis:vis, $Owner:ty, $Dependent:ident, $x:ident) => {
-        compile_error!("This macro only accepts `async_builder`");
+        });
     };
 }
 
 #[doc(hidden)]
 #[macro_export]
-macro_rules! _self_cell_try_new_or_recover_body {
-    ($JoinedCell:ty, $owner:expr $(=> $OwnerLifetime:lifetime)?, $dependent_builder:expr $(, $AsyncBuilder:ident)?) => {{
-        let layout = $crate::alloc::alloc::Layout::new::<$JoinedCell>();
-        assert!(layout.size() != 0);
+macro_rules! _self_cell_with_owner_mut_body {
+    (
+        $mode:ident,
+        $JoinedCell:ty,
+        $Dependent:tt,
+        $this:expr,
+        $edit:expr
+        $(=> $OwnerLifetime:lifetime)?,
+        $dependent_builder:expr
+    ) => {{
+        // The struct implements Drop, so the cell can't be moved out directly.
+        let this = ::core::mem::ManuallyDrop::new($this);
+        let unsafe_self_cell = ::core::ptr::read(&this.unsafe_self_cell);
 
-        let joined_void_ptr = ::core::ptr::NonNull::new($crate::alloc::alloc::alloc(layout)).unwrap();
+        let (joined_void_ptr, allocator) =
+            unsafe_self_cell.into_parts_without_dependent::<$crate::_self_cell_dependent!($Dependent, '_)>();
 
-        let mut joined_ptr = joined_void_ptr.cast::<$JoinedCell>();
+        let joined_ptr = joined_void_ptr.cast::<$JoinedCell>();
 
         let (owner_ptr, dependent_ptr) = <$JoinedCell>::_field_pointers(joined_ptr.as_ptr());
 
-        // Move owner into newly allocated space.
-        owner_ptr.write($owner);
+        // Drop guard that cleans up should editing owner or building the
+        // dependent fail.
+        let drop_guard =
+            $crate::unsafe_self_cell::OwnerAndCellDropGuard::new(joined_ptr, &allocator);
 
-        // Drop guard that cleans up should building the dependent panic.
-        let mut drop_guard =
-            $crate::unsafe_self_cell::OwnerAndCellDropGuard::new(joined_ptr);
+        // The dependent is gone, so nothing borrows owner anymore.
+        $edit(&mut *owner_ptr);
 
-        match $crate::_await_opt!($dependent_builder(&*owner_ptr) $(, $AsyncBuilder)?) {
+        match $crate::_self_cell_build_result!($mode, $dependent_builder(&*owner_ptr)) {
             ::core::result::Result::Ok(dependent) => {
                 dependent_ptr.write(dependent);
                 ::core::mem::forget(drop_guard);
@@ -720,26 +4223,23 @@ macro_rules! _self_cell_try_new_or_recover_body {
                 ::core::result::Result::Ok(Self {
                     unsafe_self_cell: $crate::unsafe_self_cell::UnsafeSelfCell::new(
                         joined_void_ptr,
+                        allocator
                     ),
                     $(owner_marker: $crate::_covariant_owner_marker_ctor!($OwnerLifetime) ,)?
                 })
             }
-            ::core::result::Result::Err(err) => {
-                // In contrast to into_owner ptr::read, here no dependent
-                // ever existed in this function and so we are sure its
-                // drop impl can't access owner after the read.
-                // And err can't return a reference to owner.
-                let owner_on_err = ::core::ptr::read(owner_ptr);
-
-                // Allowing drop_guard to finish would let it double free owner.
-                // So we dealloc the JoinedCell here manually.
-                ::core::mem::forget(drop_guard);
-                $crate::alloc::alloc::dealloc(joined_void_ptr.as_ptr(), layout);
-
-                ::core::result::Result::Err((owner_on_err, err))
-            }
+            ::core::result::Result::Err(err) => $crate::_self_cell_rebuild_err!(
+                $mode,
+                $JoinedCell,
+                err,
+                [],
+                drop_guard,
+                owner_ptr,
+                allocator,
+                joined_void_ptr
+            ),
         }
-    }}
+    }};
 }
 
 #[doc(hidden)]
@@ -759,45 +4259,910 @@ macro_rules! _await_opt {
 #[doc(hidden)]
 #[macro_export]
 macro_rules! _impl_automatic_derive {
-    (Debug, $StructName:ident) => {
-        impl ::core::fmt::Debug for $StructName {
+    // The field names and generics can't be repeated per derive, so they are
+    // passed on as single token trees. The first holds the owner field and its
+    // borrow accessor and the dependent field and its with accessor, the
+    // second the impl parameters, type arguments and where predicates.
+    (
+        [$($AutomaticDerive:ident $(($($DeriveArgs:tt)*))?),*],
+        $StructName:ident,
+        $Names:tt,
+        $Generics:tt,
+        $Owner:ty,
+        $Dependent:tt
+    ) => {
+        $(
+            $crate::_impl_automatic_derive!(
+                $AutomaticDerive [$($($DeriveArgs)*)?], $StructName, $Names, $Generics, $Owner, $Dependent
+            );
+        )*
+    };
+    (
+        Debug [],
+        $StructName:ident,
+        {$OwnerField:ident $BorrowOwner:ident, $DependentField:ident $WithDependent:ident},
+        {[$($Params:tt)*] [$($Args:tt)*] [$($Where:tt)*]},
+        $Owner:ty,
+        $Dependent:tt
+    ) => {
+        impl<$($Params)*> ::core::fmt::Debug for $StructName<$($Args)*>
+        where
+            $($Where)*
+            $Owner: ::core::fmt::Debug,
+            for<'_q> $crate::_self_cell_dependent!($Dependent, '_q): ::core::fmt::Debug,
+        {
             fn fmt(
                 &self,
                 fmt: &mut ::core::fmt::Formatter,
             ) -> ::core::result::Result<(), ::core::fmt::Error> {
-                self.with_dependent(|owner, dependent| {
+                self.$WithDependent(|owner, dependent| {
                     fmt.debug_struct(stringify!($StructName))
-                        .field("owner", owner)
-                        .field("dependent", dependent)
+                        .field(stringify!($OwnerField), owner)
+                        .field(stringify!($DependentField), dependent)
+                        .finish()
+                })
+            }
+        }
+    };
+    (
+        Debug [dependent_only],
+        $StructName:ident,
+        {$OwnerField:ident $BorrowOwner:ident, $DependentField:ident $WithDependent:ident},
+        {[$($Params:tt)*] [$($Args:tt)*] [$($Where:tt)*]},
+        $Owner:ty,
+        $Dependent:tt
+    ) => {
+        impl<$($Params)*> ::core::fmt::Debug for $StructName<$($Args)*>
+        where
+            $($Where)*
+            for<'_q> $crate::_self_cell_dependent!($Dependent, '_q): ::core::fmt::Debug,
+        {
+            fn fmt(
+                &self,
+                fmt: &mut ::core::fmt::Formatter,
+            ) -> ::core::result::Result<(), ::core::fmt::Error> {
+                self.$WithDependent(|_, dependent| {
+                    fmt.debug_struct(stringify!($StructName))
+                        .field(stringify!($DependentField), dependent)
+                        .finish()
+                })
+            }
+        }
+    };
+    (
+        Debug [owner_only],
+        $StructName:ident,
+        {$OwnerField:ident $BorrowOwner:ident, $DependentField:ident $WithDependent:ident},
+        {[$($Params:tt)*] [$($Args:tt)*] [$($Where:tt)*]},
+        $Owner:ty,
+        $Dependent:tt
+    ) => {
+        impl<$($Params)*> ::core::fmt::Debug for $StructName<$($Args)*>
+        where
+            $($Where)*
+            $Owner: ::core::fmt::Debug,
+        {
+            fn fmt(
+                &self,
+                fmt: &mut ::core::fmt::Formatter,
+            ) -> ::core::result::Result<(), ::core::fmt::Error> {
+                self.$WithDependent(|owner, _| {
+                    fmt.debug_struct(stringify!($StructName))
+                        .field(stringify!($OwnerField), owner)
+                        .finish()
+                })
+            }
+        }
+    };
+    (
+        Debug [owner_len],
+        $StructName:ident,
+        {$OwnerField:ident $BorrowOwner:ident, $DependentField:ident $WithDependent:ident},
+        {[$($Params:tt)*] [$($Args:tt)*] [$($Where:tt)*]},
+        $Owner:ty,
+        $Dependent:tt
+    ) => {
+        impl<$($Params)*> ::core::fmt::Debug for $StructName<$($Args)*>
+        where
+            $($Where)*
+            $Owner: ::core::convert::AsRef<[u8]>,
+            for<'_q> $crate::_self_cell_dependent!($Dependent, '_q): ::core::fmt::Debug,
+        {
+            fn fmt(
+                &self,
+                fmt: &mut ::core::fmt::Formatter,
+            ) -> ::core::result::Result<(), ::core::fmt::Error> {
+                self.$WithDependent(|owner, dependent| {
+                    fmt.debug_struct(stringify!($StructName))
+                        .field(
+                            concat!(stringify!($OwnerField), "_len"),
+                            &::core::convert::AsRef::<[u8]>::as_ref(owner).len(),
+                        )
+                        .field(stringify!($DependentField), dependent)
                         .finish()
                 })
             }
         }
     };
-    (PartialEq, $StructName:ident) => {
-        impl ::core::cmp::PartialEq for $StructName {
+    (
+        PartialEq [],
+        $StructName:ident,
+        {$OwnerField:ident $BorrowOwner:ident, $DependentField:ident $WithDependent:ident},
+        {[$($Params:tt)*] [$($Args:tt)*] [$($Where:tt)*]},
+        $Owner:ty,
+        $Dependent:tt
+    ) => {
+        impl<$($Params)*> ::core::cmp::PartialEq for $StructName<$($Args)*>
+        where
+            $($Where)*
+            $Owner: ::core::cmp::PartialEq,
+        {
+            fn eq(&self, other: &Self) -> bool {
+                *self.$BorrowOwner() == *other.$BorrowOwner()
+            }
+        }
+
+        impl<$($Params)*> ::core::cmp::PartialEq<$Owner> for $StructName<$($Args)*>
+        where
+            $($Where)*
+            $Owner: ::core::cmp::PartialEq,
+        {
+            fn eq(&self, other: &$Owner) -> bool {
+                *self.$BorrowOwner() == *other
+            }
+        }
+    };
+    (
+        Eq [],
+        $StructName:ident,
+        $Names:tt,
+        {[$($Params:tt)*] [$($Args:tt)*] [$($Where:tt)*]},
+        $Owner:ty,
+        $Dependent:tt
+    ) => {
+        impl<$($Params)*> ::core::cmp::Eq for $StructName<$($Args)*>
+        where
+            $($Where)*
+            $Owner: ::core::cmp::Eq,
+        {
+        }
+    };
+    (
+        PartialOrd [],
+        $StructName:ident,
+        {$OwnerField:ident $BorrowOwner:ident, $DependentField:ident $WithDependent:ident},
+        {[$($Params:tt)*] [$($Args:tt)*] [$($Where:tt)*]},
+        $Owner:ty,
+        $Dependent:tt
+    ) => {
+        impl<$($Params)*> ::core::cmp::PartialOrd for $StructName<$($Args)*>
+        where
+            $($Where)*
+            $Owner: ::core::cmp::PartialOrd,
+        {
+            fn partial_cmp(&self, other: &Self) -> ::core::option::Option<::core::cmp::Ordering> {
+                self.$BorrowOwner().partial_cmp(other.$BorrowOwner())
+            }
+        }
+    };
+    (
+        Ord [],
+        $StructName:ident,
+        {$OwnerField:ident $BorrowOwner:ident, $DependentField:ident $WithDependent:ident},
+        {[$($Params:tt)*] [$($Args:tt)*] [$($Where:tt)*]},
+        $Owner:ty,
+        $Dependent:tt
+    ) => {
+        impl<$($Params)*> ::core::cmp::Ord for $StructName<$($Args)*>
+        where
+            $($Where)*
+            $Owner: ::core::cmp::Ord,
+        {
+            fn cmp(&self, other: &Self) -> ::core::cmp::Ordering {
+                self.$BorrowOwner().cmp(other.$BorrowOwner())
+            }
+        }
+    };
+    (
+        Hash [],
+        $StructName:ident,
+        {$OwnerField:ident $BorrowOwner:ident, $DependentField:ident $WithDependent:ident},
+        {[$($Params:tt)*] [$($Args:tt)*] [$($Where:tt)*]},
+        $Owner:ty,
+        $Dependent:tt
+    ) => {
+        impl<$($Params)*> ::core::hash::Hash for $StructName<$($Args)*>
+        where
+            $($Where)*
+            $Owner: ::core::hash::Hash,
+        {
+            fn hash<H: ::core::hash::Hasher>(&self, state: &mut H) {
+                self.$BorrowOwner().hash(state);
+            }
+        }
+    };
+    (
+        $x:ident [owner],
+        $StructName:ident,
+        $Names:tt,
+        $Generics:tt,
+        $Owner:ty,
+        $Dependent:tt
+    ) => {
+        $crate::_impl_automatic_derive!($x [], $StructName, $Names, $Generics, $Owner, $Dependent);
+    };
+    (
+        PartialEq [dependent],
+        $StructName:ident,
+        {$OwnerField:ident $BorrowOwner:ident, $DependentField:ident $WithDependent:ident},
+        {[$($Params:tt)*] [$($Args:tt)*] [$($Where:tt)*]},
+        $Owner:ty,
+        $Dependent:tt
+    ) => {
+        impl<$($Params)*> ::core::cmp::PartialEq for $StructName<$($Args)*>
+        where
+            $($Where)*
+            for<'_q> $crate::_self_cell_dependent!($Dependent, '_q): ::core::cmp::PartialEq,
+        {
+            fn eq(&self, other: &Self) -> bool {
+                self.$WithDependent(|_, dependent| {
+                    other.$WithDependent(|_, other_dependent| *dependent == *other_dependent)
+                })
+            }
+        }
+    };
+    (
+        PartialEq [both],
+        $StructName:ident,
+        {$OwnerField:ident $BorrowOwner:ident, $DependentField:ident $WithDependent:ident},
+        {[$($Params:tt)*] [$($Args:tt)*] [$($Where:tt)*]},
+        $Owner:ty,
+        $Dependent:tt
+    ) => {
+        impl<$($Params)*> ::core::cmp::PartialEq for $StructName<$($Args)*>
+        where
+            $($Where)*
+            $Owner: ::core::cmp::PartialEq,
+            for<'_q> $crate::_self_cell_dependent!($Dependent, '_q): ::core::cmp::PartialEq,
+        {
             fn eq(&self, other: &Self) -> bool {
-                *self.borrow_owner() == *other.borrow_owner()
+                self.$WithDependent(|owner, dependent| {
+                    other.$WithDependent(|other_owner, other_dependent| {
+                        *owner == *other_owner && *dependent == *other_dependent
+                    })
+                })
             }
         }
     };
-    (Eq, $StructName:ident) => {
-        // TODO this should only be allowed if owner is Eq.
-        impl ::core::cmp::Eq for $StructName {}
+    (
+        Eq [dependent],
+        $StructName:ident,
+        $Names:tt,
+        {[$($Params:tt)*] [$($Args:tt)*] [$($Where:tt)*]},
+        $Owner:ty,
+        $Dependent:tt
+    ) => {
+        impl<$($Params)*> ::core::cmp::Eq for $StructName<$($Args)*>
+        where
+            $($Where)*
+            for<'_q> $crate::_self_cell_dependent!($Dependent, '_q): ::core::cmp::Eq,
+        {
+        }
+    };
+    (
+        Eq [both],
+        $StructName:ident,
+        $Names:tt,
+        {[$($Params:tt)*] [$($Args:tt)*] [$($Where:tt)*]},
+        $Owner:ty,
+        $Dependent:tt
+    ) => {
+        impl<$($Params)*> ::core::cmp::Eq for $StructName<$($Args)*>
+        where
+            $($Where)*
+            $Owner: ::core::cmp::Eq,
+            for<'_q> $crate::_self_cell_dependent!($Dependent, '_q): ::core::cmp::Eq,
+        {
+        }
+    };
+    (
+        Hash [dependent],
+        $StructName:ident,
+        {$OwnerField:ident $BorrowOwner:ident, $DependentField:ident $WithDependent:ident},
+        {[$($Params:tt)*] [$($Args:tt)*] [$($Where:tt)*]},
+        $Owner:ty,
+        $Dependent:tt
+    ) => {
+        impl<$($Params)*> ::core::hash::Hash for $StructName<$($Args)*>
+        where
+            $($Where)*
+            for<'_q> $crate::_self_cell_dependent!($Dependent, '_q): ::core::hash::Hash,
+        {
+            fn hash<H: ::core::hash::Hasher>(&self, state: &mut H) {
+                self.$WithDependent(|_, dependent| ::core::hash::Hash::hash(dependent, state));
+            }
+        }
     };
-    (Hash, $StructName:ident) => {
-        impl ::core::hash::Hash for $StructName {
+    (
+        Hash [both],
+        $StructName:ident,
+        {$OwnerField:ident $BorrowOwner:ident, $DependentField:ident $WithDependent:ident},
+        {[$($Params:tt)*] [$($Args:tt)*] [$($Where:tt)*]},
+        $Owner:ty,
+        $Dependent:tt
+    ) => {
+        impl<$($Params)*> ::core::hash::Hash for $StructName<$($Args)*>
+        where
+            $($Where)*
+            $Owner: ::core::hash::Hash,
+            for<'_q> $crate::_self_cell_dependent!($Dependent, '_q): ::core::hash::Hash,
+        {
             fn hash<H: ::core::hash::Hasher>(&self, state: &mut H) {
-                self.borrow_owner().hash(state);
+                self.$WithDependent(|owner, dependent| {
+                    ::core::hash::Hash::hash(owner, state);
+                    ::core::hash::Hash::hash(dependent, state);
+                });
+            }
+        }
+    };
+    (
+        Clone [],
+        $StructName:ident,
+        {$OwnerField:ident $BorrowOwner:ident, $DependentField:ident $WithDependent:ident},
+        {[$($Params:tt)*] [$($Args:tt)*] [$($Where:tt)*]},
+        $Owner:ty,
+        $Dependent:tt
+    ) => {
+        impl<$($Params)*> ::core::clone::Clone for $StructName<$($Args)*>
+        where
+            $($Where)*
+            $Owner: ::core::clone::Clone,
+        {
+            fn clone(&self) -> Self {
+                Self::new(::core::clone::Clone::clone(self.$BorrowOwner()), |owner| {
+                    ::core::convert::From::from(owner)
+                })
+            }
+        }
+    };
+    (
+        Clone [with = $Builder:expr],
+        $StructName:ident,
+        {$OwnerField:ident $BorrowOwner:ident, $DependentField:ident $WithDependent:ident},
+        {[$($Params:tt)*] [$($Args:tt)*] [$($Where:tt)*]},
+        $Owner:ty,
+        $Dependent:tt
+    ) => {
+        impl<$($Params)*> ::core::clone::Clone for $StructName<$($Args)*>
+        where
+            $($Where)*
+            $Owner: ::core::clone::Clone,
+        {
+            fn clone(&self) -> Self {
+                Self::new(::core::clone::Clone::clone(self.$BorrowOwner()), $Builder)
+            }
+        }
+    };
+    (
+        Clone [rebase],
+        $StructName:ident,
+        {$OwnerField:ident $BorrowOwner:ident, $DependentField:ident $WithDependent:ident},
+        {[$($Params:tt)*] [$($Args:tt)*] [$($Where:tt)*]},
+        $Owner:ty,
+        $Dependent:tt
+    ) => {
+        impl<$($Params)*> ::core::clone::Clone for $StructName<$($Args)*>
+        where
+            $($Where)*
+            $Owner: $crate::RebaseOwner,
+            for<'_q> $crate::_self_cell_dependent!($Dependent, '_q): $crate::Rebase<$Owner>,
+        {
+            fn clone(&self) -> Self {
+                let old_owner = self.$BorrowOwner();
+
+                Self::new(::core::clone::Clone::clone(old_owner), |new_owner| {
+                    self.$WithDependent(|_, old_dependent| {
+                        // The new owner is a clone of the one old_dependent borrows
+                        // from, and the result is the same dependent type.
+                        unsafe {
+                            $crate::unsafe_self_cell::rebase_dependent(
+                                old_owner,
+                                new_owner,
+                                old_dependent,
+                            )
+                        }
+                    })
+                })
             }
         }
     };
-    ($x:ident, $StructName:ident) => {
+    (
+        Display [],
+        $StructName:ident,
+        {$OwnerField:ident $BorrowOwner:ident, $DependentField:ident $WithDependent:ident},
+        {[$($Params:tt)*] [$($Args:tt)*] [$($Where:tt)*]},
+        $Owner:ty,
+        $Dependent:tt
+    ) => {
+        impl<$($Params)*> ::core::fmt::Display for $StructName<$($Args)*>
+        where
+            $($Where)*
+            for<'_q> $crate::_self_cell_dependent!($Dependent, '_q): ::core::fmt::Display,
+        {
+            fn fmt(
+                &self,
+                fmt: &mut ::core::fmt::Formatter,
+            ) -> ::core::result::Result<(), ::core::fmt::Error> {
+                self.$WithDependent(|_, dependent| ::core::fmt::Display::fmt(dependent, fmt))
+            }
+        }
+    };
+    (
+        Error [],
+        $StructName:ident,
+        {$OwnerField:ident $BorrowOwner:ident, $DependentField:ident $WithDependent:ident},
+        {[$($Params:tt)*] [$($Args:tt)*] [$($Where:tt)*]},
+        $Owner:ty,
+        $Dependent:tt
+    ) => {
+        $crate::_self_cell_std! {
+            impl<$($Params)*> $crate::std::error::Error for $StructName<$($Args)*>
+            where
+                $($Where)*
+                Self: ::core::fmt::Debug + ::core::fmt::Display,
+                for<'_q> $crate::_self_cell_dependent!($Dependent, '_q): $crate::std::error::Error,
+            {
+                fn source(&self) -> ::core::option::Option<&(dyn $crate::std::error::Error + 'static)> {
+                    self.$WithDependent(|_, dependent| $crate::std::error::Error::source(dependent))
+                }
+            }
+        }
+    };
+    (
+        Serialize [],
+        $StructName:ident,
+        {$OwnerField:ident $BorrowOwner:ident, $DependentField:ident $WithDependent:ident},
+        {[$($Params:tt)*] [$($Args:tt)*] [$($Where:tt)*]},
+        $Owner:ty,
+        $Dependent:tt
+    ) => {
+        $crate::_self_cell_serde! {
+            impl<$($Params)*> $crate::_serde::Serialize for $StructName<$($Args)*>
+            where
+                $($Where)*
+                $Owner: $crate::_serde::Serialize,
+            {
+                fn serialize<S: $crate::_serde::Serializer>(
+                    &self,
+                    serializer: S,
+                ) -> ::core::result::Result<S::Ok, S::Error> {
+                    $crate::_serde::Serialize::serialize(self.$BorrowOwner(), serializer)
+                }
+            }
+        }
+    };
+    (
+        Deserialize [],
+        $StructName:ident,
+        $Names:tt,
+        $Generics:tt,
+        $Owner:ty,
+        $Dependent:tt
+    ) => {
+        $crate::_impl_automatic_derive!(
+            Deserialize [with = |owner| ::core::convert::From::from(owner)],
+            $StructName,
+            $Names,
+            $Generics,
+            $Owner,
+            $Dependent
+        );
+    };
+    (
+        Deserialize [with = $Builder:expr],
+        $StructName:ident,
+        $Names:tt,
+        {[$($Params:tt)*] [$($Args:tt)*] [$($Where:tt)*]},
+        $Owner:ty,
+        $Dependent:tt
+    ) => {
+        $crate::_self_cell_serde! {
+            impl<'de, $($Params)*> $crate::_serde::Deserialize<'de> for $StructName<$($Args)*>
+            where
+                $($Where)*
+                $Owner: $crate::_serde::Deserialize<'de>,
+            {
+                fn deserialize<D: $crate::_serde::Deserializer<'de>>(
+                    deserializer: D,
+                ) -> ::core::result::Result<Self, D::Error> {
+                    let owner = <$Owner as $crate::_serde::Deserialize<'de>>::deserialize(deserializer)?;
+                    ::core::result::Result::Ok(Self::new(owner, $Builder))
+                }
+            }
+        }
+    };
+    (
+        Deserialize [try_with = $Builder:expr],
+        $StructName:ident,
+        $Names:tt,
+        {[$($Params:tt)*] [$($Args:tt)*] [$($Where:tt)*]},
+        $Owner:ty,
+        $Dependent:tt
+    ) => {
+        $crate::_self_cell_serde! {
+            impl<'de, $($Params)*> $crate::_serde::Deserialize<'de> for $StructName<$($Args)*>
+            where
+                $($Where)*
+                $Owner: $crate::_serde::Deserialize<'de>,
+            {
+                fn deserialize<D: $crate::_serde::Deserializer<'de>>(
+                    deserializer: D,
+                ) -> ::core::result::Result<Self, D::Error> {
+                    let owner = <$Owner as $crate::_serde::Deserialize<'de>>::deserialize(deserializer)?;
+                    Self::try_new(owner, $Builder).map_err(<D::Error as $crate::_serde::de::Error>::custom)
+                }
+            }
+        }
+    };
+    ($x:ident $XArgs:tt, $StructName:ident, $Names:tt, $Generics:tt, $Owner:ty, $Dependent:tt) => {
         compile_error!(concat!(
             "No automatic trait impl for trait: ",
             stringify!($x)
         ));
     };
 }
+#[doc(hidden)]
+#[macro_export]
+macro_rules! _impl_automatic_derive_dependents {
+    // The generics, field and dependent lists can't be repeated per derive, so
+    // they are passed on as single token trees.
+    (
+        [$($AutomaticDerive:ident $(($($DeriveArgs:tt)*))?),*],
+        $StructName:ident,
+        $Generics:tt,
+        $fields:tt,
+        $Owner:ty,
+        $Dependents:tt
+    ) => {
+        $(
+            $crate::_impl_automatic_derive_dependents!(
+                $AutomaticDerive [$($($DeriveArgs)*)?], $StructName, $Generics, $fields, $Owner, $Dependents
+            );
+        )*
+    };
+    (
+        Debug [],
+        $StructName:ident,
+        {[$($Params:tt)*] [$($Args:tt)*] [$($Where:tt)*]},
+        [$($field:ident),+],
+        $Owner:ty,
+        [$($Dependent:tt),+]
+    ) => {
+        impl<$($Params)*> ::core::fmt::Debug for $StructName<$($Args)*>
+        where
+            $($Where)*
+            $Owner: ::core::fmt::Debug,
+            $(for<'_q> $crate::_self_cell_dependent!($Dependent, '_q): ::core::fmt::Debug,)+
+        {
+            fn fmt(
+                &self,
+                fmt: &mut ::core::fmt::Formatter,
+            ) -> ::core::result::Result<(), ::core::fmt::Error> {
+                self.with_dependents(|owner $(, $field)+| {
+                    fmt.debug_struct(stringify!($StructName))
+                        .field("owner", owner)
+                        $(.field(stringify!($field), $field))+
+                        .finish()
+                })
+            }
+        }
+    };
+    (Clone $CloneArgs:tt, $StructName:ident, $Generics:tt, $fields:tt, $Owner:ty, $Dependent:tt) => {
+        compile_error!("Clone can't be automatically implemented for cells with multiple dependents");
+    };
+    (Deserialize $DeserializeArgs:tt, $StructName:ident, $Generics:tt, $fields:tt, $Owner:ty, $Dependent:tt) => {
+        compile_error!("Deserialize can't be automatically implemented for cells with multiple dependents");
+    };
+    (Display $DisplayArgs:tt, $StructName:ident, $Generics:tt, $fields:tt, $Owner:ty, $Dependent:tt) => {
+        compile_error!("Display can't be automatically implemented for cells with multiple dependents");
+    };
+    (Error $ErrorArgs:tt, $StructName:ident, $Generics:tt, $fields:tt, $Owner:ty, $Dependent:tt) => {
+        compile_error!("Error can't be automatically implemented for cells with multiple dependents");
+    };
+    (Debug $DebugArgs:tt, $StructName:ident, $Generics:tt, $fields:tt, $Owner:ty, $Dependent:tt) => {
+        compile_error!("Debug options can't be used for cells with multiple dependents");
+    };
+    ($x:ident [dependent], $StructName:ident, $Generics:tt, $fields:tt, $Owner:ty, $Dependent:tt) => {
+        compile_error!(concat!(
+            stringify!($x),
+            "(dependent) can't be automatically implemented for cells with multiple dependents"
+        ));
+    };
+    ($x:ident [both], $StructName:ident, $Generics:tt, $fields:tt, $Owner:ty, $Dependent:tt) => {
+        compile_error!(concat!(
+            stringify!($x),
+            "(both) can't be automatically implemented for cells with multiple dependents"
+        ));
+    };
+    (
+        $x:ident $XArgs:tt,
+        $StructName:ident,
+        $Generics:tt,
+        $fields:tt,
+        $Owner:ty,
+        [$Dependent:tt $(, $RestDependent:tt)*]
+    ) => {
+        $crate::_impl_automatic_derive!(
+            $x $XArgs,
+            $StructName,
+            {owner borrow_owner, dependent with_dependent},
+            $Generics,
+            $Owner,
+            $Dependent
+        );
+    };
+}
+#[doc(hidden)]
+#[macro_export]
+macro_rules! _impl_automatic_derive_owners {
+    // The generics and owner list can't be repeated per derive, so they are
+    // passed on as single token trees.
+    (
+        [$($AutomaticDerive:ident $(($($DeriveArgs:tt)*))?),*],
+        $StructName:ident,
+        $Generics:tt,
+        $fields:tt,
+        $Owner:ty,
+        $Dependent:tt
+    ) => {
+        $(
+            $crate::_impl_automatic_derive_owners!(
+                $AutomaticDerive [$($($DeriveArgs)*)?], $StructName, $Generics, $fields, $Owner, $Dependent
+            );
+        )*
+    };
+    (
+        Debug [],
+        $StructName:ident,
+        {[$($Params:tt)*] [$($Args:tt)*] [$($Where:tt)*]},
+        [$($field:ident),+],
+        $Owner:ty,
+        $Dependent:tt
+    ) => {
+        impl<$($Params)*> ::core::fmt::Debug for $StructName<$($Args)*>
+        where
+            $($Where)*
+            $Owner: ::core::fmt::Debug,
+            for<'_q> $crate::_self_cell_dependent!($Dependent, '_q): ::core::fmt::Debug,
+        {
+            fn fmt(
+                &self,
+                fmt: &mut ::core::fmt::Formatter,
+            ) -> ::core::result::Result<(), ::core::fmt::Error> {
+                self.with_dependent(|$($field,)+ dependent| {
+                    fmt.debug_struct(stringify!($StructName))
+                        $(.field(stringify!($field), $field))+
+                        .field("dependent", dependent)
+                        .finish()
+                })
+            }
+        }
+    };
+    (
+        PartialEq [],
+        $StructName:ident,
+        {[$($Params:tt)*] [$($Args:tt)*] [$($Where:tt)*]},
+        [$($field:ident),+],
+        $Owner:ty,
+        $Dependent:tt
+    ) => {
+        impl<$($Params)*> ::core::cmp::PartialEq for $StructName<$($Args)*>
+        where
+            $($Where)*
+            $Owner: ::core::cmp::PartialEq,
+        {
+            fn eq(&self, other: &Self) -> bool {
+                *self.borrow_owners() == *other.borrow_owners()
+            }
+        }
+
+        impl<$($Params)*> ::core::cmp::PartialEq<$Owner> for $StructName<$($Args)*>
+        where
+            $($Where)*
+            $Owner: ::core::cmp::PartialEq,
+        {
+            fn eq(&self, other: &$Owner) -> bool {
+                *self.borrow_owners() == *other
+            }
+        }
+    };
+    (
+        PartialOrd [],
+        $StructName:ident,
+        {[$($Params:tt)*] [$($Args:tt)*] [$($Where:tt)*]},
+        [$($field:ident),+],
+        $Owner:ty,
+        $Dependent:tt
+    ) => {
+        impl<$($Params)*> ::core::cmp::PartialOrd for $StructName<$($Args)*>
+        where
+            $($Where)*
+            $Owner: ::core::cmp::PartialOrd,
+        {
+            fn partial_cmp(&self, other: &Self) -> ::core::option::Option<::core::cmp::Ordering> {
+                self.borrow_owners().partial_cmp(other.borrow_owners())
+            }
+        }
+    };
+    (
+        Ord [],
+        $StructName:ident,
+        {[$($Params:tt)*] [$($Args:tt)*] [$($Where:tt)*]},
+        [$($field:ident),+],
+        $Owner:ty,
+        $Dependent:tt
+    ) => {
+        impl<$($Params)*> ::core::cmp::Ord for $StructName<$($Args)*>
+        where
+            $($Where)*
+            $Owner: ::core::cmp::Ord,
+        {
+            fn cmp(&self, other: &Self) -> ::core::cmp::Ordering {
+                self.borrow_owners().cmp(other.borrow_owners())
+            }
+        }
+    };
+    (
+        Hash [],
+        $StructName:ident,
+        {[$($Params:tt)*] [$($Args:tt)*] [$($Where:tt)*]},
+        [$($field:ident),+],
+        $Owner:ty,
+        $Dependent:tt
+    ) => {
+        impl<$($Params)*> ::core::hash::Hash for $StructName<$($Args)*>
+        where
+            $($Where)*
+            $Owner: ::core::hash::Hash,
+        {
+            fn hash<H: ::core::hash::Hasher>(&self, state: &mut H) {
+                self.borrow_owners().hash(state);
+            }
+        }
+    };
+    (Clone $CloneArgs:tt, $StructName:ident, $Generics:tt, $fields:tt, $Owner:ty, $Dependent:tt) => {
+        compile_error!("Clone can't be automatically implemented for cells with multiple owners");
+    };
+    (Deserialize $DeserializeArgs:tt, $StructName:ident, $Generics:tt, $fields:tt, $Owner:ty, $Dependent:tt) => {
+        compile_error!("Deserialize can't be automatically implemented for cells with multiple owners");
+    };
+    (Display $DisplayArgs:tt, $StructName:ident, $Generics:tt, $fields:tt, $Owner:ty, $Dependent:tt) => {
+        compile_error!("Display can't be automatically implemented for cells with multiple owners");
+    };
+    (Error $ErrorArgs:tt, $StructName:ident, $Generics:tt, $fields:tt, $Owner:ty, $Dependent:tt) => {
+        compile_error!("Error can't be automatically implemented for cells with multiple owners");
+    };
+    (Debug $DebugArgs:tt, $StructName:ident, $Generics:tt, $fields:tt, $Owner:ty, $Dependent:tt) => {
+        compile_error!("Debug options can't be used for cells with multiple owners");
+    };
+    ($x:ident [owner], $StructName:ident, $Generics:tt, $fields:tt, $Owner:ty, $Dependent:tt) => {
+        $crate::_impl_automatic_derive_owners!($x [], $StructName, $Generics, $fields, $Owner, $Dependent);
+    };
+    ($x:ident [dependent], $StructName:ident, $Generics:tt, $fields:tt, $Owner:ty, $Dependent:tt) => {
+        compile_error!(concat!(
+            stringify!($x),
+            "(dependent) can't be automatically implemented for cells with multiple owners"
+        ));
+    };
+    ($x:ident [both], $StructName:ident, $Generics:tt, $fields:tt, $Owner:ty, $Dependent:tt) => {
+        compile_error!(concat!(
+            stringify!($x),
+            "(both) can't be automatically implemented for cells with multiple owners"
+        ));
+    };
+    ($x:ident $XArgs:tt, $StructName:ident, $Generics:tt, $fields:tt, $Owner:ty, $Dependent:tt) => {
+        $crate::_impl_automatic_derive!(
+            $x $XArgs,
+            $StructName,
+            {owner borrow_owner, dependent with_dependent},
+            $Generics,
+            $Owner,
+            $Dependent
+        );
+    };
+}
+
+/// Implements [`Rebase`] for a struct by rebasing each of its fields, which
+/// all have to implement [`Rebase`] themselves. This takes the place of a
+/// derive, as `self_cell` has no proc-macro dependency.
+///
+/// All fields have to be listed with their type, by name or for tuple structs
+/// by index, the struct is rebuilt from them so a missing field fails to
+/// compile. The impl is for every owner that all field types implement
+/// `Rebase` for, and type parameters get a `Rebase` bound. `PhantomData`
+/// fields, eg. `PhantomData<&'a str>`, implement `Rebase` and are just
+/// recreated.
+///
+/// Calling it is safe, the emitted `unsafe impl` upholds the contract of
+/// [`Rebase`] because every field is replaced by its own rebased value.
+/// Types that hold references in ways the field types don't show, eg. raw
+/// pointers or `Cell`, don't implement `Rebase` and are rejected. Enums and
+/// unions are not supported, implement `Rebase` by hand for them.
+///
+/// ### Example:
+///
+/// ```rust
+/// use self_cell::{impl_rebase, self_cell};
+///
+/// struct Ast<'a> {
+///     words: Vec<&'a str>,
+///     title: Option<&'a str>,
+/// }
+///
+/// impl_rebase!(Ast<'a> { words: Vec<&'a str>, title: Option<&'a str> });
+///
+/// struct Span<'a>(&'a str, usize);
+///
+/// impl_rebase!(Span<'a>(0: &'a str, 1: usize));
+///
+/// self_cell!(
+///     struct AstCell {
+///         owner: String,
+///
+///         #[covariant]
+///         dependent: Ast,
+///     }
+///
+///     impl {Clone(rebase)}
+/// );
+///
+/// let cell = AstCell::new("The quick fox".into(), |owner| Ast {
+///     words: owner.split(' ').collect(),
+///     title: owner.split(' ').next(),
+/// });
+/// let cloned = cell.clone();
+/// drop(cell);
+///
+/// assert_eq!(cloned.borrow_dependent().words, ["The", "quick", "fox"]);
+/// assert_eq!(cloned.borrow_dependent().title, Some("The"));
+/// ```
+#[macro_export]
+macro_rules! impl_rebase {
+    (
+        $Name:ident $(<$($Lifetime:lifetime),* $(,)? $($Param:ident),*>)? {
+            $($field:ident: $FieldTy:ty),+ $(,)?
+        }
+    ) => {
+        unsafe impl<$($($Lifetime,)*)? __Owner $($(, $Param: $crate::Rebase<__Owner>)*)?>
+            $crate::Rebase<__Owner> for $Name $(<$($Lifetime,)* $($Param),*>)?
+        where
+            $($FieldTy: $crate::Rebase<__Owner>,)+
+        {
+            fn rebase(&self, rebaser: &$crate::Rebaser<__Owner>) -> Self {
+                $Name {
+                    $($field: $crate::Rebase::rebase(&self.$field, rebaser),)+
+                }
+            }
+        }
+    };
+    (
+        $Name:ident $(<$($Lifetime:lifetime),* $(,)? $($Param:ident),*>)? (
+            $($index:tt: $FieldTy:ty),+ $(,)?
+        )
+    ) => {
+        unsafe impl<$($($Lifetime,)*)? __Owner $($(, $Param: $crate::Rebase<__Owner>)*)?>
+            $crate::Rebase<__Owner> for $Name $(<$($Lifetime,)* $($Param),*>)?
+        where
+            $($FieldTy: $crate::Rebase<__Owner>,)+
+        {
+            fn rebase(&self, rebaser: &$crate::Rebaser<__Owner>) -> Self {
+                $Name($($crate::Rebase::rebase(&self.$index, rebaser),)+)
+            }
+        }
+    };
+}
+
+pub use unsafe_self_cell::{MutBorrow, Rebase, RebaseOwner, RebaseRef, Rebaser, StableDeref};
 
-pub use unsafe_self_cell::MutBorrow;
+#[cfg(feature = "alloc")]
+pub use unsafe_self_cell::{AllocError, BorrowedError, ErrorCell, TryNewError};
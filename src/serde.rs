@@ -0,0 +1,68 @@
+//! Zero-copy deserialization into a value that borrows from an owned buffer.
+//!
+//! Requires the `serde` feature, [`Borrowed::from_json`] also the `serde_json`
+//! feature.
+
+use ::serde::Deserialize;
+
+/// Names the type of a value that borrows from the input it was deserialized
+/// from, for the input lifetime `'de`.
+///
+/// Implement it on a marker type, once for all lifetimes:
+///
+/// ```
+/// struct WordsType;
+///
+/// impl<'de> self_cell::serde::BorrowedType<'de> for WordsType {
+///     type Value = Vec<&'de str>;
+/// }
+///
+/// type Words = self_cell::serde::Borrowed<String, WordsType>;
+/// ```
+pub trait BorrowedType<'de> {
+    type Value: Deserialize<'de>;
+}
+
+/// Implemented for all types that implement [`BorrowedType`] for every
+/// lifetime.
+pub trait AnyBorrowedType: for<'de> BorrowedType<'de> {}
+
+impl<T: ?Sized + for<'de> BorrowedType<'de>> AnyBorrowedType for T {}
+
+#[doc(hidden)]
+pub type Value<'de, T> = <T as BorrowedType<'de>>::Value;
+
+crate::self_cell!(
+    /// Owner `O`, eg. a `Vec<u8>` buffer, together with the value of
+    /// [`BorrowedType`] `T` deserialized from it, which may borrow from the
+    /// buffer, eg. with `#[serde(borrow)]`.
+    ///
+    /// Access the value with `with_dependent`.
+    pub struct Borrowed<O, T: AnyBorrowedType> {
+        owner: O,
+
+        #[not_covariant]
+        dependent: Value<'_, T>,
+    }
+);
+
+impl<O: 'static, T: AnyBorrowedType + 'static> Borrowed<O, T> {
+    /// Deserializes the value from `owner` with the given function, which
+    /// usually creates a deserializer for the owner and calls
+    /// `Deserialize::deserialize`. Its error is returned as is, if any.
+    pub fn from_deserializer<E>(
+        owner: O,
+        deserialize: impl for<'de> FnOnce(&'de O) -> Result<Value<'de, T>, E>,
+    ) -> Result<Self, E> {
+        Self::try_new(owner, deserialize)
+    }
+
+    /// Deserializes the value from the JSON in `owner`.
+    #[cfg(feature = "serde_json")]
+    pub fn from_json(owner: O) -> Result<Self, serde_json::Error>
+    where
+        O: AsRef<[u8]>,
+    {
+        Self::try_new(owner, |owner| serde_json::from_slice(owner.as_ref()))
+    }
+}
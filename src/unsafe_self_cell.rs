@@ -1,14 +1,35 @@
 #![allow(clippy::missing_safety_doc, clippy::needless_lifetimes)]
 
-use core::cell::UnsafeCell;
-use core::marker::PhantomData;
-use core::mem;
-use core::ptr::{drop_in_place, read, NonNull};
+use core::cell::{Cell, UnsafeCell};
+use core::marker::{PhantomData, PhantomPinned};
+use core::mem::{self, ManuallyDrop, MaybeUninit};
+use core::ops::Deref;
+use core::ptr::{drop_in_place, read};
+#[cfg(feature = "alloc")]
+use core::sync::atomic::{fence, AtomicUsize};
 use core::sync::atomic::{AtomicBool, Ordering};
 
+#[cfg(feature = "alloc")]
 extern crate alloc;
 
-use alloc::alloc::{dealloc, Layout};
+#[cfg(feature = "alloc")]
+use alloc::alloc::{alloc, dealloc};
+#[cfg(feature = "alloc")]
+use alloc::boxed::Box;
+#[cfg(feature = "alloc")]
+use alloc::rc::Rc;
+#[cfg(feature = "alloc")]
+use alloc::string::String;
+#[cfg(feature = "alloc")]
+use alloc::sync::Arc;
+#[cfg(feature = "alloc")]
+use alloc::vec::Vec;
+#[cfg(feature = "alloc")]
+use core::alloc::Layout;
+#[cfg(feature = "alloc")]
+use core::fmt;
+#[cfg(feature = "alloc")]
+use core::ptr::NonNull;
 
 // Self referential structs are currently not supported with safe vanilla Rust.
 // The only reasonable safe alternative is to expect the user to juggle 2 separate
@@ -17,7 +38,7 @@ use alloc::alloc::{dealloc, Layout};
 // These are some of the core invariants we require for this to be safe to use.
 //
 // 1. owner is initialized when UnsafeSelfCell is constructed.
-// 2. owner is NEVER changed again.
+// 2. owner is NEVER changed again, while a dependent exists.
 // 3. The pointer to owner and dependent never changes, even when moved.
 // 4. The only access to owner and dependent is as immutable reference.
 // 5. owner lives longer than dependent.
@@ -28,12 +49,127 @@ pub struct JoinedCell<Owner, Dependent> {
     pub dependent: Dependent,
 }
 
+// Abstraction over the allocator used to allocate and free the JoinedCell.
+// The same allocator value has to be used for both, which is why it is stored
+// inside the UnsafeSelfCell.
+#[cfg(feature = "alloc")]
+#[doc(hidden)]
+pub unsafe trait CellAllocator {
+    // layout must not be zero sized.
+    unsafe fn allocate(&self, layout: Layout) -> Option<NonNull<u8>>;
+
+    // ptr must have been returned by allocate of this allocator with the same layout.
+    unsafe fn deallocate(&self, ptr: NonNull<u8>, layout: Layout);
+}
+
+// Zero sized stand-in for the global allocator. Used by all cells that don't
+// declare their own allocator, so that they remain pointer sized.
+#[cfg(feature = "alloc")]
+#[doc(hidden)]
+pub struct DefaultAllocator;
+
+#[cfg(feature = "alloc")]
+unsafe impl CellAllocator for DefaultAllocator {
+    unsafe fn allocate(&self, layout: Layout) -> Option<NonNull<u8>> {
+        NonNull::new(alloc(layout))
+    }
+
+    unsafe fn deallocate(&self, ptr: NonNull<u8>, layout: Layout) {
+        dealloc(ptr.as_ptr(), layout)
+    }
+}
+
+// Zero sized JoinedCell layouts, e.g. a unit owner with a PhantomData
+// dependent, can't be passed to the allocator. Instead they get a dangling but
+// well aligned pointer, which is valid for zero sized reads and writes.
+#[cfg(feature = "alloc")]
+#[doc(hidden)]
+pub unsafe fn allocate_joined<JoinedCell, Alloc: CellAllocator>(
+    allocator: &Alloc,
+    layout: Layout,
+) -> Option<NonNull<u8>> {
+    if layout.size() == 0 {
+        Some(NonNull::<JoinedCell>::dangling().cast())
+    } else {
+        allocator.allocate(layout)
+    }
+}
+
+// Aborts the process, for states that must neither continue nor unwind.
+#[cfg(feature = "alloc")]
+#[cold]
+fn abort(msg: &'static str) -> ! {
+    #[cfg(feature = "std")]
+    {
+        let _ = msg;
+        std::process::abort()
+    }
+
+    // Without std a panic while panicking aborts.
+    #[cfg(not(feature = "std"))]
+    {
+        struct PanicOnDrop(&'static str);
+
+        impl Drop for PanicOnDrop {
+            fn drop(&mut self) {
+                panic!("{}", self.0);
+            }
+        }
+
+        let _guard = PanicOnDrop(msg);
+        panic!("{}", msg);
+    }
+}
+
+// Aborts the process if dropped, mem::forget it once it's no longer needed.
+#[cfg(feature = "alloc")]
+struct AbortOnDrop(&'static str);
+
+#[cfg(feature = "alloc")]
+impl Drop for AbortOnDrop {
+    fn drop(&mut self) {
+        abort(self.0);
+    }
+}
+
+// ptr must have been returned by allocate_joined with the same allocator and
+// layout.
+#[cfg(feature = "alloc")]
+#[doc(hidden)]
+pub unsafe fn deallocate_joined<Alloc: CellAllocator>(
+    allocator: &Alloc,
+    ptr: NonNull<u8>,
+    layout: Layout,
+) {
+    if layout.size() != 0 {
+        allocator.deallocate(ptr, layout)
+    }
+}
+
+#[cfg(feature = "allocator_api2")]
+unsafe impl<A: allocator_api2::alloc::Allocator> CellAllocator for A {
+    unsafe fn allocate(&self, layout: Layout) -> Option<NonNull<u8>> {
+        allocator_api2::alloc::Allocator::allocate(self, layout)
+            .ok()
+            .map(|ptr| ptr.cast::<u8>())
+    }
+
+    unsafe fn deallocate(&self, ptr: NonNull<u8>, layout: Layout) {
+        allocator_api2::alloc::Allocator::deallocate(self, ptr, layout)
+    }
+}
+
 // Library controlled struct that marks all accesses as unsafe.
 // Because the macro generated struct impl can be extended, could be unsafe.
+#[cfg(feature = "alloc")]
 #[doc(hidden)]
-pub struct UnsafeSelfCell<ContainedIn, Owner, DependentStatic: 'static> {
+pub struct UnsafeSelfCell<ContainedIn, Owner, DependentStatic: 'static, Alloc = DefaultAllocator> {
     joined_void_ptr: NonNull<u8>,
 
+    // The allocator that allocated joined_void_ptr, it is needed again to
+    // deallocate it.
+    allocator: Alloc,
+
     // ContainedIn is necessary for type safety since we don't fully
     // prohibit access to the UnsafeSelfCell; swapping between different
     // structs can be unsafe otherwise, see Issue #17.
@@ -44,10 +180,14 @@ pub struct UnsafeSelfCell<ContainedIn, Owner, DependentStatic: 'static> {
     dependent_marker: PhantomData<DependentStatic>,
 }
 
-impl<ContainedIn, Owner, DependentStatic> UnsafeSelfCell<ContainedIn, Owner, DependentStatic> {
-    pub unsafe fn new(joined_void_ptr: NonNull<u8>) -> Self {
+#[cfg(feature = "alloc")]
+impl<ContainedIn, Owner, DependentStatic, Alloc: CellAllocator>
+    UnsafeSelfCell<ContainedIn, Owner, DependentStatic, Alloc>
+{
+    pub unsafe fn new(joined_void_ptr: NonNull<u8>, allocator: Alloc) -> Self {
         Self {
             joined_void_ptr,
+            allocator,
             contained_in_marker: PhantomData,
             owner_marker: PhantomData,
             dependent_marker: PhantomData,
@@ -85,7 +225,7 @@ impl<ContainedIn, Owner, DependentStatic> UnsafeSelfCell<ContainedIn, Owner, Dep
         let joined_ptr = self.joined_void_ptr.cast::<JoinedCell<Owner, Dependent>>();
 
         // Also used in case drop_in_place(...dependent) fails
-        let _guard = OwnerAndCellDropGuard { joined_ptr };
+        let _guard = OwnerAndCellDropGuard::new(joined_ptr, &self.allocator);
 
         // IMPORTANT dependent must be dropped before owner.
         // We don't want to rely on an implicit order of struct fields.
@@ -97,33 +237,578 @@ impl<ContainedIn, Owner, DependentStatic> UnsafeSelfCell<ContainedIn, Owner, Dep
         // due to _guard at end of scope.
     }
 
+    // Drops dependent and calls `rebuild` with pointers to owner and the now
+    // uninitialized dependent, which `rebuild` has to initialize. Should
+    // dropping dependent or `rebuild` panic the process is aborted, as the
+    // cell is borrowed and can't be left without a dependent.
+    pub unsafe fn rebuild_dependent<Dependent, Ret>(
+        &mut self,
+        rebuild: impl FnOnce(*mut Owner, *mut Dependent) -> Ret,
+    ) -> Ret {
+        let joined_ptr = self.joined_void_ptr.cast::<JoinedCell<Owner, Dependent>>();
+        let (owner_ptr, dependent_ptr) = JoinedCell::_field_pointers(joined_ptr.as_ptr());
+
+        let abort_guard = AbortOnDrop("self_cell: panic while rebuilding the dependent in place");
+
+        drop_in_place(dependent_ptr);
+        let ret = rebuild(owner_ptr, dependent_ptr);
+
+        mem::forget(abort_guard);
+
+        ret
+    }
+
+    // Drops dependent, but keeps owner and the allocation alive, so that a new
+    // dependent can be built in place. Should dropping dependent panic, owner
+    // is dropped and the allocation freed.
+    pub unsafe fn into_parts_without_dependent<Dependent>(self) -> (NonNull<u8>, Alloc) {
+        let joined_ptr = self.joined_void_ptr.cast::<JoinedCell<Owner, Dependent>>();
+
+        // In case drop_in_place(...dependent) fails
+        let drop_guard = OwnerAndCellDropGuard::new(joined_ptr, &self.allocator);
+
+        drop_in_place(&mut (*joined_ptr.as_ptr()).dependent);
+
+        mem::forget(drop_guard);
+
+        (self.joined_void_ptr, self.allocator)
+    }
+
     pub unsafe fn into_owner<Dependent>(self) -> Owner {
         let joined_ptr = self.joined_void_ptr.cast::<JoinedCell<Owner, Dependent>>();
 
         // In case drop_in_place(...dependent) fails
-        let drop_guard = OwnerAndCellDropGuard::new(joined_ptr);
+        let drop_guard = OwnerAndCellDropGuard::new(joined_ptr, &self.allocator);
 
         // Drop dependent
         drop_in_place(&mut (*joined_ptr.as_ptr()).dependent);
 
-        mem::forget(drop_guard);
+        mem::forget(drop_guard);
+
+        let owner_ptr: *const Owner = &(*joined_ptr.as_ptr()).owner;
+
+        // Move owner out so it can be returned.
+        // Must not read before dropping dependent!! (Which happened above.)
+        let owner = read(owner_ptr);
+
+        // Deallocate JoinedCell
+        let layout = Layout::new::<JoinedCell<Owner, Dependent>>();
+        deallocate_joined(&self.allocator, self.joined_void_ptr, layout);
+
+        owner
+    }
+}
+
+// Cells with multiple chained dependents never create references to the
+// whole DependentChain, only to individual dependents via field_pointers.
+#[cfg(feature = "alloc")]
+impl<ContainedIn, Owner, DependentStatic: DependentList, Alloc: CellAllocator>
+    UnsafeSelfCell<ContainedIn, Owner, DependentStatic, Alloc>
+{
+    pub unsafe fn field_pointers(&self) -> (*mut Owner, *mut DependentStatic) {
+        let joined_ptr = self
+            .joined_void_ptr
+            .cast::<JoinedCell<Owner, DependentStatic>>();
+
+        JoinedCell::_field_pointers(joined_ptr.as_ptr())
+    }
+
+    // Any subsequent use of this struct other than dropping it is UB.
+    pub unsafe fn drop_joined_list(&mut self) {
+        let joined_ptr = self
+            .joined_void_ptr
+            .cast::<JoinedCell<Owner, DependentStatic>>();
+
+        // Also used in case dropping a dependent fails
+        let _guard = OwnerAndCellDropGuard::new(joined_ptr, &self.allocator);
+
+        let (_, dependents_ptr) = JoinedCell::_field_pointers(joined_ptr.as_ptr());
+        DependentStatic::drop_list(dependents_ptr);
+    }
+
+    pub unsafe fn into_owner_list(self) -> Owner {
+        let joined_ptr = self
+            .joined_void_ptr
+            .cast::<JoinedCell<Owner, DependentStatic>>();
+
+        // In case dropping a dependent fails
+        let drop_guard = OwnerAndCellDropGuard::new(joined_ptr, &self.allocator);
+
+        let (owner_ptr, dependents_ptr) = JoinedCell::_field_pointers(joined_ptr.as_ptr());
+        DependentStatic::drop_list(dependents_ptr);
+
+        mem::forget(drop_guard);
+
+        // Must not read before dropping the dependents!! (Which happened above.)
+        let owner = read(owner_ptr);
+
+        let layout = Layout::new::<JoinedCell<Owner, DependentStatic>>();
+        deallocate_joined(&self.allocator, self.joined_void_ptr, layout);
+
+        owner
+    }
+}
+
+#[cfg(feature = "alloc")]
+unsafe impl<ContainedIn, Owner, DependentStatic, Alloc> Send
+    for UnsafeSelfCell<ContainedIn, Owner, DependentStatic, Alloc>
+where
+    // Only derive Send if Owner, DependentStatic and Alloc is also Send
+    Owner: Send,
+    DependentStatic: Send,
+    Alloc: Send,
+{
+}
+
+#[cfg(feature = "alloc")]
+unsafe impl<ContainedIn, Owner, DependentStatic, Alloc> Sync
+    for UnsafeSelfCell<ContainedIn, Owner, DependentStatic, Alloc>
+where
+    // Only derive Sync if Owner, DependentStatic and Alloc is also Sync
+    Owner: Sync,
+    DependentStatic: Sync,
+    Alloc: Sync,
+{
+}
+
+// This struct is used to safely deallocate only the owner if dependent
+// construction fails.
+//
+// mem::forget it once it's no longer needed or dtor will be UB.
+#[cfg(feature = "alloc")]
+#[doc(hidden)]
+pub struct OwnerAndCellDropGuard<'a, Owner, Dependent, Alloc: CellAllocator> {
+    joined_ptr: NonNull<JoinedCell<Owner, Dependent>>,
+    allocator: &'a Alloc,
+}
+
+#[cfg(feature = "alloc")]
+impl<'a, Owner, Dependent, Alloc: CellAllocator>
+    OwnerAndCellDropGuard<'a, Owner, Dependent, Alloc>
+{
+    pub unsafe fn new(
+        joined_ptr: NonNull<JoinedCell<Owner, Dependent>>,
+        allocator: &'a Alloc,
+    ) -> Self {
+        Self {
+            joined_ptr,
+            allocator,
+        }
+    }
+}
+
+#[cfg(feature = "alloc")]
+impl<'a, Owner, Dependent, Alloc: CellAllocator> Drop
+    for OwnerAndCellDropGuard<'a, Owner, Dependent, Alloc>
+{
+    fn drop(&mut self) {
+        struct DeallocGuard<'a, Alloc: CellAllocator> {
+            ptr: NonNull<u8>,
+            layout: Layout,
+            allocator: &'a Alloc,
+        }
+        impl<'a, Alloc: CellAllocator> Drop for DeallocGuard<'a, Alloc> {
+            fn drop(&mut self) {
+                unsafe { deallocate_joined(self.allocator, self.ptr, self.layout) }
+            }
+        }
+
+        // Deallocate even when the drop_in_place(...owner) panics
+        let _guard = DeallocGuard {
+            ptr: self.joined_ptr.cast::<u8>(),
+            layout: Layout::new::<JoinedCell<Owner, Dependent>>(),
+            allocator: self.allocator,
+        };
+
+        unsafe {
+            // We must only drop owner and the struct itself,
+            // The whole point of this drop guard is to clean up the partially
+            // initialized struct should building the dependent fail.
+            drop_in_place(&mut (*self.joined_ptr.as_ptr()).owner);
+        }
+
+        // Deallocation happens at end of scope
+    }
+}
+
+// Owner of `#[shared]` cells, the reference count lives next to the actual
+// owner, so that handles and JoinedCell share a single heap allocation. The
+// dependent_builder only ever sees Owner via Deref.
+#[cfg(feature = "alloc")]
+#[doc(hidden)]
+pub struct SharedOwner<Owner> {
+    ref_count: AtomicUsize,
+    owner: Owner,
+}
+
+#[cfg(feature = "alloc")]
+impl<Owner> SharedOwner<Owner> {
+    pub fn new(owner: Owner) -> Self {
+        Self {
+            ref_count: AtomicUsize::new(1),
+            owner,
+        }
+    }
+
+    pub fn into_inner(self) -> Owner {
+        self.owner
+    }
+}
+
+#[cfg(feature = "alloc")]
+impl<Owner> Deref for SharedOwner<Owner> {
+    type Target = Owner;
+
+    fn deref(&self) -> &Owner {
+        &self.owner
+    }
+}
+
+// Same limit as Arc, protects against overflowing the count with mem::forget.
+// Equals isize::MAX, spelled out to support old rustc versions.
+#[cfg(feature = "alloc")]
+const MAX_REF_COUNT: usize = !0 >> 1;
+
+// Library controlled handle for `#[shared]` cells. Every handle points to the
+// same JoinedCell, which is only dropped once the last handle is dropped.
+#[cfg(feature = "alloc")]
+#[doc(hidden)]
+pub struct SharedSelfCell<ContainedIn, Owner, DependentStatic: 'static> {
+    unsafe_self_cell: UnsafeSelfCell<ContainedIn, SharedOwner<Owner>, DependentStatic>,
+}
+
+#[cfg(feature = "alloc")]
+impl<ContainedIn, Owner, DependentStatic>
+    From<UnsafeSelfCell<ContainedIn, SharedOwner<Owner>, DependentStatic>>
+    for SharedSelfCell<ContainedIn, Owner, DependentStatic>
+{
+    fn from(
+        unsafe_self_cell: UnsafeSelfCell<ContainedIn, SharedOwner<Owner>, DependentStatic>,
+    ) -> Self {
+        Self { unsafe_self_cell }
+    }
+}
+
+#[cfg(feature = "alloc")]
+impl<ContainedIn, Owner, DependentStatic> SharedSelfCell<ContainedIn, Owner, DependentStatic> {
+    // Calling any of these *unsafe* functions with the wrong Dependent type is UB.
+
+    pub unsafe fn borrow_owner<'a, Dependent>(&'a self) -> &'a Owner {
+        &self.unsafe_self_cell.borrow_owner::<Dependent>().owner
+    }
+
+    pub unsafe fn borrow_dependent<'a, Dependent>(&'a self) -> &'a Dependent {
+        self.unsafe_self_cell.borrow_dependent()
+    }
+
+    pub unsafe fn clone_shared<Dependent>(&self) -> Self {
+        let shared_owner = self.unsafe_self_cell.borrow_owner::<Dependent>();
+
+        // Relaxed is enough, see Arc::clone. A new handle can only be created
+        // from an existing one, which already keeps the JoinedCell alive.
+        let old_count = shared_owner.ref_count.fetch_add(1, Ordering::Relaxed);
+
+        // Other threads may already see the overflowed count, so like Arc
+        // this must not continue or unwind.
+        if old_count > MAX_REF_COUNT {
+            abort("self_cell: shared cell reference count overflow");
+        }
+
+        Self {
+            unsafe_self_cell: UnsafeSelfCell::new(
+                self.unsafe_self_cell.joined_void_ptr,
+                DefaultAllocator,
+            ),
+        }
+    }
+
+    // Only true if this is the only handle, then no other handle can be
+    // created concurrently.
+    pub unsafe fn is_unique<Dependent>(&self) -> bool {
+        let shared_owner = self.unsafe_self_cell.borrow_owner::<Dependent>();
+
+        // Acquire synchronizes with the Release decrement of other handles
+        // that were dropped, see Arc::get_mut.
+        shared_owner.ref_count.load(Ordering::Acquire) == 1
+    }
+
+    // Any subsequent use of this struct other than dropping it is UB.
+    pub unsafe fn drop_shared<Dependent>(&mut self) {
+        let shared_owner = self.unsafe_self_cell.borrow_owner::<Dependent>();
+
+        // See Arc::drop, Release makes all uses of this handle happen before
+        // the last handle drops owner and dependent.
+        if shared_owner.ref_count.fetch_sub(1, Ordering::Release) != 1 {
+            return;
+        }
+
+        fence(Ordering::Acquire);
+
+        self.unsafe_self_cell.drop_joined::<Dependent>();
+    }
+
+    // Must only be called if is_unique returned true.
+    pub unsafe fn into_owner<Dependent>(self) -> Owner {
+        self.unsafe_self_cell.into_owner::<Dependent>().into_inner()
+    }
+}
+
+// In contrast to UnsafeSelfCell, handles can be cloned and sent to other
+// threads, so just like Arc both Send and Sync require Owner and
+// DependentStatic to be Send and Sync.
+#[cfg(feature = "alloc")]
+unsafe impl<ContainedIn, Owner, DependentStatic> Send
+    for SharedSelfCell<ContainedIn, Owner, DependentStatic>
+where
+    Owner: Send + Sync,
+    DependentStatic: Send + Sync,
+{
+}
+
+#[cfg(feature = "alloc")]
+unsafe impl<ContainedIn, Owner, DependentStatic> Sync
+    for SharedSelfCell<ContainedIn, Owner, DependentStatic>
+where
+    Owner: Send + Sync,
+    DependentStatic: Send + Sync,
+{
+}
+
+// Library controlled storage for `#[stable_deref]` cells. Owner and dependent
+// live inline, dependent borrows from the Deref target of owner, which doesn't
+// move when owner is moved, see StableDeref. Owner is never mutated while
+// dependent exists.
+#[doc(hidden)]
+pub struct StableDerefSelfCell<ContainedIn, Owner, DependentStatic: 'static> {
+    owner: ManuallyDrop<Owner>,
+
+    dependent: ManuallyDrop<DependentStatic>,
+
+    // See UnsafeSelfCell.
+    contained_in_marker: PhantomData<ContainedIn>,
+}
+
+impl<ContainedIn, Owner: StableDeref, DependentStatic>
+    StableDerefSelfCell<ContainedIn, Owner, DependentStatic>
+{
+    // dependent must have been built from the Deref target of owner and
+    // Dependent must be DependentStatic with a different lifetime.
+    pub unsafe fn new<Dependent>(owner: Owner, dependent: Dependent) -> Self {
+        // Dependent and DependentStatic only differ in their lifetime, which
+        // transmute can't express for generic types.
+        let dependent_static = read(&dependent as *const Dependent as *const DependentStatic);
+        mem::forget(dependent);
+
+        Self {
+            owner: ManuallyDrop::new(owner),
+            dependent: ManuallyDrop::new(dependent_static),
+            contained_in_marker: PhantomData,
+        }
+    }
+
+    // Calling any of these *unsafe* functions with the wrong Dependent type is UB.
+
+    pub unsafe fn borrow_owner<'a, Dependent>(&'a self) -> &'a Owner {
+        &self.owner
+    }
+
+    pub unsafe fn borrow_dependent<'a, Dependent>(&'a self) -> &'a Dependent {
+        &*(&*self.dependent as *const DependentStatic as *const Dependent)
+    }
+
+    pub unsafe fn borrow_mut<'a, Dependent>(&'a mut self) -> (&'a Owner, &'a mut Dependent) {
+        (
+            &self.owner,
+            &mut *(&mut *self.dependent as *mut DependentStatic as *mut Dependent),
+        )
+    }
+
+    // Any subsequent use of this struct other than dropping it is UB.
+    pub unsafe fn drop_joined<Dependent>(&mut self) {
+        // Also used in case drop_in_place(...dependent) fails
+        let _guard = DropInPlaceGuard::new(&mut *self.owner as *mut Owner);
+
+        // IMPORTANT dependent must be dropped before owner.
+        drop_in_place(&mut *self.dependent as *mut DependentStatic as *mut Dependent);
+
+        // Dropping owner due to _guard at end of scope.
+    }
+
+    pub unsafe fn into_owner<Dependent>(mut self) -> Owner {
+        // In case drop_in_place(...dependent) fails
+        let drop_guard = DropInPlaceGuard::new(&mut *self.owner as *mut Owner);
+
+        drop_in_place(&mut *self.dependent as *mut DependentStatic as *mut Dependent);
+
+        mem::forget(drop_guard);
+
+        // Must not read before dropping dependent!! (Which happened above.)
+        ManuallyDrop::into_inner(read(&self.owner))
+    }
+}
+
+/// Marker for owner types whose [`Deref`] target stays at the same address,
+/// even if the owner itself is moved.
+///
+/// Required for the owner of `#[stable_deref]` cells, see
+/// [`self_cell!`](crate::self_cell!). The dependent borrows from the target, so
+/// owner can be stored inline instead of being moved into a separate heap
+/// allocation.
+///
+/// `Box<T>` is deliberately not implemented. Moving a `Box` asserts unique
+/// access to its content, which would invalidate the references held by the
+/// dependent.
+///
+/// # Safety
+///
+/// Implementors must guarantee that `deref` always returns a reference to the
+/// same address for as long as the value is alive and not mutated, and that
+/// moving the value neither moves the target nor invalidates references to it.
+///
+/// For foreign types, e.g. `memmap2::Mmap` or `bytes::Bytes`, implement it on
+/// a new-type wrapper.
+pub unsafe trait StableDeref: Deref {}
+
+#[cfg(feature = "alloc")]
+unsafe impl StableDeref for String {}
+
+#[cfg(feature = "alloc")]
+unsafe impl<T> StableDeref for Vec<T> {}
+
+#[cfg(feature = "alloc")]
+unsafe impl<T: ?Sized> StableDeref for Rc<T> {}
+
+#[cfg(feature = "alloc")]
+unsafe impl<T: ?Sized> StableDeref for Arc<T> {}
+
+unsafe impl<'a, T: ?Sized> StableDeref for &'a T {}
+
+// Library controlled storage for `#[pinned]` cells. Owner and dependent live
+// inline instead of in a heap allocation, so invariant 3. only holds as long
+// as the containing struct is pinned. Initialization is only possible through
+// Pin<&mut Self> and PhantomPinned makes sure the containing struct is !Unpin,
+// so once initialized it can't be moved anymore. The macro additionally
+// implements Unpin for the containing struct with a bound on `PinnedGuard`
+// that never holds, so that a user implementation of Unpin conflicts with it.
+//
+// All functions take &self, no unique reference to the storage is ever
+// created, which would invalidate the references in dependent pointing into
+// owner. Functions that mutate must only be called with exclusive access.
+// Unpin only if T is Unpin. The lifetime keeps the bound on the Unpin impl of
+// `#[pinned]` cells from being trivially false, which would be an error.
+#[doc(hidden)]
+pub struct PinnedGuard<'a, T>(PhantomData<&'a ()>, T);
+
+#[doc(hidden)]
+pub struct PinnedSelfCell<ContainedIn, Owner, DependentStatic: 'static> {
+    joined: UnsafeCell<MaybeUninit<JoinedCell<Owner, DependentStatic>>>,
+
+    initialized: Cell<bool>,
+
+    // See UnsafeSelfCell.
+    contained_in_marker: PhantomData<ContainedIn>,
+
+    owner_marker: PhantomData<Owner>,
+    // DependentStatic is only used to correctly derive Send and Sync.
+    dependent_marker: PhantomData<DependentStatic>,
+
+    _pinned_marker: PhantomPinned,
+}
+
+impl<ContainedIn, Owner, DependentStatic> PinnedSelfCell<ContainedIn, Owner, DependentStatic> {
+    pub fn uninit() -> Self {
+        Self {
+            joined: UnsafeCell::new(MaybeUninit::uninit()),
+            initialized: Cell::new(false),
+            contained_in_marker: PhantomData,
+            owner_marker: PhantomData,
+            dependent_marker: PhantomData,
+            _pinned_marker: PhantomPinned,
+        }
+    }
+
+    pub fn is_initialized(&self) -> bool {
+        self.initialized.get()
+    }
+
+    // Calling any of these *unsafe* functions with the wrong Dependent type is UB.
+
+    // Pointer to the possibly uninitialized storage, panics if already
+    // initialized. Call set_initialized once owner and dependent are written.
+    pub unsafe fn uninit_joined_void_ptr(&self) -> *mut u8 {
+        assert!(
+            !self.initialized.get(),
+            "self_cell: pinned cell is already initialized"
+        );
+
+        self.joined.get() as *mut u8
+    }
+
+    pub unsafe fn set_initialized(&self) {
+        self.initialized.set(true);
+    }
+
+    unsafe fn joined_ptr<Dependent>(&self) -> *mut JoinedCell<Owner, Dependent> {
+        assert!(
+            self.initialized.get(),
+            "self_cell: pinned cell is not initialized"
+        );
+
+        self.joined.get() as *mut JoinedCell<Owner, Dependent>
+    }
+
+    pub unsafe fn borrow_owner<'a, Dependent>(&'a self) -> &'a Owner {
+        &(*self.joined_ptr::<Dependent>()).owner
+    }
+
+    pub unsafe fn borrow_dependent<'a, Dependent>(&'a self) -> &'a Dependent {
+        &(*self.joined_ptr::<Dependent>()).dependent
+    }
+
+    #[allow(clippy::mut_from_ref)]
+    pub unsafe fn borrow_mut<'a, Dependent>(&'a self) -> (&'a Owner, &'a mut Dependent) {
+        let joined_ptr = self.joined_ptr::<Dependent>();
+
+        // See UnsafeSelfCell::borrow_mut, never claim mutable access to owner.
+        (&(*joined_ptr).owner, &mut (*joined_ptr).dependent)
+    }
+
+    // Returns to the uninitialized state, even if dropping dependent panics.
+    pub unsafe fn take_owner<Dependent>(&self) -> Owner {
+        let joined_ptr = self.joined_ptr::<Dependent>();
+        self.initialized.set(false);
+
+        // In case drop_in_place(...dependent) fails
+        let drop_guard = DropInPlaceGuard::new(&mut (*joined_ptr).owner);
+
+        drop_in_place(&mut (*joined_ptr).dependent);
+
+        mem::forget(drop_guard);
+
+        // Must not read before dropping dependent!! (Which happened above.)
+        read(&(*joined_ptr).owner)
+    }
+
+    pub unsafe fn drop_joined<Dependent>(&self) {
+        if !self.initialized.get() {
+            return;
+        }
 
-        let owner_ptr: *const Owner = &(*joined_ptr.as_ptr()).owner;
+        let joined_ptr = self.joined_ptr::<Dependent>();
+        self.initialized.set(false);
 
-        // Move owner out so it can be returned.
-        // Must not read before dropping dependent!! (Which happened above.)
-        let owner = read(owner_ptr);
+        // Also used in case drop_in_place(...dependent) fails
+        let _guard = DropInPlaceGuard::new(&mut (*joined_ptr).owner);
 
-        // Deallocate JoinedCell
-        let layout = Layout::new::<JoinedCell<Owner, Dependent>>();
-        dealloc(self.joined_void_ptr.as_ptr(), layout);
+        // IMPORTANT dependent must be dropped before owner.
+        drop_in_place(&mut (*joined_ptr).dependent);
 
-        owner
+        // Dropping owner due to _guard at end of scope.
     }
 }
 
 unsafe impl<ContainedIn, Owner, DependentStatic> Send
-    for UnsafeSelfCell<ContainedIn, Owner, DependentStatic>
+    for PinnedSelfCell<ContainedIn, Owner, DependentStatic>
 where
     // Only derive Send if Owner and DependentStatic is also Send
     Owner: Send,
@@ -132,7 +817,7 @@ where
 }
 
 unsafe impl<ContainedIn, Owner, DependentStatic> Sync
-    for UnsafeSelfCell<ContainedIn, Owner, DependentStatic>
+    for PinnedSelfCell<ContainedIn, Owner, DependentStatic>
 where
     // Only derive Sync if Owner and DependentStatic is also Sync
     Owner: Sync,
@@ -140,47 +825,66 @@ where
 {
 }
 
-// This struct is used to safely deallocate only the owner if dependent
-// construction fails.
+// Drops a single value in place, used for owners of cells that have no
+// allocation to free and for the already built dependents of a chain.
 //
 // mem::forget it once it's no longer needed or dtor will be UB.
 #[doc(hidden)]
-pub struct OwnerAndCellDropGuard<Owner, Dependent> {
-    joined_ptr: NonNull<JoinedCell<Owner, Dependent>>,
+pub struct DropInPlaceGuard<T> {
+    ptr: *mut T,
 }
 
-impl<Owner, Dependent> OwnerAndCellDropGuard<Owner, Dependent> {
-    pub unsafe fn new(joined_ptr: NonNull<JoinedCell<Owner, Dependent>>) -> Self {
-        Self { joined_ptr }
+impl<T> DropInPlaceGuard<T> {
+    pub unsafe fn new(ptr: *mut T) -> Self {
+        Self { ptr }
     }
 }
 
-impl<Owner, Dependent> Drop for OwnerAndCellDropGuard<Owner, Dependent> {
+impl<T> Drop for DropInPlaceGuard<T> {
     fn drop(&mut self) {
-        struct DeallocGuard {
-            ptr: *mut u8,
-            layout: Layout,
-        }
-        impl Drop for DeallocGuard {
-            fn drop(&mut self) {
-                unsafe { dealloc(self.ptr, self.layout) }
-            }
+        unsafe {
+            drop_in_place(self.ptr);
         }
+    }
+}
 
-        // Deallocate even when the drop_in_place(...owner) panics
-        let _guard = DeallocGuard {
-            ptr: self.joined_ptr.as_ptr() as *mut u8,
-            layout: Layout::new::<JoinedCell<Owner, Dependent>>(),
-        };
+// Names the dependent type of a cell declared with `for<'a> Type`. The macro
+// implements it on the cell struct itself, so that the dependent type can be
+// named for any lifetime, which macro_rules can't substitute into a `ty`.
+#[doc(hidden)]
+pub trait DependentType<'a> {
+    type Dependent;
+}
 
-        unsafe {
-            // We must only drop owner and the struct itself,
-            // The whole point of this drop guard is to clean up the partially
-            // initialized struct should building the dependent fail.
-            drop_in_place(&mut (*self.joined_ptr.as_ptr()).owner);
-        }
+// Storage for multiple chained dependents, as a cons list terminated by `()`.
+// Later dependents may borrow earlier ones, so they must be dropped first.
+// Rather than relying on the implicit field drop order, and to avoid creating
+// a &mut to the whole chain while later dependents still hold shared
+// references into it, chains are only ever dropped via DependentList.
+#[doc(hidden)]
+pub struct DependentChain<First, Rest> {
+    pub first: First,
+    pub rest: Rest,
+}
 
-        // Deallocation happens at end of scope
+#[doc(hidden)]
+pub unsafe trait DependentList {
+    // this must point to a fully initialized list.
+    unsafe fn drop_list(this: *mut Self);
+}
+
+unsafe impl DependentList for () {
+    unsafe fn drop_list(_this: *mut Self) {}
+}
+
+unsafe impl<First, Rest: DependentList> DependentList for DependentChain<First, Rest> {
+    unsafe fn drop_list(this: *mut Self) {
+        let (first_ptr, rest_ptr) = Self::_field_pointers(this);
+
+        // Also used in case dropping a later dependent fails.
+        let _guard = DropInPlaceGuard::new(first_ptr);
+
+        Rest::drop_list(rest_ptr);
     }
 }
 
@@ -226,6 +930,39 @@ impl<Owner, Dependent> JoinedCell<Owner, Dependent> {
     }
 }
 
+// See JoinedCell::_field_pointers.
+impl<First, Rest> DependentChain<First, Rest> {
+    #[doc(hidden)]
+    #[cfg(not(feature = "old_rust"))]
+    pub unsafe fn _field_pointers(this: *mut Self) -> (*mut First, *mut Rest) {
+        let first_ptr = core::ptr::addr_of_mut!((*this).first);
+        let rest_ptr = core::ptr::addr_of_mut!((*this).rest);
+
+        (first_ptr, rest_ptr)
+    }
+
+    #[doc(hidden)]
+    #[cfg(feature = "old_rust")]
+    #[rustversion::since(1.51)]
+    pub unsafe fn _field_pointers(this: *mut Self) -> (*mut First, *mut Rest) {
+        let first_ptr = core::ptr::addr_of_mut!((*this).first);
+        let rest_ptr = core::ptr::addr_of_mut!((*this).rest);
+
+        (first_ptr, rest_ptr)
+    }
+
+    #[doc(hidden)]
+    #[cfg(feature = "old_rust")]
+    #[rustversion::before(1.51)]
+    pub unsafe fn _field_pointers(this: *mut Self) -> (*mut First, *mut Rest) {
+        // See comment above, technically this is UB.
+        let first_ptr = &mut (*this).first as *mut First;
+        let rest_ptr = &mut (*this).rest as *mut Rest;
+
+        (first_ptr, rest_ptr)
+    }
+}
+
 /// Wrapper type that allows creating a self-referential type that hold a mutable borrow `&mut T`.
 ///
 /// Example usage:
@@ -315,3 +1052,541 @@ impl<T> From<T> for MutBorrow<T> {
 // reference through `borrow_mut()` no other interaction with the inner value MUST ever be possible
 // while the outer `MutBorrow` is alive.
 unsafe impl<T: Send> Sync for MutBorrow<T> {}
+
+/// The error returned by the `try_new_alloc` family of constructors, if the heap
+/// allocation for the cell failed.
+#[cfg(feature = "alloc")]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct AllocError;
+
+#[cfg(feature = "alloc")]
+impl fmt::Display for AllocError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("memory allocation failed")
+    }
+}
+
+/// The error returned by `try_new_or_recover_alloc`, it tells apart a failed
+/// heap allocation from a failed dependent builder.
+#[cfg(feature = "alloc")]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum TryNewError<Err> {
+    /// The heap allocation failed, the dependent builder was not called.
+    Alloc(AllocError),
+
+    /// The dependent builder returned an error.
+    Builder(Err),
+}
+
+#[cfg(feature = "alloc")]
+impl<Err: fmt::Display> fmt::Display for TryNewError<Err> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            TryNewError::Alloc(err) => err.fmt(f),
+            TryNewError::Builder(err) => err.fmt(f),
+        }
+    }
+}
+
+/// Names the error type of `try_new_borrowed_err`, for the lifetime `'a` of
+/// the borrowed owner.
+///
+/// Implement it on a marker type, once for all lifetimes:
+///
+/// ```
+/// struct ParseError<'a> {
+///     token: &'a str,
+/// }
+///
+/// struct ParseErrorType;
+///
+/// impl<'a> self_cell::BorrowedError<'a> for ParseErrorType {
+///     type Error = ParseError<'a>;
+/// }
+/// ```
+#[cfg(feature = "alloc")]
+pub trait BorrowedError<'a> {
+    type Error;
+}
+
+/// The error returned by `try_new_borrowed_err`. Holds the owner, still in the
+/// heap allocation made for the cell, together with the error of the dependent
+/// builder, which may borrow from the owner.
+#[cfg(feature = "alloc")]
+pub struct ErrorCell<Owner, E: for<'a> BorrowedError<'a>, Alloc: CellAllocator = DefaultAllocator> {
+    // The allocation of the cell that failed to build, its dependent is
+    // uninitialized.
+    joined_void_ptr: NonNull<u8>,
+    layout: Layout,
+    allocator: Alloc,
+
+    owner_ptr: NonNull<Owner>,
+
+    // Borrows from owner, the 'static lifetime is a lie and never handed out.
+    error: ManuallyDrop<<E as BorrowedError<'static>>::Error>,
+
+    owner_marker: PhantomData<Owner>,
+}
+
+#[cfg(feature = "alloc")]
+impl<Owner, E: for<'a> BorrowedError<'a>, Alloc: CellAllocator> ErrorCell<Owner, E, Alloc> {
+    // owner_ptr must point to the initialized owner inside the allocation
+    // joined_void_ptr of layout made with allocator, and error must only
+    // borrow from owner.
+    #[doc(hidden)]
+    pub unsafe fn new<'a>(
+        joined_void_ptr: NonNull<u8>,
+        layout: Layout,
+        allocator: Alloc,
+        owner_ptr: *mut Owner,
+        error: <E as BorrowedError<'a>>::Error,
+    ) -> Self {
+        let error = ManuallyDrop::new(error);
+
+        Self {
+            joined_void_ptr,
+            layout,
+            allocator,
+            owner_ptr: NonNull::new_unchecked(owner_ptr),
+            error: ManuallyDrop::new(read(
+                &*error as *const <E as BorrowedError<'a>>::Error
+                    as *const <E as BorrowedError<'static>>::Error,
+            )),
+            owner_marker: PhantomData,
+        }
+    }
+
+    /// Borrows owner.
+    pub fn borrow_owner<'a>(&'a self) -> &'a Owner {
+        unsafe { self.owner_ptr.as_ref() }
+    }
+
+    /// Calls given closure `func` with a shared reference to owner and error.
+    pub fn with_error<'outer_fn, Ret>(
+        &'outer_fn self,
+        func: impl for<'_q> FnOnce(&'_q Owner, &'outer_fn <E as BorrowedError<'_q>>::Error) -> Ret,
+    ) -> Ret {
+        unsafe {
+            func(
+                self.owner_ptr.as_ref(),
+                &*(&*self.error as *const <E as BorrowedError<'static>>::Error
+                    as *const <E as BorrowedError<'outer_fn>>::Error),
+            )
+        }
+    }
+
+    /// Drops the error and returns owner.
+    pub fn into_owner(self) -> Owner {
+        let mut this = ManuallyDrop::new(self);
+        let this = &mut *this;
+
+        unsafe {
+            // Drops and deallocates owner should dropping error panic.
+            let drop_guard = OwnerDropGuard {
+                owner_ptr: this.owner_ptr,
+                joined_void_ptr: this.joined_void_ptr,
+                layout: this.layout,
+                allocator: &this.allocator,
+            };
+
+            ManuallyDrop::drop(&mut this.error);
+
+            mem::forget(drop_guard);
+
+            let owner = read(this.owner_ptr.as_ptr());
+            let allocator = read(&this.allocator);
+            deallocate_joined(&allocator, this.joined_void_ptr, this.layout);
+
+            owner
+        }
+    }
+}
+
+#[cfg(feature = "alloc")]
+impl<Owner, E: for<'a> BorrowedError<'a>, Alloc: CellAllocator> Drop
+    for ErrorCell<Owner, E, Alloc>
+{
+    fn drop(&mut self) {
+        // Drops owner and deallocates at end of scope, also if dropping error
+        // panics.
+        let _guard = OwnerDropGuard {
+            owner_ptr: self.owner_ptr,
+            joined_void_ptr: self.joined_void_ptr,
+            layout: self.layout,
+            allocator: &self.allocator,
+        };
+
+        unsafe { ManuallyDrop::drop(&mut self.error) };
+    }
+}
+
+#[cfg(feature = "alloc")]
+struct OwnerDropGuard<'a, Owner, Alloc: CellAllocator> {
+    owner_ptr: NonNull<Owner>,
+    joined_void_ptr: NonNull<u8>,
+    layout: Layout,
+    allocator: &'a Alloc,
+}
+
+#[cfg(feature = "alloc")]
+impl<'a, Owner, Alloc: CellAllocator> Drop for OwnerDropGuard<'a, Owner, Alloc> {
+    fn drop(&mut self) {
+        struct DeallocGuard<'a, Alloc: CellAllocator> {
+            ptr: NonNull<u8>,
+            layout: Layout,
+            allocator: &'a Alloc,
+        }
+        impl<'a, Alloc: CellAllocator> Drop for DeallocGuard<'a, Alloc> {
+            fn drop(&mut self) {
+                unsafe { deallocate_joined(self.allocator, self.ptr, self.layout) }
+            }
+        }
+
+        // Deallocate even when the drop_in_place(...owner) panics
+        let _guard = DeallocGuard {
+            ptr: self.joined_void_ptr,
+            layout: self.layout,
+            allocator: self.allocator,
+        };
+
+        unsafe { drop_in_place(self.owner_ptr.as_ptr()) };
+    }
+}
+
+#[cfg(feature = "alloc")]
+impl<Owner, E, Alloc> fmt::Debug for ErrorCell<Owner, E, Alloc>
+where
+    Owner: fmt::Debug,
+    E: for<'a> BorrowedError<'a>,
+    for<'a> <E as BorrowedError<'a>>::Error: fmt::Debug,
+    Alloc: CellAllocator,
+{
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        self.with_error(|owner, error| {
+            f.debug_struct("ErrorCell")
+                .field("owner", owner)
+                .field("error", error)
+                .finish()
+        })
+    }
+}
+
+#[cfg(feature = "alloc")]
+unsafe impl<Owner, E, Alloc> Send for ErrorCell<Owner, E, Alloc>
+where
+    // Only derive Send if Owner, the error and Alloc is also Send
+    Owner: Send,
+    E: for<'a> BorrowedError<'a>,
+    <E as BorrowedError<'static>>::Error: Send,
+    Alloc: CellAllocator + Send,
+{
+}
+
+#[cfg(feature = "alloc")]
+unsafe impl<Owner, E, Alloc> Sync for ErrorCell<Owner, E, Alloc>
+where
+    // Only derive Sync if Owner, the error and Alloc is also Sync
+    Owner: Sync,
+    E: for<'a> BorrowedError<'a>,
+    <E as BorrowedError<'static>>::Error: Sync,
+    Alloc: CellAllocator + Sync,
+{
+}
+
+/// Rewrites the references of a dependent from the owner of one cell to the
+/// clone of that owner in another cell, see [`Rebase`].
+///
+/// Only the macro generated `Clone` implementation creates a `Rebaser`.
+pub struct Rebaser<Owner> {
+    // (old start address, new start pointer, length in bytes) of the owner
+    // itself and of the memory owned by it.
+    regions: [(usize, *const u8, usize); 2],
+    owner_marker: PhantomData<fn(&Owner)>,
+}
+
+impl<Owner: RebaseOwner> Rebaser<Owner> {
+    #[doc(hidden)]
+    pub unsafe fn new(old_owner: &Owner, new_owner: &Owner) -> Self {
+        let (old_owned, old_owned_len) = old_owner.owned_region();
+        let (new_owned, new_owned_len) = new_owner.owned_region();
+        assert_eq!(old_owned_len, new_owned_len);
+
+        Self {
+            regions: [
+                (
+                    old_owner as *const Owner as usize,
+                    new_owner as *const Owner as *const u8,
+                    mem::size_of::<Owner>(),
+                ),
+                (old_owned as usize, new_owned, old_owned_len),
+            ],
+            owner_marker: PhantomData,
+        }
+    }
+
+    fn rebase_ptr(&self, ptr: *const u8, size: usize) -> *const u8 {
+        let addr = ptr as usize;
+
+        for &(old_start, new_start, len) in self.regions.iter() {
+            if addr >= old_start && size <= len && addr - old_start <= len - size {
+                // The offset is kept, RebaseRef ensures that this is still
+                // aligned in the new owner.
+                return new_start.wrapping_add(addr - old_start);
+            }
+        }
+
+        // Not borrowed from owner, so it outlives owner and stays valid.
+        ptr
+    }
+
+    // The returned references have the lifetime of `value`, not of the new
+    // owner, so these are only used by the `Rebase` implementations below.
+
+    fn rebase_ref<'a, T>(&self, value: &'a T) -> &'a T
+    where
+        Owner: RebaseRef<T>,
+    {
+        let ptr = self.rebase_ptr(value as *const T as *const u8, mem::size_of::<T>());
+        unsafe { &*(ptr as *const T) }
+    }
+
+    fn rebase_slice<'a, T>(&self, value: &'a [T]) -> &'a [T]
+    where
+        Owner: RebaseRef<T>,
+    {
+        let ptr = self.rebase_ptr(value.as_ptr() as *const u8, mem::size_of_val(value));
+        unsafe { core::slice::from_raw_parts(ptr as *const T, value.len()) }
+    }
+}
+
+/// Types that can be copied from one owner to a clone of that owner, by
+/// rewriting the references into the old owner to the same offsets in the new
+/// owner.
+///
+/// Used by `impl {Clone(rebase)}`, which clones a cell without calling the
+/// dependent builder again. Implemented for `Vec`, `Option`, tuples,
+/// `PhantomData`, types without references and references to types without
+/// references, as far as `Owner` implements [`RebaseRef`] for them. Use
+/// [`impl_rebase!`](crate::impl_rebase) to implement it for your own structs.
+///
+/// # Safety
+///
+/// `rebase` must return a value whose references are all the result of
+/// `rebase` of other `Rebase` types, or don't point into the owner.
+pub unsafe trait Rebase<Owner> {
+    /// Returns a copy of `self` with all references into the old owner
+    /// rewritten to the new owner.
+    fn rebase(&self, rebaser: &Rebaser<Owner>) -> Self;
+}
+
+// `T: 'static` as the referenced value itself is not rebased, so it must not
+// borrow from the owner.
+unsafe impl<'a, T: 'static, Owner: RebaseOwner + RebaseRef<T>> Rebase<Owner> for &'a T {
+    fn rebase(&self, rebaser: &Rebaser<Owner>) -> Self {
+        rebaser.rebase_ref(*self)
+    }
+}
+
+unsafe impl<'a, T: 'static, Owner: RebaseOwner + RebaseRef<T>> Rebase<Owner> for &'a [T] {
+    fn rebase(&self, rebaser: &Rebaser<Owner>) -> Self {
+        rebaser.rebase_slice(self)
+    }
+}
+
+unsafe impl<'a, Owner: RebaseOwner + RebaseRef<u8>> Rebase<Owner> for &'a str {
+    fn rebase(&self, rebaser: &Rebaser<Owner>) -> Self {
+        unsafe { core::str::from_utf8_unchecked(rebaser.rebase_slice(self.as_bytes())) }
+    }
+}
+
+unsafe impl<Owner, T: Rebase<Owner>> Rebase<Owner> for Option<T> {
+    fn rebase(&self, rebaser: &Rebaser<Owner>) -> Self {
+        self.as_ref().map(|value| value.rebase(rebaser))
+    }
+}
+
+#[cfg(feature = "alloc")]
+unsafe impl<Owner, T: Rebase<Owner>> Rebase<Owner> for Vec<T> {
+    fn rebase(&self, rebaser: &Rebaser<Owner>) -> Self {
+        self.iter().map(|value| value.rebase(rebaser)).collect()
+    }
+}
+
+// Holds no references, eg. the marker of a struct that only borrows through
+// raw pointers wouldn't be rebased by this, but raw pointers don't implement
+// Rebase.
+unsafe impl<Owner, T: ?Sized> Rebase<Owner> for PhantomData<T> {
+    fn rebase(&self, _rebaser: &Rebaser<Owner>) -> Self {
+        PhantomData
+    }
+}
+
+macro_rules! impl_rebase_tuple {
+    ($($T:ident $idx:tt),+) => {
+        unsafe impl<Owner, $($T: Rebase<Owner>),+> Rebase<Owner> for ($($T,)+) {
+            fn rebase(&self, rebaser: &Rebaser<Owner>) -> Self {
+                ($(self.$idx.rebase(rebaser),)+)
+            }
+        }
+    };
+}
+
+impl_rebase_tuple!(A 0);
+impl_rebase_tuple!(A 0, B 1);
+impl_rebase_tuple!(A 0, B 1, C 2);
+impl_rebase_tuple!(A 0, B 1, C 2, D 3);
+impl_rebase_tuple!(A 0, B 1, C 2, D 3, E 4);
+impl_rebase_tuple!(A 0, B 1, C 2, D 3, E 4, F 5);
+
+macro_rules! impl_rebase_clone {
+    ($($T:ty),+) => {
+        $(
+            unsafe impl<Owner> Rebase<Owner> for $T {
+                fn rebase(&self, _rebaser: &Rebaser<Owner>) -> Self {
+                    self.clone()
+                }
+            }
+        )+
+    };
+}
+
+impl_rebase_clone!((), bool, char, u8, u16, u32, u64, u128, usize);
+impl_rebase_clone!(i8, i16, i32, i64, i128, isize, f32, f64);
+
+#[cfg(feature = "alloc")]
+impl_rebase_clone!(String);
+
+/// Owners that `impl {Clone(rebase)}` can clone.
+///
+/// # Safety
+///
+/// Cloning must copy the owned memory, so that everything at an offset in
+/// `owned_region` of the original is equal at the same offset of the clone.
+/// Except for the owner itself and `owned_region`, all memory a shared
+/// reference to the owner gives access to must stay valid for as long as the
+/// clone is alive.
+pub unsafe trait RebaseOwner: Clone {
+    /// Start and length in bytes of the memory owned by `self` outside of
+    /// itself.
+    fn owned_region(&self) -> (*const u8, usize);
+}
+
+/// Owners whose clones keep references to `T` and `[T]` aligned, when they are
+/// moved to the same offset in the clone.
+///
+/// A dependent can only be rebased, if the owner implements this for every
+/// type it holds references to. This rejects eg. a `&u32` borrowed from a
+/// `Vec<u8>` at compile time, the buffer of the cloned `Vec<u8>` may not be
+/// aligned for it.
+///
+/// # Safety
+///
+/// `align_of::<T>()` must not be larger than the alignment of the start of
+/// the owner itself and of `owned_region`, or no reference to `T` can point
+/// into them.
+pub unsafe trait RebaseRef<T>: RebaseOwner {}
+
+#[cfg(feature = "alloc")]
+unsafe impl RebaseOwner for String {
+    fn owned_region(&self) -> (*const u8, usize) {
+        (self.as_ptr(), self.len())
+    }
+}
+
+#[cfg(feature = "alloc")]
+unsafe impl RebaseRef<u8> for String {}
+
+#[cfg(feature = "alloc")]
+unsafe impl RebaseRef<String> for String {}
+
+#[cfg(feature = "alloc")]
+unsafe impl<T: Copy> RebaseOwner for Vec<T> {
+    fn owned_region(&self) -> (*const u8, usize) {
+        (
+            self.as_ptr() as *const u8,
+            mem::size_of_val(self.as_slice()),
+        )
+    }
+}
+
+#[cfg(feature = "alloc")]
+unsafe impl<T: Copy> RebaseRef<T> for Vec<T> {}
+
+#[cfg(feature = "alloc")]
+unsafe impl<T: Copy> RebaseRef<Vec<T>> for Vec<T> {}
+
+#[cfg(feature = "alloc")]
+unsafe impl RebaseOwner for Box<str> {
+    fn owned_region(&self) -> (*const u8, usize) {
+        (self.as_ptr(), self.len())
+    }
+}
+
+#[cfg(feature = "alloc")]
+unsafe impl RebaseRef<u8> for Box<str> {}
+
+#[cfg(feature = "alloc")]
+unsafe impl RebaseRef<Box<str>> for Box<str> {}
+
+#[cfg(feature = "alloc")]
+unsafe impl<T: Copy> RebaseOwner for Box<[T]> {
+    fn owned_region(&self) -> (*const u8, usize) {
+        (self.as_ptr() as *const u8, mem::size_of_val(&**self))
+    }
+}
+
+#[cfg(feature = "alloc")]
+unsafe impl<T: Copy> RebaseRef<T> for Box<[T]> {}
+
+#[cfg(feature = "alloc")]
+unsafe impl<T: Copy> RebaseRef<Box<[T]>> for Box<[T]> {}
+
+// Clones share the target, it outlives both owners. The only memory that is
+// rebased is the pointer itself, which no other reference can point into.
+#[cfg(feature = "alloc")]
+unsafe impl<T: ?Sized> RebaseOwner for Rc<T> {
+    fn owned_region(&self) -> (*const u8, usize) {
+        (core::ptr::null(), 0)
+    }
+}
+
+#[cfg(feature = "alloc")]
+unsafe impl<T: ?Sized, U> RebaseRef<U> for Rc<T> {}
+
+#[cfg(feature = "alloc")]
+unsafe impl<T: ?Sized> RebaseOwner for Arc<T> {
+    fn owned_region(&self) -> (*const u8, usize) {
+        (core::ptr::null(), 0)
+    }
+}
+
+#[cfg(feature = "alloc")]
+unsafe impl<T: ?Sized, U> RebaseRef<U> for Arc<T> {}
+
+unsafe impl<'a, T: ?Sized> RebaseOwner for &'a T {
+    fn owned_region(&self) -> (*const u8, usize) {
+        (core::ptr::null(), 0)
+    }
+}
+
+unsafe impl<'a, T: ?Sized, U> RebaseRef<U> for &'a T {}
+
+/// Returns `dependent` rebased from `old_owner` to `new_owner`, with the lifetime
+/// of `new_owner`.
+///
+/// # Safety
+///
+/// `Old` and `New` have to be the same dependent type, only differing in
+/// lifetime. `dependent` has to borrow from `old_owner`, which `new_owner` has
+/// to be a clone of.
+#[doc(hidden)]
+pub unsafe fn rebase_dependent<Owner: RebaseOwner, Old: Rebase<Owner>, New>(
+    old_owner: &Owner,
+    new_owner: &Owner,
+    dependent: &Old,
+) -> New {
+    assert_eq!(mem::size_of::<Old>(), mem::size_of::<New>());
+
+    let rebased = ManuallyDrop::new(dependent.rebase(&Rebaser::new(old_owner, new_owner)));
+    read(&*rebased as *const Old as *const New)
+}
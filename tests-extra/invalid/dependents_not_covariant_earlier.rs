@@ -0,0 +1,42 @@
+use std::cell::Cell;
+
+use self_cell::self_cell;
+
+struct First<'a>(Cell<Option<&'a Second<'a>>>);
+
+impl<'a> Drop for First<'a> {
+    fn drop(&mut self) {
+        // Second is dropped before First.
+        if let Some(second) = self.0.get() {
+            println!("{}", second.0);
+        }
+    }
+}
+
+struct Second<'a>(String, &'a First<'a>);
+
+self_cell! {
+    struct Foo {
+        owner: (),
+
+        dependents: {
+            #[not_covariant]
+            first: First,
+
+            #[not_covariant]
+            second: Second,
+        }
+    }
+}
+
+fn main() {
+    let x = Foo::new(
+        (),
+        |_| First(Cell::new(None)),
+        |_, first| Second("Hello".to_owned(), first),
+    );
+
+    x.with_dependents(|_, first, second| {
+        first.0.set(Some(second));
+    });
+}
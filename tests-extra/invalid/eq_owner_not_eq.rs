@@ -0,0 +1,16 @@
+use self_cell::self_cell;
+
+type Ratio<'a> = &'a f64;
+
+self_cell! {
+    struct RatioCell {
+        owner: f64,
+
+        #[covariant]
+        dependent: Ratio,
+    }
+
+    impl {PartialEq, Eq}
+}
+
+fn main() {}
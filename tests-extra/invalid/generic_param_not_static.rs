@@ -0,0 +1,18 @@
+use self_cell::self_cell;
+
+type Dependent<'a> = &'a str;
+
+self_cell! {
+    struct WordCell<T: AsRef<str>> {
+        owner: T,
+
+        #[covariant]
+        dependent: Dependent,
+    }
+}
+
+fn main() {
+    let owner = String::from("fox");
+
+    let _cell = WordCell::new(&owner, |owner| owner.as_str());
+}
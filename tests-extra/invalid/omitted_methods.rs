@@ -0,0 +1,42 @@
+use self_cell::self_cell;
+
+type Dependent<'a> = &'a str;
+
+mod cells {
+    use super::Dependent;
+    use self_cell::self_cell;
+
+    self_cell! {
+        pub struct ImmutableCell {
+            owner: String,
+
+            #[covariant]
+            dependent: Dependent,
+        }
+
+        methods {
+            borrow_owner: pub(self),
+            with_dependent_mut: none,
+        }
+    }
+}
+
+self_cell! {
+    struct TypoCell {
+        owner: String,
+
+        #[covariant]
+        dependent: Dependent,
+    }
+
+    methods {
+        into_ownr: none,
+    }
+}
+
+fn main() {
+    let mut cell = cells::ImmutableCell::new("fox".into(), |owner| owner.as_str());
+
+    let _owner = cell.borrow_owner();
+    cell.with_dependent_mut(|_, dependent| *dependent = "static");
+}
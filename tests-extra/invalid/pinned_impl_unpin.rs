@@ -0,0 +1,26 @@
+use std::pin::Pin;
+
+use self_cell::self_cell;
+
+type Ref<'a> = &'a str;
+
+self_cell! {
+    struct PinnedCell {
+        owner: String,
+
+        #[covariant, pinned]
+        dependent: Ref,
+    }
+}
+
+impl Unpin for PinnedCell {}
+
+fn main() {
+    let mut cell = PinnedCell::uninit();
+    Pin::new(&mut cell).init("abc".into(), |owner| owner);
+
+    let mut other = PinnedCell::uninit();
+    std::mem::swap(&mut cell, &mut other);
+    drop(cell);
+    println!("{}", other.borrow_dependent());
+}
@@ -0,0 +1,21 @@
+use self_cell::self_cell;
+
+type Word<'a> = &'a u32;
+
+self_cell! {
+    struct WordCell {
+        owner: Vec<u8>,
+
+        #[covariant]
+        dependent: Word,
+    }
+
+    impl {Clone(rebase)}
+}
+
+fn main() {
+    static WORD: u32 = 7;
+
+    let cell = WordCell::new(vec![0; 8], |_| &WORD);
+    let _cloned = cell.clone();
+}
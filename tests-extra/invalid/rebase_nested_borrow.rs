@@ -0,0 +1,25 @@
+use self_cell::self_cell;
+
+struct Foo<'a> {
+    s: &'a str,
+}
+
+type Dep<'a> = &'a Foo<'a>;
+
+self_cell! {
+    struct FooCell {
+        owner: String,
+
+        #[covariant]
+        dependent: Dep,
+    }
+
+    impl {Clone(rebase)}
+}
+
+fn main() {
+    let cell = FooCell::new("hello".into(), |o| &*Box::leak(Box::new(Foo { s: &o[..] })));
+    let cloned = cell.clone();
+    drop(cell);
+    println!("{}", cloned.borrow_dependent().s);
+}
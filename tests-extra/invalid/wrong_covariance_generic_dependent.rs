@@ -0,0 +1,16 @@
+use std::cell::Cell;
+
+use self_cell::self_cell;
+
+struct Slot<'a, T>(Cell<&'a str>, T);
+
+self_cell! {
+    struct SlotCell<T> {
+        owner: String,
+
+        #[covariant]
+        dependent: Slot<'_, T>,
+    }
+}
+
+fn main() {}
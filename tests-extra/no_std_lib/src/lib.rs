@@ -1,52 +1,40 @@
 #![no_std]
 
-use self_cell::self_cell;
+// Not using alloc is on purpose, pinned cells store owner and dependent inline
+// and should work in such scenarios.
 
-// Not using alloc is on purpose, self_cell should also work in such scenarios.
-
-const SCRATCH_REGION: [u8; 4096] = [0u8; 4096];
-
-#[derive(Eq, PartialEq)]
-struct StaticString {
-    region: &'static [u8],
-}
-
-const MAX_NODES: usize = 8;
+#[test]
+fn self_cell_works_in_no_std_env() {
+    use self_cell::self_cell;
 
-#[derive(Eq, PartialEq)]
-struct Ast<'a>([Option<&'a [u8]>; MAX_NODES]);
+    const MAX_NODES: usize = 8;
 
-self_cell!(
-    struct AstCell {
-        owner: StaticString,
+    #[derive(Eq, PartialEq)]
+    struct Ast<'a>([Option<&'a [u8]>; MAX_NODES]);
 
-        #[covariant]
-        dependent: Ast,
-    }
+    self_cell!(
+        struct AstCell {
+            owner: [u8; 15],
 
-    impl {Eq, PartialEq}
-);
+            #[covariant, pinned]
+            dependent: Ast,
+        }
+    );
 
-fn build_ast_cell() -> AstCell {
-    // Yes in a static setting self_cell is not terribly useful, this could be solved differently.
-    // This is only a test.
-    let pre_processed_code = StaticString {
-        region: &SCRATCH_REGION[4000..4015],
-    };
+    let mut ast_cell = AstCell::uninit();
+    // SAFETY: the cell is never moved again, shadowing makes the unpinned
+    // value inaccessible. This is what core::pin::pin! does on rustc 1.68+.
+    let mut ast_cell = unsafe { core::pin::Pin::new_unchecked(&mut ast_cell) };
 
-    AstCell::new(pre_processed_code, |code| {
+    ast_cell.as_mut().init(*b"fox = cat + dog", |code| {
         let mut ast_nodes = [None; MAX_NODES];
-        ast_nodes[0] = Some(&code.region[3..7]);
-        ast_nodes[1] = Some(&code.region[10..12]);
+        ast_nodes[0] = Some(&code[0..3]);
+        ast_nodes[1] = Some(&code[6..9]);
 
         Ast(ast_nodes)
-    })
-}
+    });
 
-#[test]
-fn self_cell_works_in_no_std_env() {
-    let ast_cell = build_ast_cell();
-    assert_eq!(ast_cell.borrow_owner().region.len(), 15);
+    assert_eq!(ast_cell.borrow_owner().len(), 15);
     assert_eq!(
         ast_cell
             .borrow_dependent()
@@ -56,4 +44,7 @@ fn self_cell_works_in_no_std_env() {
             .count(),
         2
     );
+    assert!(ast_cell.borrow_dependent().0[1] == Some(&b"cat"[..]));
+
+    assert!(ast_cell.as_mut().take_owner() == *b"fox = cat + dog");
 }
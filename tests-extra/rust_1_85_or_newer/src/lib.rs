@@ -102,3 +102,222 @@ fn async_self_cell_with_mut_borrow() {
     });
     assert_eq!(**cell.borrow_dependent(), OWNER_STR[..OWNER_STR.len() - 1]);
 }
+
+#[test]
+fn pinned_on_stack() {
+    self_cell!(
+        struct PinnedCell {
+            owner: String,
+
+            #[covariant, pinned]
+            dependent: Dependent,
+        }
+    );
+
+    let mut cell = core::pin::pin!(PinnedCell::uninit());
+    cell.as_mut().init(OWNER_STR.into(), |owner| &owner[33..]);
+    assert_eq!(cell.borrow_dependent(), &"more chars");
+
+    cell.as_mut().with_dependent_mut(|owner, dependent| {
+        *dependent = &owner[..4];
+    });
+    assert_eq!(cell.borrow_dependent(), &"some");
+
+    assert_eq!(cell.as_mut().take_owner(), OWNER_STR);
+}
+
+mod custom_allocator {
+    use std::cell::Cell;
+    use std::ptr::NonNull;
+
+    use allocator_api2::alloc::{AllocError, Allocator, Global, Layout};
+
+    use self_cell::self_cell;
+
+    use super::OWNER_STR;
+
+    #[derive(Default)]
+    pub struct CountingAllocator {
+        allocs: Cell<usize>,
+        deallocs: Cell<usize>,
+    }
+
+    unsafe impl Allocator for &CountingAllocator {
+        fn allocate(&self, layout: Layout) -> Result<NonNull<[u8]>, AllocError> {
+            self.allocs.set(self.allocs.get() + 1);
+            Global.allocate(layout)
+        }
+
+        unsafe fn deallocate(&self, ptr: NonNull<u8>, layout: Layout) {
+            self.deallocs.set(self.deallocs.get() + 1);
+            unsafe { Global.deallocate(ptr, layout) }
+        }
+    }
+
+    type Dependent<'a> = &'a str;
+
+    self_cell!(
+        struct CountedCell<'a> {
+            owner: String,
+
+            #[covariant]
+            dependent: Dependent,
+
+            allocator: &'a CountingAllocator,
+        }
+    );
+
+    #[test]
+    fn new_in() {
+        let allocator = CountingAllocator::default();
+
+        let cell = CountedCell::new_in(OWNER_STR.into(), &allocator, |owner| &owner[33..]);
+        assert_eq!(allocator.allocs.get(), 1);
+        assert_eq!(cell.borrow_owner(), OWNER_STR);
+        assert_eq!(cell.borrow_dependent(), &"more chars");
+
+        drop(cell);
+        assert_eq!(allocator.deallocs.get(), 1);
+    }
+
+    #[test]
+    fn into_owner_in() {
+        let allocator = CountingAllocator::default();
+
+        let cell = CountedCell::new_in(OWNER_STR.into(), &allocator, |owner| &owner[33..]);
+        assert_eq!(cell.into_owner(), OWNER_STR);
+        assert_eq!(allocator.allocs.get(), 1);
+        assert_eq!(allocator.deallocs.get(), 1);
+    }
+
+    #[test]
+    fn try_new_in() {
+        let allocator = CountingAllocator::default();
+
+        let cell = CountedCell::try_new_in(OWNER_STR.into(), &allocator, |owner| {
+            Ok::<_, ()>(&owner[33..])
+        })
+        .unwrap();
+        assert_eq!(cell.borrow_dependent(), &"more chars");
+        drop(cell);
+
+        let Err(err) = CountedCell::try_new_in(OWNER_STR.into(), &allocator, |_| Err(5)) else {
+            panic!("expected builder error");
+        };
+        assert_eq!(err, 5);
+
+        assert_eq!(allocator.allocs.get(), 2);
+        assert_eq!(allocator.deallocs.get(), 2);
+    }
+
+    #[test]
+    fn try_new_or_recover_in() {
+        let allocator = CountingAllocator::default();
+
+        let Err((owner, err)) =
+            CountedCell::try_new_or_recover_in(OWNER_STR.into(), &allocator, |_| Err(7))
+        else {
+            panic!("expected builder error");
+        };
+        assert_eq!(owner, OWNER_STR);
+        assert_eq!(err, 7);
+
+        assert_eq!(allocator.allocs.get(), 1);
+        assert_eq!(allocator.deallocs.get(), 1);
+    }
+
+    #[test]
+    fn replace_owner_in() {
+        let allocator = CountingAllocator::default();
+
+        let mut cell = CountedCell::new_in("abc".into(), &allocator, |owner| &owner[1..]);
+        let old_owner = cell.replace_owner(OWNER_STR.into(), |owner| &owner[33..]);
+        assert_eq!(old_owner, "abc");
+        assert_eq!(cell.borrow_dependent(), &"more chars");
+        assert_eq!(allocator.allocs.get(), 1);
+
+        let Err(err) = cell.try_replace_owner("abc".into(), |_| Err(3)) else {
+            panic!("expected builder error");
+        };
+        assert_eq!(err, 3);
+
+        assert_eq!(allocator.allocs.get(), 1);
+        assert_eq!(allocator.deallocs.get(), 1);
+    }
+
+    #[test]
+    fn panic_in_builder_in() {
+        let allocator = CountingAllocator::default();
+
+        let result = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+            CountedCell::new_in(OWNER_STR.into(), &allocator, |_| panic!());
+        }));
+        assert!(result.is_err());
+
+        assert_eq!(allocator.allocs.get(), 1);
+        assert_eq!(allocator.deallocs.get(), 1);
+    }
+
+    pub struct FailingAllocator;
+
+    unsafe impl Allocator for FailingAllocator {
+        fn allocate(&self, _layout: Layout) -> Result<NonNull<[u8]>, AllocError> {
+            Err(AllocError)
+        }
+
+        unsafe fn deallocate(&self, _ptr: NonNull<u8>, _layout: Layout) {
+            unreachable!()
+        }
+    }
+
+    self_cell!(
+        struct FailingCell {
+            owner: String,
+
+            #[covariant]
+            dependent: Dependent,
+
+            allocator: FailingAllocator,
+        }
+    );
+
+    #[test]
+    fn try_new_alloc_in_fail() {
+        let Err((owner, err)) =
+            FailingCell::try_new_alloc_in(OWNER_STR.into(), FailingAllocator, |_| unreachable!())
+        else {
+            panic!("expected allocation error");
+        };
+        assert_eq!(owner, OWNER_STR);
+        assert_eq!(err, self_cell::AllocError);
+    }
+
+    #[test]
+    fn try_new_or_recover_alloc_in_fail() {
+        let Err((owner, err)) = FailingCell::try_new_or_recover_alloc_in(
+            OWNER_STR.into(),
+            FailingAllocator,
+            |_| -> Result<_, i32> { unreachable!() },
+        ) else {
+            panic!("expected allocation error");
+        };
+        assert_eq!(owner, OWNER_STR);
+        assert_eq!(err, self_cell::TryNewError::Alloc(self_cell::AllocError));
+    }
+
+    #[test]
+    fn try_new_or_recover_alloc_in_builder_err() {
+        let allocator = CountingAllocator::default();
+
+        let Err((owner, err)) =
+            CountedCell::try_new_or_recover_alloc_in(OWNER_STR.into(), &allocator, |_| Err(7))
+        else {
+            panic!("expected builder error");
+        };
+        assert_eq!(owner, OWNER_STR);
+        assert_eq!(err, self_cell::TryNewError::Builder(7));
+
+        assert_eq!(allocator.allocs.get(), 1);
+        assert_eq!(allocator.deallocs.get(), 1);
+    }
+}
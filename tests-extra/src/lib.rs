@@ -83,6 +83,70 @@ fn mut_borrow_traits() {
     assert!(impls!(MutBorrowStringCell: Sync));
 }
 
+#[test]
+fn pinned_traits() {
+    // Once initialized a pinned cell must never move again, so it must not be
+    // Unpin. Send and Sync follow owner and dependent like the heap based cell.
+
+    self_cell!(
+        struct PinnedCell {
+            owner: String,
+
+            #[covariant, pinned]
+            dependent: Ast,
+        }
+    );
+
+    assert!(!impls!(PinnedCell: Unpin));
+    assert!(impls!(PinnedCell: Send));
+    assert!(impls!(PinnedCell: Sync));
+
+    self_cell!(
+        struct PinnedNotSendCell {
+            owner: String,
+
+            #[covariant, pinned]
+            dependent: NotSend,
+        }
+    );
+
+    assert!(!impls!(PinnedNotSendCell: Send));
+    assert!(!impls!(PinnedNotSendCell: Sync));
+}
+
+#[test]
+fn shared_traits() {
+    // Like Arc, sending a handle to another thread shares owner and dependent
+    // with the remaining handles, so both Send and Sync require Send + Sync.
+
+    type CellRef<'a> = std::cell::Cell<&'a str>;
+
+    self_cell!(
+        struct SharedCell {
+            owner: String,
+
+            #[covariant, shared]
+            dependent: Ast,
+        }
+    );
+
+    self_cell!(
+        struct SharedNotSyncCell {
+            owner: String,
+
+            #[not_covariant, shared]
+            dependent: CellRef,
+        }
+    );
+
+    assert!(impls!(SharedCell: Send));
+    assert!(impls!(SharedCell: Sync));
+
+    assert!(impls!(CellRef<'static>: Send));
+    assert!(!impls!(SharedNotSyncCell: Send));
+    assert!(!impls!(SharedNotSyncCell: Sync));
+}
+
 #[test]
 #[cfg(feature = "invalid_programs")]
 // Not supported by miri isolation.
@@ -114,6 +178,38 @@ self_cell!(
     impl {Debug}
 );
 
+self_cell!(
+    struct SharedAstCell {
+        owner: String,
+
+        #[covariant, shared]
+        dependent: Ast,
+    }
+);
+
+#[test]
+fn shared_clone_across_threads() {
+    let body = String::from("hy hyperspeed");
+    let expected_ast = Ast::from(&body);
+
+    let cell = SharedAstCell::new(body.clone(), |o| o.into());
+
+    thread::scope(|s| {
+        for _ in 0..4 {
+            let cell_clone = cell.clone();
+            let body = &body;
+            let expected_ast = &expected_ast;
+            s.spawn(move |_| {
+                assert_eq!(cell_clone.borrow_owner(), body);
+                assert_eq!(cell_clone.borrow_dependent(), expected_ast);
+            });
+        }
+    })
+    .unwrap();
+
+    assert_eq!(cell.try_into_owner().ok(), Some(body));
+}
+
 #[test]
 fn share_across_threads() {
     // drop_joined takes &mut self, so that's not a thread concern anyway.
@@ -144,3 +240,258 @@ fn share_across_threads() {
     })
     .unwrap();
 }
+
+#[test]
+fn const_generic_params() {
+    #[derive(Debug, PartialEq)]
+    struct Chunks<'a>(Vec<&'a [u8]>);
+
+    self_cell!(
+        struct ChunksCell<const N: usize> {
+            owner: Vec<u8>,
+
+            #[covariant]
+            dependent: Chunks,
+        }
+
+        impl {Debug, PartialEq}
+    );
+
+    let cell = ChunksCell::<2>::new(vec![1, 2, 3, 4, 5], |owner| {
+        Chunks(owner.chunks(2).collect())
+    });
+    assert_eq!(
+        cell.borrow_dependent(),
+        &Chunks(vec![&[1, 2], &[3, 4], &[5]])
+    );
+
+    let other = ChunksCell::<2>::new(vec![1, 2, 3, 4, 5], |owner| {
+        Chunks(owner.chunks(2).collect())
+    });
+    assert_eq!(cell, other);
+    assert_eq!(cell.into_owner(), vec![1, 2, 3, 4, 5]);
+}
+
+#[test]
+fn serde_owner_round_trip() {
+    self_cell!(
+        struct SerdeAstCell {
+            owner: String,
+
+            #[covariant]
+            dependent: Ast,
+        }
+
+        impl {Debug, PartialEq, Serialize, Deserialize}
+    );
+
+    let cell = SerdeAstCell::new(String::from("hy hyperspeed"), |o| o.into());
+
+    let json = serde_json::to_string(&cell).unwrap();
+    assert_eq!(json, "\"hy hyperspeed\"");
+
+    let loaded: SerdeAstCell = serde_json::from_str(&json).unwrap();
+    assert_eq!(loaded, cell);
+    assert_eq!(loaded.borrow_dependent(), &Ast(vec!["h", " hypersp"]));
+}
+
+#[test]
+fn serde_deserialize_builders() {
+    type Words<'a> = Vec<&'a str>;
+    type Number<'a> = &'a str;
+
+    self_cell!(
+        struct WordsCell {
+            owner: String,
+
+            #[covariant]
+            dependent: Words,
+        }
+
+        impl {Deserialize(with = |owner| owner.split(' ').collect())}
+    );
+
+    self_cell!(
+        struct NumberCell {
+            owner: String,
+
+            #[covariant]
+            dependent: Number,
+        }
+
+        impl {Deserialize(try_with = |owner| {
+            if owner.bytes().all(|b| b.is_ascii_digit()) {
+                Ok(owner.as_str())
+            } else {
+                Err(format!("not a number: {}", owner))
+            }
+        })}
+    );
+
+    let words: WordsCell = serde_json::from_str("\"fox cat dog\"").unwrap();
+    assert_eq!(words.borrow_dependent(), &vec!["fox", "cat", "dog"]);
+
+    let number: NumberCell = serde_json::from_str("\"1234\"").unwrap();
+    assert_eq!(*number.borrow_dependent(), "1234");
+
+    let err = serde_json::from_str::<NumberCell>("\"12a4\"")
+        .err()
+        .unwrap();
+    assert_eq!(err.to_string(), "not a number: 12a4");
+}
+
+#[cfg(test)]
+#[derive(serde::Deserialize, Debug, PartialEq)]
+struct Person<'a> {
+    name: &'a str,
+    #[serde(borrow)]
+    tags: Vec<&'a str>,
+}
+
+#[cfg(test)]
+struct PersonType;
+
+#[cfg(test)]
+impl<'de> self_cell::serde::BorrowedType<'de> for PersonType {
+    type Value = Person<'de>;
+}
+
+#[test]
+fn serde_borrowed_from_json() {
+    use self_cell::serde::Borrowed;
+
+    let input = br#"{"name": "Ada", "tags": ["math", "engines"]}"#.to_vec();
+    let input_range = input.as_ptr_range();
+
+    let person = Borrowed::<Vec<u8>, PersonType>::from_json(input).unwrap();
+    person.with_dependent(|owner, person| {
+        assert_eq!(
+            person,
+            &Person {
+                name: "Ada",
+                tags: vec!["math", "engines"],
+            }
+        );
+        assert!(input_range.contains(&person.name.as_ptr()));
+        assert!(input_range.contains(&person.tags[1].as_ptr()));
+        assert_eq!(owner.as_ptr(), input_range.start);
+    });
+
+    let err = Borrowed::<Vec<u8>, PersonType>::from_json(b"{\"name\": 3}".to_vec()).err();
+    assert!(err.is_some());
+}
+
+#[test]
+fn serde_borrowed_from_deserializer() {
+    use self_cell::serde::Borrowed;
+
+    let person = Borrowed::<String, PersonType>::from_deserializer(
+        String::from(r#"{"name": "Grace", "tags": []}"#),
+        |owner| {
+            let mut deserializer = serde_json::Deserializer::from_str(owner);
+            serde::Deserialize::deserialize(&mut deserializer)
+        },
+    )
+    .unwrap();
+
+    person.with_dependent(|_, person| assert_eq!(person.name, "Grace"));
+    assert_eq!(person.into_owner(), r#"{"name": "Grace", "tags": []}"#);
+}
+
+#[cfg(test)]
+#[derive(Debug)]
+struct Diagnostic<'a> {
+    span: &'a str,
+    source: std::num::ParseIntError,
+}
+
+#[cfg(test)]
+impl std::fmt::Display for Diagnostic<'_> {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "invalid number `{}`", self.span)
+    }
+}
+
+#[cfg(test)]
+impl std::error::Error for Diagnostic<'_> {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        Some(&self.source)
+    }
+}
+
+#[cfg(test)]
+self_cell!(
+    struct DiagnosticCell {
+        owner: String,
+
+        #[covariant]
+        dependent: Diagnostic,
+    }
+
+    impl {Debug, Display, Error}
+);
+
+#[cfg(test)]
+fn parse_number(input: String) -> Result<i32, DiagnosticCell> {
+    input.trim().parse().map_err(|source| {
+        DiagnosticCell::new(input, |owner| Diagnostic {
+            span: owner.trim(),
+            source,
+        })
+    })
+}
+
+#[test]
+fn error_forwards_to_dependent() {
+    fn run(input: &str) -> Result<i32, Box<dyn std::error::Error>> {
+        Ok(parse_number(input.to_string())?)
+    }
+
+    assert_eq!(run(" 12 ").unwrap(), 12);
+
+    let err = run(" 1x2 ").unwrap_err();
+    assert_eq!(err.to_string(), "invalid number `1x2`");
+    assert_eq!(
+        err.source().unwrap().to_string(),
+        "invalid digit found in string"
+    );
+}
+
+#[test]
+fn new_catch_unwind_returns_owner() {
+    type Words<'a> = Vec<&'a str>;
+
+    self_cell!(
+        struct WordsCell {
+            owner: Rc<String>,
+
+            #[covariant]
+            dependent: Words,
+        }
+    );
+
+    let input = Rc::new(String::from("fox cat dog"));
+
+    let cell = WordsCell::new_catch_unwind(input.clone(), |owner| owner.split(' ').collect())
+        .ok()
+        .unwrap();
+    assert_eq!(cell.borrow_dependent(), &vec!["fox", "cat", "dog"]);
+    drop(cell);
+
+    let result = WordsCell::new_catch_unwind(input.clone(), |owner| {
+        if owner.contains("cat") {
+            panic!("parser crashed on {}", owner);
+        }
+        owner.split(' ').collect()
+    });
+
+    let (owner, payload) = result.err().unwrap();
+    assert!(Rc::ptr_eq(&owner, &input));
+    assert_eq!(
+        payload.downcast_ref::<String>().unwrap(),
+        "parser crashed on fox cat dog"
+    );
+
+    drop(owner);
+    assert_eq!(Rc::strong_count(&input), 1);
+}
@@ -2,11 +2,13 @@
 
 use std::cell::Cell;
 use std::cell::RefCell;
+use std::collections::HashSet;
 use std::fmt::Debug;
 use std::marker::PhantomData;
 use std::panic::catch_unwind;
 use std::rc::Rc;
 use std::str;
+use std::sync::atomic::{AtomicUsize, Ordering};
 
 use once_cell::unsync::OnceCell;
 
@@ -30,15 +32,9 @@ self_cell!(
         dependent: Ast,
     }
 
-    impl {Debug, PartialEq, Eq, Hash}
+    impl {Clone, Debug, PartialEq, Eq, Hash}
 );
 
-impl Clone for PackedAstCell {
-    fn clone(&self) -> Self {
-        PackedAstCell::new(self.borrow_owner().clone(), |owner| owner.into())
-    }
-}
-
 #[derive(Clone, Debug, Eq, PartialEq, Hash)]
 struct PackedAst {
     ast_cell: PackedAstCell,
@@ -551,6 +547,38 @@ fn try_new_or_recover() {
     assert_eq!(cell.borrow_dependent(), &Ast::from(&original_input));
 }
 
+#[test]
+fn try_new_alloc() {
+    let original_input = String::from("Ein See aus Schweiß ..");
+
+    let cell = PackedAstCell::try_new_alloc(original_input.clone(), |o| o.into()).unwrap();
+    assert_eq!(cell.borrow_owner(), &original_input);
+    assert_eq!(cell.borrow_dependent(), &Ast::from(&original_input));
+}
+
+#[test]
+fn try_new_or_recover_alloc() {
+    let original_input = String::from("Ein See aus Schweiß ..");
+
+    // bad path
+    let (input, err) = PackedAstCell::try_new_or_recover_alloc(original_input.clone(), |_| {
+        std::result::Result::Err(-1)
+    })
+    .unwrap_err();
+
+    assert_eq!(original_input, input);
+    assert_eq!(err, self_cell::TryNewError::Builder(-1));
+
+    // happy path
+    let cell =
+        PackedAstCell::try_new_or_recover_alloc(original_input.clone(), |o| -> Result<_, ()> {
+            std::result::Result::Ok(o.into())
+        })
+        .unwrap();
+    assert_eq!(cell.borrow_owner(), &original_input);
+    assert_eq!(cell.borrow_dependent(), &Ast::from(&original_input));
+}
+
 #[test]
 fn into_owner() {
     // The Rc stuff here is somewhat tangential to what is being tested here.
@@ -596,27 +624,59 @@ fn zero_size_cell() {
         }
     );
 
-    assert!(catch_unwind(|| ZeroSizeCell::new((), |_| ZeroSizeRef(PhantomData))).is_err());
+    let cell = ZeroSizeCell::new((), |_| ZeroSizeRef(PhantomData));
+    assert_eq!(cell.borrow_owner(), &());
+    cell.into_owner();
+
+    let cell = ZeroSizeCell::try_new((), |_| -> Result<_, i32> {
+        std::result::Result::Ok(ZeroSizeRef(PhantomData))
+    });
+    assert!(cell.is_ok());
+
+    let cell = ZeroSizeCell::try_new_or_recover((), |_| -> Result<_, i32> {
+        std::result::Result::Ok(ZeroSizeRef(PhantomData))
+    });
+    assert!(cell.is_ok());
+
+    let res = ZeroSizeCell::try_new_or_recover((), |_| std::result::Result::Err(3));
+    match res {
+        std::result::Result::Ok(_) => unreachable!(),
+        std::result::Result::Err((_owner, err)) => assert_eq!(err, 3),
+    }
+
+    // Zero sized owner with drop glue, the drop must still run exactly once.
+    static DROP_COUNT: AtomicUsize = AtomicUsize::new(0);
 
-    assert!(
-        catch_unwind(|| ZeroSizeCell::try_new((), |_| -> Result<_, i32> {
-            std::result::Result::Ok(ZeroSizeRef(PhantomData))
-        }))
-        .is_err()
+    struct ZeroSizeOwner;
+
+    impl Drop for ZeroSizeOwner {
+        fn drop(&mut self) {
+            DROP_COUNT.fetch_add(1, Ordering::SeqCst);
+        }
+    }
+
+    self_cell!(
+        struct ZeroSizeDropCell {
+            owner: ZeroSizeOwner,
+
+            #[covariant]
+            dependent: ZeroSizeRef,
+        }
     );
 
-    assert!(catch_unwind(
-        || ZeroSizeCell::try_new_or_recover((), |_| -> Result<_, i32> {
-            std::result::Result::Ok(ZeroSizeRef(PhantomData))
-        })
-    )
-    .is_err());
+    let cell = ZeroSizeDropCell::new(ZeroSizeOwner, |_| ZeroSizeRef(PhantomData));
+    drop(cell);
+    assert_eq!(DROP_COUNT.load(Ordering::SeqCst), 1);
+
+    let panic_result = catch_unwind(|| {
+        ZeroSizeDropCell::new(ZeroSizeOwner, |_| -> ZeroSizeRef { panic!() });
+    });
+    assert!(panic_result.is_err());
+    assert_eq!(DROP_COUNT.load(Ordering::SeqCst), 2);
 }
 
 #[test]
 fn nested_cells() {
-    // TODO allow automatic impls.
-    // impl {Debug, PartialEq, Eq, Hash}
     self_cell!(
         struct ChildCell<'a> {
             owner: &'a String,
@@ -624,6 +684,8 @@ fn nested_cells() {
             #[covariant]
             dependent: Ast,
         }
+
+        impl {Debug, PartialEq, Eq, Hash}
     );
 
     self_cell!(
@@ -633,6 +695,8 @@ fn nested_cells() {
             #[covariant]
             dependent: ChildCell,
         }
+
+        impl {Debug}
     );
 
     let parent_owner_expected = String::from("some string it is");
@@ -647,6 +711,145 @@ fn nested_cells() {
     let child_cell = parent_cell.borrow_dependent();
     assert_eq!(*child_cell.borrow_owner(), &parent_owner_expected);
     assert_eq!(child_cell.borrow_dependent(), &ast_expected);
+
+    let other_child_cell = ChildCell::new(&parent_owner_expected, |child| Ast::from(*child));
+    assert_eq!(child_cell, &other_child_cell);
+
+    let mut set = HashSet::new();
+    set.insert(other_child_cell);
+    assert!(set.contains(child_cell));
+
+    assert_eq!(
+        format!("{:?}", parent_cell),
+        "ParentCell { owner: \"some string it is\", dependent: ChildCell { owner: \"some string it is\", dependent: Ast([\"me \", \"om\"]) } }"
+    );
+}
+
+#[test]
+fn multiple_owner_lifetimes() {
+    self_cell!(
+        struct PairCell<'a, 'b> {
+            owner: (&'a str, &'b str),
+
+            #[not_covariant]
+            dependent: Words,
+        }
+
+        impl {Debug, PartialEq, Eq, Hash}
+    );
+
+    let schema = String::from("id name");
+    let cell = {
+        let dict = String::from("key value");
+        let cell = PairCell::new((&schema, &dict), |owner| {
+            Words(owner.0.split(' ').chain(owner.1.split(' ')).collect())
+        });
+        cell.with_dependent(|_, dependent| {
+            assert_eq!(dependent, &Words(vec!["id", "name", "key", "value"]));
+        });
+        assert_eq!(
+            format!("{:?}", cell),
+            "PairCell { owner: (\"id name\", \"key value\"), dependent: Words([\"id\", \"name\", \"key\", \"value\"]) }"
+        );
+
+        PairCell::new((&schema, "static"), |owner| Words(vec![owner.0, owner.1]))
+    };
+
+    assert_eq!(cell.borrow_owner(), &("id name", "static"));
+    assert!(cell == PairCell::new((&schema, "static"), |_| Words(Vec::new())));
+}
+
+#[test]
+fn multiple_owner_lifetimes_with_markers() {
+    self_cell!(
+        struct PinnedPairCell<'a, 'b> {
+            owner: (&'a str, &'b str),
+
+            #[covariant, pinned]
+            dependent: Words,
+        }
+    );
+
+    self_cell!(
+        struct StableDerefPairCell<'a, 'b> {
+            owner: Vec<(&'a str, &'b str)>,
+
+            #[covariant, stable_deref]
+            dependent: Words,
+        }
+
+        impl {Debug, PartialEq}
+    );
+
+    self_cell!(
+        struct SharedPairCell<'a, 'b> {
+            owner: (&'a str, &'b str),
+
+            #[not_covariant, shared]
+            dependent: Words,
+        }
+
+        impl {Debug}
+    );
+
+    self_cell!(
+        struct DependentsPairCell<'a, 'b> {
+            owner: (&'a str, &'b str),
+
+            dependents: {
+                #[covariant]
+                first: Words,
+
+                #[covariant]
+                second: Words,
+            }
+        }
+
+        impl {Debug}
+    );
+
+    self_cell!(
+        struct OwnersPairCell<'a, 'b> {
+            owners: {
+                schema: &'a str,
+                dict: &'b str,
+            }
+
+            #[covariant]
+            dependent: Words,
+        }
+
+        impl {Debug, PartialEq}
+    );
+
+    let schema = String::from("id");
+    let dict = String::from("key");
+
+    let mut pinned = Box::pin(PinnedPairCell::uninit());
+    pinned
+        .as_mut()
+        .init((&schema, &dict), |owner| Words(vec![owner.0, owner.1]));
+    assert_eq!(pinned.borrow_dependent(), &Words(vec!["id", "key"]));
+
+    let stable_deref = StableDerefPairCell::new(vec![(&schema, &dict)], |owner| {
+        Words(vec![owner[0].0, owner[0].1])
+    });
+    assert_eq!(stable_deref.borrow_dependent(), &Words(vec!["id", "key"]));
+
+    let shared = SharedPairCell::new((&schema, &dict), |owner| Words(vec![owner.1]));
+    shared.clone().with_dependent(|_, dependent| {
+        assert_eq!(dependent, &Words(vec!["key"]));
+    });
+
+    let dependents = DependentsPairCell::new(
+        (&schema, &dict),
+        |owner| Words(vec![owner.0]),
+        |owner, first| Words(vec![first.0[0], owner.1]),
+    );
+    assert_eq!(dependents.second(), &Words(vec!["id", "key"]));
+
+    let owners = OwnersPairCell::new(&schema, &dict, |schema, dict| Words(vec![schema, dict]));
+    assert_eq!(owners.borrow_dependent(), &Words(vec!["id", "key"]));
 }
 
 // partial nested cells
@@ -906,3 +1109,1798 @@ fn mut_borrow_new_borrow_mut() {
 
     cell.borrow_owner().borrow_mut();
 }
+
+self_cell!(
+    struct PinnedAstCell {
+        owner: String,
+
+        #[covariant, pinned]
+        dependent: Ast,
+    }
+);
+
+#[test]
+fn pinned_init() {
+    let mut cell = Box::pin(PinnedAstCell::uninit());
+    assert!(!cell.is_initialized());
+
+    cell.as_mut()
+        .init("Endless joy for you never 3".into(), |owner| owner.into());
+    assert!(cell.is_initialized());
+
+    assert_eq!(cell.borrow_owner(), "Endless joy for you never 3");
+    assert_eq!(cell.borrow_dependent(), &Ast(vec!["dle", "nd"]));
+
+    cell.with_dependent(|owner, dependent| {
+        assert_eq!(dependent, &Ast::from(owner));
+    });
+
+    cell.as_mut().with_dependent_mut(|owner, dependent| {
+        dependent.0.push(&owner[0..3]);
+    });
+    assert_eq!(cell.borrow_dependent(), &Ast(vec!["dle", "nd", "End"]));
+
+    let owner = cell.as_mut().take_owner();
+    assert_eq!(owner, "Endless joy for you never 3");
+    assert!(!cell.is_initialized());
+
+    // The same pinned place can be initialized again.
+    cell.as_mut().init(owner, |owner| owner.into());
+    assert_eq!(cell.borrow_dependent(), &Ast(vec!["dle", "nd"]));
+}
+
+#[test]
+fn pinned_try_init() {
+    let mut cell = Box::pin(PinnedAstCell::uninit());
+
+    let res = cell
+        .as_mut()
+        .try_init("abc".into(), |_| std::result::Result::Err(5));
+    assert_eq!(res, std::result::Result::Err(5));
+    assert!(!cell.is_initialized());
+
+    let res = cell
+        .as_mut()
+        .try_init_or_recover("abc".into(), |_| std::result::Result::Err(6));
+    assert_eq!(res, std::result::Result::Err(("abc".into(), 6)));
+    assert!(!cell.is_initialized());
+
+    let res = cell
+        .as_mut()
+        .try_init("abcdef".into(), |owner| -> Result<_, ()> {
+            std::result::Result::Ok(owner.into())
+        });
+    assert!(res.is_ok());
+    assert_eq!(cell.borrow_dependent(), &Ast(vec!["cde", "bc"]));
+}
+
+#[test]
+#[should_panic(expected = "not initialized")]
+fn pinned_uninit_borrow() {
+    let cell = PinnedAstCell::uninit();
+    cell.borrow_owner();
+}
+
+#[test]
+#[should_panic(expected = "already initialized")]
+fn pinned_init_twice() {
+    let mut cell = Box::pin(PinnedAstCell::uninit());
+    cell.as_mut().init("abcdef".into(), |owner| owner.into());
+    cell.as_mut().init("abcdef".into(), |owner| owner.into());
+}
+
+#[test]
+fn pinned_drop_order() {
+    struct DropTrack<'a>(&'a RefCell<Vec<&'static str>>, &'static str);
+
+    impl<'a> Drop for DropTrack<'a> {
+        fn drop(&mut self) {
+            self.0.borrow_mut().push(self.1);
+        }
+    }
+
+    type Dependent<'a> = DropTrack<'a>;
+
+    let order = RefCell::new(Vec::new());
+
+    {
+        // Owner borrows from the stack, so it can't outlive `order`.
+        self_cell!(
+            struct PinnedDropCell<'o> {
+                owner: DropTrack<'o>,
+
+                #[not_covariant, pinned]
+                dependent: Dependent,
+            }
+        );
+
+        let mut cell = Box::pin(PinnedDropCell::uninit());
+        cell.as_mut().init(DropTrack(&order, "owner"), |owner| {
+            DropTrack(owner.0, "dependent")
+        });
+
+        // Panicking in the builder drops the owner and leaves the cell uninitialized.
+        let mut uninit_cell = Box::pin(PinnedDropCell::uninit());
+        let res = catch_unwind(std::panic::AssertUnwindSafe(|| {
+            uninit_cell
+                .as_mut()
+                .init(DropTrack(&order, "panic_owner"), |_| panic!());
+        }));
+        assert!(res.is_err());
+        assert!(!uninit_cell.is_initialized());
+    }
+
+    assert_eq!(*order.borrow(), vec!["panic_owner", "dependent", "owner"]);
+}
+
+self_cell!(
+    struct SharedAstCell {
+        owner: String,
+
+        #[covariant, shared]
+        dependent: Ast,
+    }
+
+    impl {Debug, PartialEq, Eq, Hash}
+);
+
+#[test]
+fn shared_clone() {
+    let cell = SharedAstCell::new("Endless joy for you never 4".into(), |owner| owner.into());
+    let cell_clone = cell.clone();
+
+    // Both handles point to the same owner and dependent.
+    assert!(std::ptr::eq(cell.borrow_owner(), cell_clone.borrow_owner()));
+    assert!(std::ptr::eq(
+        cell.borrow_dependent(),
+        cell_clone.borrow_dependent()
+    ));
+    assert_eq!(cell, cell_clone);
+
+    cell_clone.with_dependent(|owner, dependent| {
+        assert_eq!(dependent, &Ast::from(owner));
+    });
+
+    drop(cell);
+    assert_eq!(cell_clone.borrow_dependent(), &Ast(vec!["dle", "nd"]));
+}
+
+#[test]
+fn shared_try_into_owner() {
+    let cell = SharedAstCell::new("abcdef".into(), |owner| owner.into());
+    let cell_clone = cell.clone();
+
+    let cell = match cell.try_into_owner() {
+        std::result::Result::Ok(_) => unreachable!(),
+        std::result::Result::Err(cell) => cell,
+    };
+    assert_eq!(cell.borrow_owner(), "abcdef");
+
+    drop(cell_clone);
+    assert_eq!(cell.try_into_owner().unwrap(), "abcdef");
+}
+
+#[test]
+fn shared_try_new() {
+    let cell = SharedAstCell::try_new("abcdef".into(), |owner| -> Result<_, ()> {
+        std::result::Result::Ok(owner.into())
+    })
+    .unwrap();
+    assert_eq!(cell.borrow_dependent(), &Ast(vec!["cde", "bc"]));
+
+    let res = SharedAstCell::try_new("abcdef".into(), |_| std::result::Result::Err(5));
+    assert_eq!(res.unwrap_err(), 5);
+
+    let (owner, err) =
+        SharedAstCell::try_new_or_recover("abcdef".into(), |_| std::result::Result::Err(6))
+            .unwrap_err();
+    assert_eq!(owner, "abcdef");
+    assert_eq!(err, 6);
+}
+
+#[test]
+fn shared_drop_order() {
+    struct DropTrack<'a>(&'a RefCell<Vec<&'static str>>, &'static str);
+
+    impl<'a> Drop for DropTrack<'a> {
+        fn drop(&mut self) {
+            self.0.borrow_mut().push(self.1);
+        }
+    }
+
+    type Dependent<'a> = DropTrack<'a>;
+
+    let order = RefCell::new(Vec::new());
+
+    {
+        self_cell!(
+            struct SharedDropCell<'o> {
+                owner: DropTrack<'o>,
+
+                #[not_covariant, shared]
+                dependent: Dependent,
+            }
+        );
+
+        let cell = SharedDropCell::new(DropTrack(&order, "owner"), |owner| {
+            DropTrack(owner.0, "dependent")
+        });
+        let cell_clone = cell.clone();
+
+        drop(cell);
+        assert!(order.borrow().is_empty());
+
+        drop(cell_clone);
+    }
+
+    assert_eq!(*order.borrow(), vec!["dependent", "owner"]);
+}
+
+#[derive(Debug, Eq, PartialEq)]
+pub struct StrAst<'input>(pub Vec<&'input str>);
+
+impl<'x> From<&'x str> for StrAst<'x> {
+    fn from(body: &str) -> StrAst<'_> {
+        StrAst(vec![&body[2..5], &body[1..3]])
+    }
+}
+
+self_cell!(
+    struct StableDerefAstCell {
+        owner: String,
+
+        #[covariant, stable_deref]
+        dependent: StrAst,
+    }
+
+    impl {Clone, Debug, PartialEq, Eq, Hash}
+);
+
+#[test]
+fn stable_deref_new() {
+    let cell =
+        StableDerefAstCell::new("Endless joy for you never 5".into(), |target| target.into());
+    let target_ptr = cell.borrow_owner().as_ptr();
+
+    // Moving the cell moves the owner, but not its heap buffer.
+    let mut cells = vec![cell];
+    let mut cell = cells.pop().unwrap();
+    assert_eq!(cell.borrow_owner().as_ptr(), target_ptr);
+    assert_eq!(cell.borrow_dependent(), &StrAst(vec!["dle", "nd"]));
+
+    cell.with_dependent(|owner, dependent| {
+        assert_eq!(dependent, &StrAst::from(owner.as_str()));
+    });
+
+    cell.with_dependent_mut(|owner, dependent| {
+        dependent.0.push(&owner[0..3]);
+    });
+    assert_eq!(cell.borrow_dependent(), &StrAst(vec!["dle", "nd", "End"]));
+
+    let cloned = cell.clone();
+    assert_ne!(cloned.borrow_owner().as_ptr(), target_ptr);
+    assert_eq!(cloned.borrow_dependent(), &StrAst(vec!["dle", "nd"]));
+
+    assert_eq!(cell.into_owner(), "Endless joy for you never 5");
+}
+
+#[test]
+fn stable_deref_try_new() {
+    let cell = StableDerefAstCell::try_new("abcdef".into(), |target| -> Result<_, ()> {
+        std::result::Result::Ok(target.into())
+    })
+    .unwrap();
+    assert_eq!(cell.borrow_dependent(), &StrAst(vec!["cde", "bc"]));
+
+    let res = StableDerefAstCell::try_new("abcdef".into(), |_| std::result::Result::Err(5));
+    assert_eq!(res.unwrap_err(), 5);
+
+    let (owner, err) =
+        StableDerefAstCell::try_new_or_recover("abcdef".into(), |_| std::result::Result::Err(6))
+            .unwrap_err();
+    assert_eq!(owner, "abcdef");
+    assert_eq!(err, 6);
+}
+
+#[test]
+fn stable_deref_owners() {
+    type Bytes<'a> = &'a [u8];
+
+    self_cell!(
+        struct VecCell {
+            owner: Vec<u8>,
+
+            #[covariant, stable_deref]
+            dependent: Bytes,
+        }
+    );
+
+    let cell = VecCell::new(vec![1, 2, 3, 4], |target| &target[1..3]);
+    assert_eq!(cell.borrow_dependent(), &[2, 3]);
+
+    type Str<'a> = &'a str;
+
+    self_cell!(
+        struct RcStrCell {
+            owner: Rc<str>,
+
+            #[covariant, stable_deref]
+            dependent: Str,
+        }
+    );
+
+    let owner: Rc<str> = Rc::from("abcdef");
+    let cell = RcStrCell::new(Rc::clone(&owner), |target| &target[2..]);
+    assert_eq!(cell.borrow_dependent(), &"cdef");
+    assert_eq!(Rc::strong_count(&owner), 2);
+    drop(cell);
+    assert_eq!(Rc::strong_count(&owner), 1);
+
+    // No heap allocation for the cell itself.
+    assert_eq!(
+        std::mem::size_of::<RcStrCell>(),
+        std::mem::size_of::<Rc<str>>() + std::mem::size_of::<&str>()
+    );
+}
+
+#[test]
+fn stable_deref_drop_order() {
+    struct DropTrack<'a>(&'a RefCell<Vec<&'static str>>, &'static str);
+
+    impl<'a> Drop for DropTrack<'a> {
+        fn drop(&mut self) {
+            self.0.borrow_mut().push(self.1);
+        }
+    }
+
+    struct OwnerTrack<'a>(Rc<DropTrack<'a>>);
+
+    impl<'a> std::ops::Deref for OwnerTrack<'a> {
+        type Target = DropTrack<'a>;
+
+        fn deref(&self) -> &DropTrack<'a> {
+            &self.0
+        }
+    }
+
+    // Rc content never moves.
+    unsafe impl<'a> self_cell::StableDeref for OwnerTrack<'a> {}
+
+    struct Dependent<'a>(&'a RefCell<Vec<&'static str>>, &'static str);
+
+    impl<'a> Drop for Dependent<'a> {
+        fn drop(&mut self) {
+            self.0.borrow_mut().push(self.1);
+        }
+    }
+
+    let order = RefCell::new(Vec::new());
+
+    {
+        self_cell!(
+            struct StableDerefDropCell<'o> {
+                owner: OwnerTrack<'o>,
+
+                #[not_covariant, stable_deref]
+                dependent: Dependent,
+            }
+        );
+
+        let _cell =
+            StableDerefDropCell::new(OwnerTrack(Rc::new(DropTrack(&order, "owner"))), |target| {
+                Dependent(target.0, "dependent")
+            });
+
+        let res = catch_unwind(std::panic::AssertUnwindSafe(|| {
+            StableDerefDropCell::new(
+                OwnerTrack(Rc::new(DropTrack(&order, "panic_owner"))),
+                |_| -> Dependent { panic!() },
+            );
+        }));
+        assert!(res.is_err());
+    }
+
+    assert_eq!(*order.borrow(), vec!["panic_owner", "dependent", "owner"]);
+}
+
+type Tokens<'a> = Vec<&'a str>;
+
+#[derive(Debug, Eq, PartialEq)]
+pub struct TokenAst<'a>(pub Vec<&'a &'a str>);
+
+self_cell!(
+    struct TokenAstCell {
+        owner: String,
+
+        dependents: {
+            #[covariant]
+            tokens: Tokens,
+
+            #[covariant]
+            ast: TokenAst,
+        }
+    }
+
+    impl {Debug, PartialEq, Eq, Hash}
+);
+
+fn build_token_ast_cell(code: &str) -> TokenAstCell {
+    TokenAstCell::new(
+        code.into(),
+        |owner| owner.split(' ').collect(),
+        |_owner, tokens| TokenAst(tokens.iter().filter(|token| token.len() > 1).collect()),
+    )
+}
+
+#[test]
+fn dependents_new() {
+    let cell = build_token_ast_cell("fox = cat + dog");
+
+    // Moving the cell doesn't invalidate the references between dependents.
+    let mut cells = vec![cell];
+    let mut cell = cells.pop().unwrap();
+
+    assert_eq!(cell.borrow_owner(), "fox = cat + dog");
+    assert_eq!(cell.tokens(), &vec!["fox", "=", "cat", "+", "dog"]);
+    assert_eq!(cell.ast(), &TokenAst(vec![&"fox", &"cat", &"dog"]));
+
+    cell.with_dependents(|owner, tokens, ast| {
+        assert_eq!(tokens.len(), owner.split(' ').count());
+        assert!(std::ptr::eq(ast.0[1], &tokens[2]));
+    });
+
+    cell.with_dependents_mut(|_owner, tokens, ast| {
+        ast.0.push(&tokens[1]);
+    });
+    assert_eq!(cell.ast(), &TokenAst(vec![&"fox", &"cat", &"dog", &"="]));
+
+    assert_eq!(
+        format!("{:?}", cell),
+        "TokenAstCell { owner: \"fox = cat + dog\", tokens: [\"fox\", \"=\", \"cat\", \"+\", \
+         \"dog\"], ast: TokenAst([\"fox\", \"cat\", \"dog\", \"=\"]) }"
+    );
+    assert_eq!(cell, build_token_ast_cell("fox = cat + dog"));
+
+    assert_eq!(cell.into_owner(), "fox = cat + dog");
+}
+
+#[test]
+fn dependents_try_new() {
+    let cell = TokenAstCell::try_new(
+        "a bc".into(),
+        |owner| -> Result<_, usize> { std::result::Result::Ok(owner.split(' ').collect()) },
+        |_owner, tokens| std::result::Result::Ok(TokenAst(tokens.iter().collect())),
+    )
+    .unwrap();
+    assert_eq!(cell.ast(), &TokenAst(vec![&"a", &"bc"]));
+
+    let res = TokenAstCell::try_new(
+        "a bc".into(),
+        |owner| std::result::Result::Ok(owner.split(' ').collect()),
+        |_owner, tokens| std::result::Result::Err(tokens.len()),
+    );
+    assert_eq!(res.unwrap_err(), 2);
+
+    let (owner, err) = TokenAstCell::try_new_or_recover(
+        "a bc".into(),
+        |owner| std::result::Result::Ok(owner.split(' ').collect()),
+        |_owner, tokens| std::result::Result::Err(tokens.len()),
+    )
+    .unwrap_err();
+    assert_eq!(owner, "a bc");
+    assert_eq!(err, 2);
+
+    let (owner, err) = TokenAstCell::try_new_or_recover(
+        "a bc".into(),
+        |_owner| std::result::Result::Err(0),
+        |_owner, _tokens| unreachable!(),
+    )
+    .unwrap_err();
+    assert_eq!(owner, "a bc");
+    assert_eq!(err, 0);
+}
+
+#[test]
+fn dependents_drop_order() {
+    struct DropTrack<'a>(&'a RefCell<Vec<&'static str>>, &'static str);
+
+    impl<'a> Drop for DropTrack<'a> {
+        fn drop(&mut self) {
+            self.0.borrow_mut().push(self.1);
+        }
+    }
+
+    type First<'a> = DropTrack<'a>;
+
+    // Reads the earlier dependent in drop, which must still be alive.
+    struct Second<'a>(&'a First<'a>);
+
+    impl<'a> Drop for Second<'a> {
+        fn drop(&mut self) {
+            (self.0).0.borrow_mut().push("second");
+        }
+    }
+
+    type Third<'a> = DropTrack<'a>;
+
+    let order = RefCell::new(Vec::new());
+
+    {
+        self_cell!(
+            struct ChainDropCell<'o> {
+                owner: DropTrack<'o>,
+
+                dependents: {
+                    #[covariant]
+                    first: First,
+
+                    #[covariant]
+                    second: Second,
+
+                    #[not_covariant]
+                    third: Third,
+                }
+            }
+        );
+
+        let _cell = ChainDropCell::new(
+            DropTrack(&order, "owner"),
+            |owner| DropTrack(owner.0, "first"),
+            |_owner, first| Second(first),
+            |owner, _first, _second| DropTrack(owner.0, "third"),
+        );
+
+        let res = catch_unwind(std::panic::AssertUnwindSafe(|| {
+            ChainDropCell::new(
+                DropTrack(&order, "panic_owner"),
+                |owner| DropTrack(owner.0, "panic_first"),
+                |_owner, _first| -> Second { panic!() },
+                |_owner, _first, _second| unreachable!(),
+            );
+        }));
+        assert!(res.is_err());
+    }
+
+    assert_eq!(
+        *order.borrow(),
+        vec![
+            "panic_first",
+            "panic_owner",
+            "third",
+            "second",
+            "first",
+            "owner"
+        ]
+    );
+}
+
+#[derive(Debug, Eq, PartialEq)]
+pub struct Translation<'a>(pub Vec<&'a str>);
+
+self_cell!(
+    struct TranslatedCell {
+        owners: {
+            dictionary: Vec<(String, String)>,
+            text: String,
+        }
+
+        #[covariant]
+        dependent: Translation,
+    }
+
+    impl {Debug, PartialEq, Eq, Hash}
+);
+
+fn translate<'a>(dictionary: &'a [(String, String)], text: &'a str) -> Translation<'a> {
+    Translation(
+        text.split(' ')
+            .map(|word| {
+                dictionary
+                    .iter()
+                    .find(|entry| entry.0 == word)
+                    .map_or(word, |entry| entry.1.as_str())
+            })
+            .collect(),
+    )
+}
+
+fn build_translated_cell() -> TranslatedCell {
+    TranslatedCell::new(
+        vec![("cat".into(), "Katze".into())],
+        "cat and dog".into(),
+        |dictionary, text| translate(dictionary, text),
+    )
+}
+
+#[test]
+fn owners_new() {
+    let mut cell = build_translated_cell();
+
+    assert_eq!(cell.dictionary(), &vec![("cat".into(), "Katze".into())]);
+    assert_eq!(cell.text(), "cat and dog");
+    assert_eq!(cell.borrow_owners().1, "cat and dog");
+    assert_eq!(
+        cell.borrow_dependent(),
+        &Translation(vec!["Katze", "and", "dog"])
+    );
+
+    cell.with_dependent(|dictionary, text, dependent| {
+        assert_eq!(dependent, &translate(dictionary, text));
+    });
+
+    cell.with_dependent_mut(|dictionary, _text, dependent| {
+        dependent.0.push(&dictionary[0].0);
+    });
+    assert_eq!(
+        cell.borrow_dependent(),
+        &Translation(vec!["Katze", "and", "dog", "cat"])
+    );
+
+    assert_eq!(
+        format!("{:?}", cell),
+        "TranslatedCell { dictionary: [(\"cat\", \"Katze\")], text: \"cat and dog\", dependent: \
+         Translation([\"Katze\", \"and\", \"dog\", \"cat\"]) }"
+    );
+    assert_eq!(cell, build_translated_cell());
+
+    let (dictionary, text) = cell.into_owners();
+    assert_eq!(dictionary.len(), 1);
+    assert_eq!(text, "cat and dog");
+}
+
+#[test]
+fn owners_try_new() {
+    let cell = TranslatedCell::try_new(Vec::new(), "a b".into(), |dictionary, text| {
+        Result::<_, ()>::Ok(translate(dictionary, text))
+    })
+    .unwrap();
+    assert_eq!(cell.borrow_dependent(), &Translation(vec!["a", "b"]));
+
+    let res = TranslatedCell::try_new(Vec::new(), "a b".into(), |_, text| {
+        std::result::Result::Err(text.len())
+    });
+    assert_eq!(res.unwrap_err(), 3);
+
+    let ((dictionary, text), err) =
+        TranslatedCell::try_new_or_recover(Vec::new(), "a b".into(), |_, text| {
+            std::result::Result::Err(text.len())
+        })
+        .unwrap_err();
+    assert!(dictionary.is_empty());
+    assert_eq!(text, "a b");
+    assert_eq!(err, 3);
+}
+
+#[test]
+fn owners_drop_order() {
+    struct DropTrack<'a>(&'a RefCell<Vec<&'static str>>, &'static str);
+
+    impl<'a> Drop for DropTrack<'a> {
+        fn drop(&mut self) {
+            self.0.borrow_mut().push(self.1);
+        }
+    }
+
+    type Dependent<'a> = DropTrack<'a>;
+
+    let order = RefCell::new(Vec::new());
+
+    {
+        self_cell!(
+            struct OwnersDropCell<'o> {
+                owners: {
+                    first: DropTrack<'o>,
+                    second: DropTrack<'o>,
+                }
+
+                #[not_covariant]
+                dependent: Dependent,
+            }
+        );
+
+        let _cell = OwnersDropCell::new(
+            DropTrack(&order, "first"),
+            DropTrack(&order, "second"),
+            |first, _second| DropTrack(first.0, "dependent"),
+        );
+    }
+
+    assert_eq!(*order.borrow(), vec!["dependent", "first", "second"]);
+}
+
+#[test]
+fn replace_owner() {
+    let mut cell = PackedAstCell::new("abcdef".into(), |owner| owner.into());
+    let joined_ptr = cell.borrow_owner() as *const String;
+
+    let old_owner = cell.replace_owner("Endless joy".into(), |owner| owner.into());
+    assert_eq!(old_owner, "abcdef");
+    assert_eq!(cell.borrow_owner(), "Endless joy");
+    assert_eq!(cell.borrow_dependent(), &Ast(vec!["dle", "nd"]));
+
+    // The allocation is reused.
+    assert_eq!(cell.borrow_owner() as *const String, joined_ptr);
+}
+
+#[test]
+fn try_replace_owner() {
+    let cell = PackedAstCell::new("abcdef".into(), |owner| owner.into());
+
+    let (cell, old_owner) = cell
+        .try_replace_owner("Endless joy".into(), |owner| -> Result<_, ()> {
+            std::result::Result::Ok(owner.into())
+        })
+        .unwrap();
+    assert_eq!(old_owner, "abcdef");
+    assert_eq!(cell.borrow_dependent(), &Ast(vec!["dle", "nd"]));
+
+    let res = cell.try_replace_owner("xyz".into(), |owner| std::result::Result::Err(owner.len()));
+    assert_eq!(res.unwrap_err(), 3);
+
+    let cell = PackedAstCell::new("abcdef".into(), |owner| owner.into());
+    // The owner the cell was built with comes first.
+    let (old_owner, owner, err) = cell
+        .try_replace_owner_or_recover("xyz".into(), |owner| std::result::Result::Err(owner.len()))
+        .unwrap_err();
+    assert_eq!(old_owner, "abcdef");
+    assert_eq!(owner, "xyz");
+    assert_eq!(err, 3);
+}
+
+#[test]
+fn replace_owner_drop_order() {
+    struct DropTrack<'a>(&'a RefCell<Vec<&'static str>>, &'static str);
+
+    impl<'a> Drop for DropTrack<'a> {
+        fn drop(&mut self) {
+            self.0.borrow_mut().push(self.1);
+        }
+    }
+
+    type Dependent<'a> = DropTrack<'a>;
+
+    let order = RefCell::new(Vec::new());
+
+    {
+        self_cell!(
+            struct ReplaceDropCell<'o> {
+                owner: DropTrack<'o>,
+
+                #[not_covariant]
+                dependent: Dependent,
+            }
+        );
+
+        let mut cell = ReplaceDropCell::new(DropTrack(&order, "owner"), |owner| {
+            DropTrack(owner.0, "dependent")
+        });
+
+        let old_owner = cell.replace_owner(DropTrack(&order, "new_owner"), |owner| {
+            DropTrack(owner.0, "new_dependent")
+        });
+        assert_eq!(*order.borrow(), vec!["dependent"]);
+        drop(old_owner);
+
+        // replace_owner aborts on panic, try_replace_owner consumes the cell
+        // and drops it instead.
+        let res = catch_unwind(std::panic::AssertUnwindSafe(|| {
+            let _ = cell.try_replace_owner(
+                DropTrack(&order, "panic_owner"),
+                |_| -> Result<Dependent, ()> { panic!() },
+            );
+        }));
+        assert!(res.is_err());
+    }
+
+    assert_eq!(
+        *order.borrow(),
+        vec![
+            "dependent",
+            "owner",
+            "new_dependent",
+            "panic_owner",
+            "new_owner"
+        ]
+    );
+}
+
+#[test]
+fn with_owner_mut() {
+    let mut cell = PackedAstCell::new("abcdef".into(), |owner| owner.into());
+    let joined_ptr = cell.borrow_owner() as *const String;
+
+    cell.with_owner_mut(
+        |owner| owner.insert_str(0, "Endless joy "),
+        |owner| owner.into(),
+    );
+    assert_eq!(cell.borrow_owner(), "Endless joy abcdef");
+    assert_eq!(cell.borrow_dependent(), &Ast(vec!["dle", "nd"]));
+    assert_eq!(cell.borrow_owner() as *const String, joined_ptr);
+
+    let cell = cell
+        .try_with_owner_mut(
+            |owner| owner.truncate(6),
+            |owner| -> Result<_, ()> { std::result::Result::Ok(owner.into()) },
+        )
+        .unwrap();
+    assert_eq!(cell.borrow_owner(), "Endles");
+    assert_eq!(cell.borrow_dependent(), &Ast(vec!["dle", "nd"]));
+
+    let res = cell.try_with_owner_mut(
+        |owner| owner.clear(),
+        |owner| std::result::Result::Err(owner.len()),
+    );
+    assert_eq!(res.unwrap_err(), 0);
+
+    let cell = PackedAstCell::new("abcdef".into(), |owner| owner.into());
+    let (owner, err) = cell
+        .try_with_owner_mut_or_recover(
+            |owner| owner.push('g'),
+            |owner| std::result::Result::Err(owner.len()),
+        )
+        .unwrap_err();
+    assert_eq!(owner, "abcdefg");
+    assert_eq!(err, 7);
+}
+
+#[test]
+fn with_owner_mut_drop_order() {
+    struct DropTrack<'a>(&'a RefCell<Vec<&'static str>>, &'static str);
+
+    impl<'a> Drop for DropTrack<'a> {
+        fn drop(&mut self) {
+            self.0.borrow_mut().push(self.1);
+        }
+    }
+
+    type Dependent<'a> = DropTrack<'a>;
+
+    let order = RefCell::new(Vec::new());
+
+    {
+        self_cell!(
+            struct OwnerMutDropCell<'o> {
+                owner: DropTrack<'o>,
+
+                #[not_covariant]
+                dependent: Dependent,
+            }
+        );
+
+        let mut cell = OwnerMutDropCell::new(DropTrack(&order, "owner"), |owner| {
+            DropTrack(owner.0, "dependent")
+        });
+
+        cell.with_owner_mut(
+            |owner| owner.1 = "edited_owner",
+            |owner| DropTrack(owner.0, "new_dependent"),
+        );
+        assert_eq!(*order.borrow(), vec!["dependent"]);
+
+        // with_owner_mut aborts on panic, try_with_owner_mut consumes the
+        // cell and drops it instead.
+        let res = catch_unwind(std::panic::AssertUnwindSafe(|| {
+            let _ = cell.try_with_owner_mut(
+                |_| panic!(),
+                |_| -> Result<Dependent, ()> { unreachable!() },
+            );
+        }));
+        assert!(res.is_err());
+    }
+
+    assert_eq!(
+        *order.borrow(),
+        vec!["dependent", "new_dependent", "edited_owner"]
+    );
+}
+
+#[derive(Debug, PartialEq)]
+struct Words<'a>(Vec<&'a str>);
+
+self_cell!(
+    struct WordsCell<T: AsRef<str>>
+    where
+        T: Clone,
+    {
+        owner: T,
+
+        #[covariant]
+        dependent: Words,
+    }
+
+    impl {Debug, PartialEq, Eq, Hash}
+);
+
+self_cell!(
+    struct BorrowedWordsCell<'a, T: AsRef<str>> {
+        owner: &'a T,
+
+        #[not_covariant]
+        dependent: Words,
+    }
+);
+
+#[test]
+fn generic_params() {
+    let cell = WordsCell::new(String::from("fox cat"), |owner| {
+        Words(owner.split(' ').collect())
+    });
+    assert_eq!(cell.borrow_dependent(), &Words(vec!["fox", "cat"]));
+    assert_eq!(
+        format!("{:?}", cell),
+        "WordsCell { owner: \"fox cat\", dependent: Words([\"fox\", \"cat\"]) }"
+    );
+
+    let other = WordsCell::try_new(String::from("fox cat"), |owner| {
+        std::result::Result::Ok::<_, ()>(Words(owner.split(' ').collect()))
+    })
+    .unwrap();
+    assert_eq!(cell, other);
+
+    let mut set = HashSet::new();
+    set.insert(cell);
+    assert!(!set.insert(other));
+
+    let mut cell = WordsCell::new("cow", |owner: &&str| Words(vec![*owner]));
+    let old_owner = cell.replace_owner("hen owl", |owner| Words(owner.split(' ').collect()));
+    assert_eq!(old_owner, "cow");
+    assert_eq!(cell.into_owner(), "hen owl");
+
+    let text = String::from("ant bee");
+    let cell = BorrowedWordsCell::new(&text, |owner| Words(owner.split(' ').collect()));
+    cell.with_dependent(|owner, dependent| {
+        assert_eq!(*owner, &text);
+        assert_eq!(dependent, &Words(vec!["ant", "bee"]));
+    });
+}
+
+mod doc {
+    #[derive(Debug, PartialEq)]
+    pub struct Doc<'a, T> {
+        pub title: &'a str,
+        pub value: T,
+    }
+
+    #[derive(Debug, PartialEq)]
+    pub struct Title<'a>(pub &'a str);
+}
+
+self_cell!(
+    struct TitleCell {
+        owner: String,
+
+        #[covariant]
+        dependent: doc::Title,
+    }
+
+    impl {Debug}
+);
+
+self_cell!(
+    struct DocCell<T: str::FromStr> {
+        owner: String,
+
+        #[covariant]
+        dependent: self::doc::Doc<'_, T>,
+    }
+
+    impl {Debug, PartialEq}
+);
+
+fn parse_doc<T: str::FromStr>(owner: &str) -> doc::Doc<'_, T> {
+    let mut lines = owner.lines();
+    let title = lines.next().unwrap();
+    let value = lines.next().unwrap().parse().ok().unwrap();
+
+    doc::Doc { title, value }
+}
+
+#[test]
+fn path_dependent() {
+    let cell = TitleCell::new("Ferris".into(), |owner| doc::Title(owner));
+    assert_eq!(cell.borrow_dependent(), &doc::Title("Ferris"));
+    assert_eq!(
+        format!("{:?}", cell),
+        "TitleCell { owner: \"Ferris\", dependent: Title(\"Ferris\") }"
+    );
+}
+
+#[test]
+fn generic_argument_dependent() {
+    let cell = DocCell::<u32>::new("Answer\n42".into(), |owner| parse_doc(owner));
+    assert_eq!(
+        cell.borrow_dependent(),
+        &doc::Doc {
+            title: "Answer",
+            value: 42
+        }
+    );
+
+    let other = DocCell::<u32>::try_new("Answer\n42".into(), |owner| {
+        std::result::Result::Ok::<_, ()>(parse_doc(owner))
+    })
+    .unwrap();
+    assert_eq!(cell, other);
+
+    let mut cell = DocCell::<f64>::new("Half\n0.5".into(), |owner| parse_doc(owner));
+    cell.with_dependent_mut(|_, dependent| dependent.value *= 2.0);
+    assert_eq!(cell.borrow_dependent().value, 1.0);
+
+    let old_owner = cell.replace_owner("Pi\n3.14".into(), |owner| parse_doc(owner));
+    assert_eq!(old_owner, "Half\n0.5");
+    assert_eq!(cell.borrow_dependent().title, "Pi");
+    assert_eq!(cell.into_owner(), "Pi\n3.14");
+}
+
+self_cell!(
+    struct NestedCell {
+        owner: String,
+
+        #[covariant]
+        dependent: for<'a> Option<Vec<&'a str>>,
+    }
+
+    impl {Debug, PartialEq, Eq}
+);
+
+self_cell!(
+    struct ParsedWordsCell<T: str::FromStr> {
+        #[owner(borrow = borrow_text, into = into_text)]
+        text: String,
+
+        #[covariant]
+        #[dependent(borrow = borrow_words, with = with_words, with_mut = with_words_mut)]
+        words: for<'a> Vec<(&'a str, Option<T>)>,
+    }
+);
+
+#[test]
+fn nested_dependent() {
+    let mut cell = NestedCell::new("ant bee".into(), |owner| Some(owner.split(' ').collect()));
+    assert_eq!(cell.borrow_dependent(), &Some(vec!["ant", "bee"]));
+    assert_eq!(
+        format!("{:?}", cell),
+        "NestedCell { owner: \"ant bee\", dependent: Some([\"ant\", \"bee\"]) }"
+    );
+
+    cell.with_dependent_mut(|_, dependent| *dependent = None);
+    assert_eq!(cell.borrow_dependent(), &None);
+
+    let old_owner = cell.replace_owner("cat".into(), |owner| Some(vec![owner]));
+    assert_eq!(old_owner, "ant bee");
+    assert_eq!(cell.borrow_dependent(), &Some(vec!["cat"]));
+}
+
+#[test]
+fn nested_generic_dependent() {
+    let cell = ParsedWordsCell::<u8>::new("1 x 3".into(), |text| {
+        text.split(' ')
+            .map(|word| (word, word.parse().ok()))
+            .collect()
+    });
+    assert_eq!(
+        cell.borrow_words(),
+        &vec![("1", Some(1)), ("x", None), ("3", Some(3))]
+    );
+    cell.with_words(|text, words| assert_eq!(words.len(), text.split(' ').count()));
+    assert_eq!(cell.into_text(), "1 x 3");
+}
+
+self_cell!(
+    struct NestedPinnedCell {
+        owner: String,
+
+        #[covariant, pinned]
+        dependent: for<'a> Option<&'a str>,
+    }
+);
+
+self_cell!(
+    struct NestedStableDerefCell {
+        owner: String,
+
+        #[covariant, stable_deref]
+        dependent: for<'a> Vec<&'a str>,
+    }
+
+    impl {Debug, PartialEq}
+);
+
+self_cell!(
+    struct NestedSharedCell {
+        owner: String,
+
+        #[covariant, shared]
+        dependent: doc::Title,
+    }
+
+    impl {Debug}
+);
+
+self_cell!(
+    struct NestedOwnersCell {
+        owners: {
+            first: String,
+            second: String,
+        }
+
+        #[covariant]
+        dependent: for<'a> (&'a str, &'a str),
+    }
+
+    impl {Debug}
+);
+
+self_cell!(
+    struct PathDependentsCell {
+        owner: String,
+
+        dependents: {
+            #[covariant]
+            title: doc::Title,
+
+            #[covariant]
+            doc: self::doc::Doc<'_, usize>,
+        }
+    }
+);
+
+#[test]
+fn nested_dependent_with_marker() {
+    let mut pinned = Box::pin(NestedPinnedCell::uninit());
+    pinned.as_mut().init("ant".into(), |owner| Some(owner));
+    assert_eq!(pinned.borrow_dependent(), &Some("ant"));
+
+    let stable_deref =
+        NestedStableDerefCell::new("ant bee".into(), |target| target.split(' ').collect());
+    assert_eq!(stable_deref.borrow_dependent(), &vec!["ant", "bee"]);
+    assert_eq!(stable_deref.into_owner(), "ant bee");
+
+    let shared = NestedSharedCell::new("Ferris".into(), |owner| doc::Title(owner));
+    assert_eq!(shared.clone().borrow_dependent(), &doc::Title("Ferris"));
+
+    let owners = NestedOwnersCell::new("ant".into(), "bee".into(), |first, second| {
+        (first.as_str(), second.as_str())
+    });
+    assert_eq!(owners.borrow_dependent(), &("ant", "bee"));
+}
+
+#[test]
+fn path_dependents() {
+    let cell = PathDependentsCell::new(
+        "Ferris".into(),
+        |owner| doc::Title(owner),
+        |_owner, title| doc::Doc {
+            title: title.0,
+            value: title.0.len(),
+        },
+    );
+    assert_eq!(cell.title(), &doc::Title("Ferris"));
+    assert_eq!(
+        cell.doc(),
+        &doc::Doc {
+            title: "Ferris",
+            value: 6
+        }
+    );
+}
+
+self_cell!(
+    struct Config {
+        #[owner(borrow = borrow_source, into = into_source)]
+        source: String,
+
+        #[covariant]
+        #[dependent(borrow = borrow_ast, with = with_ast, with_mut = with_ast_mut)]
+        ast: Ast,
+    }
+
+    impl {Clone(with = |source| Ast(source.split(' ').collect())), Debug, PartialEq, Eq, Hash}
+);
+
+#[test]
+fn custom_field_names() {
+    let mut config = Config::new("a key value".into(), |source| {
+        Ast(source.split(' ').collect())
+    });
+
+    assert_eq!(config.borrow_source(), "a key value");
+    assert_eq!(config.borrow_ast(), &Ast(vec!["a", "key", "value"]));
+
+    config.with_ast(|source, ast| {
+        assert_eq!(source, "a key value");
+        assert_eq!(ast, &Ast(vec!["a", "key", "value"]));
+    });
+
+    config.with_ast_mut(|_, ast| ast.0.pop());
+    assert_eq!(config.borrow_ast(), &Ast(vec!["a", "key"]));
+
+    assert_eq!(
+        format!("{:?}", config),
+        "Config { source: \"a key value\", ast: Ast([\"a\", \"key\"]) }"
+    );
+    assert!(config == Config::new("a key value".into(), |_| Ast(Vec::new())));
+
+    let mut set = HashSet::new();
+    set.insert(Config::new("a key value".into(), |_| Ast(Vec::new())));
+    assert!(set.contains(&config));
+
+    let cloned = config.clone();
+    assert_eq!(cloned.borrow_ast(), &Ast(vec!["a", "key", "value"]));
+
+    assert_eq!(config.into_source(), "a key value");
+}
+
+#[test]
+fn custom_field_names_not_covariant() {
+    type Pos<'a> = Cell<&'a str>;
+
+    self_cell!(
+        struct Cursor<'a> {
+            #[owner(borrow = text, into = into_text)]
+            source: &'a str,
+
+            #[not_covariant]
+            #[dependent(with = with_pos, with_mut = with_pos_mut)]
+            pos: Pos,
+        }
+
+        impl {Debug}
+    );
+
+    let text = String::from("ab cd");
+    let mut cursor = Cursor::new(&text, |source| Cell::new(&source[..2]));
+    assert_eq!(*cursor.text(), "ab cd");
+
+    cursor.with_pos(|source, pos| pos.set(&source[3..]));
+    cursor.with_pos_mut(|_, pos| assert_eq!(pos.get(), "cd"));
+
+    assert_eq!(
+        format!("{:?}", cursor),
+        "Cursor { source: \"ab cd\", pos: Cell { value: \"cd\" } }"
+    );
+    assert_eq!(cursor.into_text(), "ab cd");
+}
+
+mod restricted {
+    use super::Ast;
+    use self_cell::self_cell;
+
+    self_cell!(
+        pub struct RestrictedCell {
+            owner: String,
+
+            #[covariant]
+            dependent: Ast,
+        }
+
+        methods {
+            borrow_owner: pub(self),
+            with_dependent_mut: none,
+            into_owner: none,
+            replace_owner: pub(crate),
+        }
+
+        impl {Debug, PartialEq}
+    );
+
+    pub fn owner_len(cell: &RestrictedCell) -> usize {
+        cell.borrow_owner().len()
+    }
+}
+
+#[test]
+fn method_visibility() {
+    use restricted::RestrictedCell;
+
+    let mut cell = RestrictedCell::new("a b".into(), |owner| Ast(owner.split(' ').collect()));
+    assert_eq!(cell.borrow_dependent(), &Ast(vec!["a", "b"]));
+    assert_eq!(restricted::owner_len(&cell), 3);
+
+    let old_owner = cell.replace_owner("c d e".into(), |owner| Ast(owner.split(' ').collect()));
+    assert_eq!(old_owner, "a b");
+    cell.with_dependent(|owner, dependent| {
+        assert_eq!(owner, "c d e");
+        assert_eq!(dependent, &Ast(vec!["c", "d", "e"]));
+    });
+
+    assert_eq!(
+        format!("{:?}", cell),
+        "RestrictedCell { owner: \"c d e\", dependent: Ast([\"c\", \"d\", \"e\"]) }"
+    );
+    assert!(cell == RestrictedCell::new("c d e".into(), |_| Ast(Vec::new())));
+}
+
+fn ast_from_ref<'a>(owner: &'a &String) -> Ast<'a> {
+    Ast::from(*owner)
+}
+
+#[test]
+fn clone_owner_lifetime() {
+    self_cell!(
+        struct BorrowedAstCell<'a> {
+            owner: &'a String,
+
+            #[covariant]
+            dependent: Ast,
+        }
+
+        impl {Clone(with = ast_from_ref), Debug, PartialEq}
+    );
+
+    let body = String::from("some string");
+    let cell = BorrowedAstCell::new(&body, |owner| Ast(vec![&owner[..4]]));
+    let cloned = cell.clone();
+    drop(cell);
+
+    assert_eq!(*cloned.borrow_owner(), &body);
+    assert_eq!(cloned.borrow_dependent(), &Ast::from(&body));
+}
+
+#[derive(Debug, PartialEq)]
+struct Outline<'a> {
+    headings: Vec<(usize, &'a str)>,
+    source: &'a String,
+    label: Option<&'a str>,
+}
+
+self_cell::impl_rebase!(Outline<'a> {
+    headings: Vec<(usize, &'a str)>,
+    source: &'a String,
+    label: Option<&'a str>,
+});
+
+self_cell!(
+    struct OutlineCell {
+        owner: String,
+
+        #[covariant]
+        dependent: Outline,
+    }
+
+    impl {Clone(rebase), Debug}
+);
+
+fn owns<T: ?Sized>(owner: &str, value: &T) -> bool {
+    let start = owner.as_ptr() as usize;
+    let addr = value as *const T as *const u8 as usize;
+    addr >= start && addr < start + owner.len()
+}
+
+#[test]
+fn clone_rebase() {
+    let cell = OutlineCell::new("# Intro\n# Usage".into(), |owner| Outline {
+        headings: owner
+            .lines()
+            .enumerate()
+            .map(|(i, line)| (i, &line[2..]))
+            .collect(),
+        source: owner,
+        label: Some("static"),
+    });
+
+    let cloned = cell.clone();
+    let old_owner_ptr = cell.borrow_owner() as *const String;
+    drop(cell);
+
+    let owner = cloned.borrow_owner();
+    let outline = cloned.borrow_dependent();
+    assert_eq!(outline.headings, vec![(0, "Intro"), (1, "Usage")]);
+    assert!(outline.headings.iter().all(|&(_, h)| owns(owner, h)));
+    assert!(std::ptr::eq(outline.source, owner));
+    assert!(!std::ptr::eq(outline.source, old_owner_ptr));
+    assert_eq!(outline.label, Some("static"));
+    assert!(!owns(owner, outline.label.unwrap()));
+}
+
+#[test]
+fn clone_rebase_slices() {
+    type Halves<'a> = (&'a [u16], &'a [u16]);
+
+    self_cell!(
+        struct HalvesCell {
+            owner: Vec<u16>,
+
+            #[covariant]
+            dependent: Halves,
+        }
+
+        impl {Clone(rebase)}
+    );
+
+    let cell = HalvesCell::new(vec![1, 2, 3, 4, 5], |owner| owner.split_at(2));
+    let cloned = cell.clone();
+    let old_ptr = cell.borrow_owner().as_ptr();
+    drop(cell);
+
+    let (left, right) = *cloned.borrow_dependent();
+    assert_eq!(left, &[1, 2]);
+    assert_eq!(right, &[3, 4, 5]);
+    assert_eq!(left.as_ptr(), cloned.borrow_owner().as_ptr());
+    assert_ne!(left.as_ptr(), old_ptr);
+}
+
+#[derive(Debug, PartialEq)]
+struct Tagged<'a, T> {
+    text: &'a str,
+    tag: T,
+    marker: std::marker::PhantomData<&'a str>,
+}
+
+self_cell::impl_rebase!(Tagged<'a, T> {
+    text: &'a str,
+    tag: T,
+    marker: std::marker::PhantomData<&'a str>,
+});
+
+struct Bytes<'a>(&'a [u8], &'a str);
+
+self_cell::impl_rebase!(Bytes<'a>(0: &'a [u8], 1: &'a str));
+
+#[test]
+fn clone_rebase_impl_rebase() {
+    type TaggedText<'a> = Tagged<'a, u8>;
+
+    self_cell!(
+        struct TaggedCell {
+            owner: Vec<u8>,
+
+            #[covariant]
+            dependent: TaggedText,
+        }
+
+        impl {Clone(rebase)}
+    );
+
+    self_cell!(
+        struct BytesCell {
+            owner: Vec<u8>,
+
+            #[covariant]
+            dependent: Bytes,
+        }
+
+        impl {Clone(rebase)}
+    );
+
+    let cell = TaggedCell::new(b"tag".to_vec(), |owner| Tagged {
+        text: std::str::from_utf8(owner).unwrap(),
+        tag: 1,
+        marker: std::marker::PhantomData,
+    });
+    let cloned = cell.clone();
+    drop(cell);
+    assert_eq!(cloned.borrow_dependent().text, "tag");
+    assert_eq!(
+        cloned.borrow_dependent().text.as_ptr(),
+        cloned.borrow_owner().as_ptr()
+    );
+
+    let cell = BytesCell::new(b"ab".to_vec(), |owner| {
+        Bytes(&owner[..1], std::str::from_utf8(&owner[1..]).unwrap())
+    });
+    let cloned = cell.clone();
+    drop(cell);
+    let Bytes(first, second) = *cloned.borrow_dependent();
+    assert_eq!((first, second), (&b"a"[..], "b"));
+    assert_eq!(first.as_ptr(), cloned.borrow_owner().as_ptr());
+}
+
+#[test]
+fn ordered_cells() {
+    use std::collections::BTreeMap;
+
+    self_cell!(
+        struct KeyCell {
+            owner: String,
+
+            #[covariant]
+            dependent: Ast,
+        }
+
+        impl {PartialEq, Eq, PartialOrd, Ord}
+    );
+
+    let key = |s: &str| KeyCell::new(s.to_string(), |owner| owner.into());
+
+    assert!(key("apple pie") < key("banana split"));
+    assert_eq!(
+        key("apple pie").cmp(&key("apple pie")),
+        std::cmp::Ordering::Equal
+    );
+    assert!(key("apple pie") == String::from("apple pie"));
+    assert!(key("apple pie") != String::from("banana split"));
+
+    let mut map = BTreeMap::new();
+    map.insert(key("zebra crossing"), 3);
+    map.insert(key("apple pie"), 1);
+    map.insert(key("mango tree"), 2);
+
+    let owners: Vec<&str> = map
+        .keys()
+        .map(|cell| cell.borrow_owner().as_str())
+        .collect();
+    assert_eq!(owners, vec!["apple pie", "mango tree", "zebra crossing"]);
+    assert_eq!(map.get(&key("mango tree")), Some(&2));
+}
+
+#[test]
+fn ordered_generic_cells() {
+    type Slice<'a, T> = &'a [T];
+
+    self_cell!(
+        struct GenericCell<T> {
+            owner: Vec<T>,
+
+            #[covariant]
+            dependent: Slice<'_, T>,
+        }
+
+        impl {PartialEq, PartialOrd}
+    );
+
+    let a = GenericCell::new(vec![1.0, 2.0], |owner| &owner[..]);
+    let b = GenericCell::new(vec![1.0, 3.0], |owner| &owner[..]);
+    let nan = GenericCell::new(vec![f64::NAN], |owner| &owner[..]);
+
+    assert!(a < b);
+    assert!(a == vec![1.0, 2.0]);
+    assert_eq!(nan.partial_cmp(&a), None);
+}
+
+#[derive(Debug, PartialEq, Eq, Hash)]
+struct Normalized<'a> {
+    lowercase: bool,
+    words: Vec<&'a str>,
+}
+
+fn normalize(text: &str, lowercase: bool) -> Normalized<'_> {
+    Normalized {
+        lowercase,
+        words: text.split(' ').collect(),
+    }
+}
+
+#[test]
+fn dependent_eq_hash_modes() {
+    self_cell!(
+        struct DependentCell {
+            owner: String,
+
+            #[covariant]
+            dependent: Normalized,
+        }
+
+        impl {Debug, PartialEq(dependent), Eq(dependent), Hash(dependent)}
+    );
+
+    self_cell!(
+        struct BothCell {
+            owner: String,
+
+            #[covariant]
+            dependent: Normalized,
+        }
+
+        impl {Debug, PartialEq(both), Eq(both), Hash(both)}
+    );
+
+    self_cell!(
+        struct OwnerCell {
+            owner: String,
+
+            #[covariant]
+            dependent: Normalized,
+        }
+
+        impl {Debug, PartialEq(owner), Eq(owner), Hash(owner)}
+    );
+
+    let dependent_cell = |text: &str, lowercase| {
+        DependentCell::new(text.into(), |owner| normalize(owner, lowercase))
+    };
+    let both_cell =
+        |text: &str, lowercase| BothCell::new(text.into(), |owner| normalize(owner, lowercase));
+    let owner_cell =
+        |text: &str, lowercase| OwnerCell::new(text.into(), |owner| normalize(owner, lowercase));
+
+    assert_eq!(dependent_cell("a b", true), dependent_cell("a b", true));
+    assert_ne!(dependent_cell("a b", true), dependent_cell("a b", false));
+    assert_eq!(both_cell("a b", true), both_cell("a b", true));
+    assert_ne!(both_cell("a b", true), both_cell("a b", false));
+    assert_ne!(both_cell("a b", true), both_cell("a c", true));
+    assert_eq!(owner_cell("a b", true), owner_cell("a b", false));
+
+    let mut dependent_set = HashSet::new();
+    dependent_set.insert(dependent_cell("a b", true));
+    dependent_set.insert(dependent_cell("a b", false));
+    dependent_set.insert(dependent_cell("a b", true));
+    assert_eq!(dependent_set.len(), 2);
+
+    let mut both_set = HashSet::new();
+    both_set.insert(both_cell("a b", true));
+    both_set.insert(both_cell("a b", false));
+    both_set.insert(both_cell("a b", true));
+    assert_eq!(both_set.len(), 2);
+
+    let mut owner_set = HashSet::new();
+    owner_set.insert(owner_cell("a b", true));
+    owner_set.insert(owner_cell("a b", false));
+    assert_eq!(owner_set.len(), 1);
+}
+
+#[test]
+fn debug_options() {
+    type Header<'a> = &'a [u8];
+
+    self_cell!(
+        struct BufferCell {
+            owner: Vec<u8>,
+
+            #[covariant]
+            dependent: Header,
+        }
+
+        impl {Debug(owner_len)}
+    );
+
+    let buffer = BufferCell::new(vec![7; 1024], |owner| &owner[..2]);
+    assert_eq!(
+        format!("{:?}", buffer),
+        "BufferCell { owner_len: 1024, dependent: [7, 7] }"
+    );
+
+    self_cell!(
+        struct BorrowedAstCell<'a> {
+            owner: &'a String,
+
+            #[covariant]
+            dependent: Ast,
+        }
+
+        impl {Debug(dependent_only)}
+    );
+
+    let body = String::from("some longer string that ends now");
+    let borrowed = BorrowedAstCell::new(&body, |owner| Ast::from(*owner));
+    assert_eq!(
+        format!("{:?}", borrowed),
+        "BorrowedAstCell { dependent: Ast([\"me \", \"om\"]) }"
+    );
+    assert_eq!(
+        format!("{:#?}", borrowed),
+        "BorrowedAstCell {\n    dependent: Ast(\n        [\n            \"me \",\n            \"om\",\n        ],\n    ),\n}"
+    );
+
+    struct Opaque<'a>(&'a str);
+
+    self_cell!(
+        struct OpaqueCell {
+            #[owner(borrow = source, into = into_source)]
+            text: String,
+
+            #[covariant]
+            #[dependent(borrow = opaque, with = with_opaque, with_mut = with_opaque_mut)]
+            parsed: Opaque,
+        }
+
+        impl {Debug(owner_only)}
+    );
+
+    let opaque = OpaqueCell::new("abc".into(), |owner| Opaque(owner));
+    assert_eq!(opaque.opaque().0, "abc");
+    assert_eq!(format!("{:?}", opaque), "OpaqueCell { text: \"abc\" }");
+}
+
+#[derive(Debug, PartialEq)]
+struct UnexpectedToken<'a> {
+    token: &'a str,
+}
+
+struct UnexpectedTokenType;
+
+impl<'a> self_cell::BorrowedError<'a> for UnexpectedTokenType {
+    type Error = UnexpectedToken<'a>;
+}
+
+fn parse_words(text: &str) -> std::result::Result<Ast<'_>, UnexpectedToken<'_>> {
+    let words: Vec<&str> = text.split(' ').collect();
+    match words
+        .iter()
+        .find(|word| word.contains(|c: char| !c.is_alphabetic()))
+    {
+        Some(token) => std::result::Result::Err(UnexpectedToken { token }),
+        None => std::result::Result::Ok(Ast(words)),
+    }
+}
+
+#[test]
+fn try_new_borrowed_err() {
+    let ast_cell =
+        PackedAstCell::try_new_borrowed_err::<UnexpectedTokenType>("fox cat dog".into(), |owner| {
+            parse_words(owner)
+        })
+        .unwrap();
+    assert_eq!(ast_cell.borrow_dependent(), &Ast(vec!["fox", "cat", "dog"]));
+
+    let err_cell =
+        PackedAstCell::try_new_borrowed_err::<UnexpectedTokenType>("fox c4t dog".into(), |owner| {
+            parse_words(owner)
+        })
+        .err()
+        .unwrap();
+
+    assert_eq!(err_cell.borrow_owner(), "fox c4t dog");
+    err_cell.with_error(|owner, err| {
+        assert_eq!(err, &UnexpectedToken { token: "c4t" });
+        assert_eq!(err.token.as_ptr(), owner[4..].as_ptr());
+    });
+    assert_eq!(
+        format!("{:?}", err_cell),
+        "ErrorCell { owner: \"fox c4t dog\", error: UnexpectedToken { token: \"c4t\" } }"
+    );
+
+    // Moving the error cell keeps the error valid.
+    let moved = Box::new(err_cell);
+    moved.with_error(|_, err| assert_eq!(err.token, "c4t"));
+    assert_eq!(moved.into_owner(), "fox c4t dog");
+}
+
+#[test]
+fn try_new_borrowed_err_drop() {
+    let owner = Rc::new(String::from("a 1"));
+
+    type RcAst<'a> = Vec<&'a str>;
+
+    self_cell!(
+        struct RcAstCell {
+            owner: Rc<String>,
+
+            #[covariant]
+            dependent: RcAst,
+        }
+    );
+
+    struct TokenType;
+
+    impl<'a> self_cell::BorrowedError<'a> for TokenType {
+        type Error = (&'a str, Rc<String>);
+    }
+
+    let err_cell = RcAstCell::try_new_borrowed_err::<TokenType>(owner.clone(), |owner| {
+        std::result::Result::Err((&owner[2..], owner.clone()))
+    })
+    .err()
+    .unwrap();
+
+    assert_eq!(Rc::strong_count(&owner), 3);
+    err_cell.with_error(|_, err| assert_eq!(err.0, "1"));
+    drop(err_cell);
+    assert_eq!(Rc::strong_count(&owner), 1);
+}
+
+#[test]
+fn display_forwards_to_dependent() {
+    struct Greeting<'a>(&'a str);
+
+    impl std::fmt::Display for Greeting<'_> {
+        fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+            write!(f, "hello {}", self.0)
+        }
+    }
+
+    self_cell!(
+        struct GreetingCell {
+            owner: String,
+
+            #[covariant]
+            dependent: Greeting,
+        }
+
+        impl {Display}
+    );
+
+    let cell = GreetingCell::new("world".into(), |owner| Greeting(owner));
+    assert_eq!(cell.to_string(), "hello world");
+    assert_eq!(format!("{:>5}|", cell), "hello world|");
+}